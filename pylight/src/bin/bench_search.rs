@@ -1,20 +1,16 @@
 use anyhow::Result;
 use clap::Parser as ClapParser;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use std::collections::HashSet;
 use std::io::Write;
 use tracing::info;
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
-use flate2::read::GzDecoder;
-use std::io::BufReader;
-use std::fs::File;
-use bincode;
 use symbol_experiments::symbols::{
-    Symbol, SymbolData, PathRegistry
+    Symbol, PathRegistry
 };
-use symbol_experiments::search::{search_symbols, SearchAlgorithm, SearchMetrics};
+use symbol_experiments::index::SymbolIndex;
+use symbol_experiments::search::{search_symbols, SearchAlgorithm};
 
 #[derive(ClapParser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -32,38 +28,9 @@ struct Args {
     iterations: usize,
 }
 
-fn load_symbols_from_file(path: &PathBuf) -> Result<(HashSet<Symbol>, HashSet<Symbol>, PathRegistry)> {
-    info!("Loading symbols from {}...", path.display());
-    
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let decoder = GzDecoder::new(reader);
-    
-    info!("Starting deserialization...");
-    let data: SymbolData = bincode::deserialize_from(decoder)?;
-    
-    info!("Converting to symbol collections...");
-    let (functions, classes, paths) = data.into_symbols();
-    
-    // Create a new PathRegistry and populate it with the exact same paths in the same order
-    let mut path_registry = PathRegistry::new();
-    
-    // First register all paths to ensure they have the same indices as when saved
-    for (i, path) in paths.iter().enumerate() {
-        let index = path_registry.register_path(path.clone());
-        // Sanity check - make sure the index matches the position
-        assert_eq!(index, i, "Path registry indexing error: expected index {} for path {}, got {}", 
-                  i, path.display(), index);
-    }
-    
-    info!("Load complete! Loaded {} functions, {} classes, and {} paths", 
-          functions.len(), classes.len(), paths.len());
-
-    Ok((
-        functions.into_iter().collect(),
-        classes.into_iter().collect(),
-        path_registry
-    ))
+fn load_symbols_from_file(path: &Path) -> Result<(HashSet<Symbol>, HashSet<Symbol>, PathRegistry)> {
+    let index = SymbolIndex::load(path)?;
+    Ok((index.functions, index.classes, index.registry))
 }
 
 /// Run a benchmark for a single query using both algorithms
@@ -1,59 +1,455 @@
 use anyhow::Result;
 use clap::Parser as ClapParser;
+use std::collections::HashSet;
 use std::path::PathBuf;
-use std::time::Instant;
-use tracing::info;
-use tracing_subscriber;
+use std::time::{Duration, Instant};
+use tracing::{info, info_span};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::EnvFilter;
-use symbol_experiments::symbols::{SymbolStats, Symbol, save_symbols};
-use symbol_experiments::files::list_python_files;
-use symbol_experiments::python::parse_python_files_parallel;
+use symbol_experiments::chrome_trace::chrome_trace_layer;
+use symbol_experiments::symbols::{
+    SymbolStats, Symbol, IndexCompression, save_ctags, save_symbols_with_base_dir, read_index_metadata,
+};
+use symbol_experiments::index::SymbolIndex;
+use symbol_experiments::files::{
+    canonicalize_paths, dedup_symlinked_paths, list_source_files_with_errors, read_file_list, scan_layout,
+};
+use symbol_experiments::python::{
+    count_symbols_parallel, find_index_drift, ignore_names_filter, parse_python_files_parallel_with_options,
+    read_ignore_names, CollectOptions, TestPatterns, DEFAULT_GENERATED_MARKERS, DEFAULT_IO_RETRIES,
+    MAX_IO_RETRIES,
+};
+use std::path::Path;
 use std::mem;
 
+/// Format used when saving symbols
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Symbol index that the search tools and LSP server can load
+    Bincode,
+    /// Exuberant-ctags-compatible tags file for Vim/Emacs
+    Ctags,
+}
+
 #[derive(ClapParser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Directory to scan
-    #[arg(short, long)]
-    directory: PathBuf,
+    /// Directory to scan (also used as the base for module names), or a single .py/.pyi file.
+    /// Repeat to scan several in one pass, each naming its modules as if scanned alone
+    #[arg(short, long, default_value = ".")]
+    directory: Vec<PathBuf>,
 
     /// Whether to follow symbolic links
     #[arg(short, long)]
     follow_links: bool,
 
-    /// Save symbols to this file
+    /// Store absolute, canonicalized file paths so a saved index opens from any working
+    /// directory. The index is then tied to this machine's directory layout.
+    #[arg(long)]
+    absolute_paths: bool,
+
+    /// Index a file once when several discovered paths (e.g. a symlink and its target)
+    /// resolve to it. Costs a canonicalize call per file
+    #[arg(long)]
+    dedup_symlinks: bool,
+
+    /// Save symbols to this file (a `.bin` extension saves uncompressed)
     #[arg(short, long)]
     save: Option<PathBuf>,
+
+    /// Save symbols as raw bincode without gzip, for faster loads from local disk
+    #[arg(long)]
+    no_compress: bool,
+
+    /// Format to save symbols in; ctags saves to `tags` unless --save is given
+    #[arg(long, value_enum, default_value = "bincode")]
+    format: OutputFormat,
+
+    /// Read newline-separated files to index from this file ('-' for stdin) instead of scanning
+    #[arg(long)]
+    files_from: Option<PathBuf>,
+
+    /// Only count function and class definitions, without building symbols
+    #[arg(long)]
+    count_only: bool,
+
+    /// Write files that could not be fully indexed to this file as JSON
+    #[arg(long)]
+    report_issues: Option<PathBuf>,
+
+    /// Print parse statistics (symbol, error and file counts) to stdout as JSON
+    #[arg(long)]
+    stats_json: bool,
+
+    /// Only log warnings and errors. Caps whatever RUST_LOG asks for, so `RUST_LOG=info --quiet`
+    /// logs warnings; a stricter RUST_LOG still applies
+    #[arg(long)]
+    quiet: bool,
+
+    /// List files that parsed but yielded no symbols, which may point to encoding or syntax
+    /// problems (files with only imports, such as most `__init__.py`, show up here too)
+    #[arg(long)]
+    list_empty_files: bool,
+
+    /// Print the files with a module-level `if __name__ == "__main__":` block to stdout, one
+    /// per line, instead of the usual summary. Saved indexes record them as well
+    #[arg(long, conflicts_with = "stats_json")]
+    list_entrypoints: bool,
+
+    /// Number of threads to parse with (defaults to the global rayon pool)
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Also index module-level `Name = factory(...)` assignments with CamelCase names as
+    /// classes (a naming heuristic; see `CollectOptions::dynamic_class_aliases`)
+    #[arg(long)]
+    dynamic_classes: bool,
+
+    /// Also index Python 3.12 `type X = ...` aliases
+    #[arg(long)]
+    type_aliases: bool,
+
+    /// Skip functions and classes whose names start with '_' (except __init__)
+    #[arg(long)]
+    public_only: bool,
+
+    /// Leave out symbols named in this file, one name per line ('#' starts a comment line).
+    /// A name matches exactly and case-sensitively unless it uses the wildcards '*' (any
+    /// characters) or '?' (one character), e.g. `test_*`. Methods of an excluded class are kept
+    #[arg(long, value_name = "FILE")]
+    ignore_names: Option<PathBuf>,
+
+    /// Leave methods and nested functions out of the index, keeping only module-level
+    /// functions and classes
+    #[arg(long)]
+    no_methods: bool,
+
+    /// Index only test code: symbols named `test_*` or `Test*`, anything defined inside
+    /// them, and everything in files under a `tests/` or `test/` directory
+    #[arg(long)]
+    tests_only: bool,
+
+    /// Name pattern marking test code for --tests-only, replacing the defaults
+    /// (repeatable; '*' and '?' are wildcards)
+    #[arg(long, value_name = "PATTERN", requires = "tests_only")]
+    test_name: Vec<String>,
+
+    /// Stop parsing new files after this many milliseconds, keeping a partial index
+    #[arg(long, value_name = "MS")]
+    time_budget: Option<u64>,
+
+    /// Give up on any single file whose parse takes longer than this many milliseconds,
+    /// counting it as a parse timeout instead of letting it stall the run
+    #[arg(long, value_name = "MS")]
+    parse_timeout_ms: Option<u64>,
+
+    /// Keep at most this many symbols, dropping the most deeply nested ones first (then
+    /// methods) so module-level functions and classes survive longest
+    #[arg(long, value_name = "N")]
+    max_symbols: Option<usize>,
+
+    /// Extra attempts at reading a file after a transient I/O error (e.g. on network filesystems), at most 10
+    #[arg(long, default_value_t = DEFAULT_IO_RETRIES, value_parser = clap::value_parser!(u32).range(0..=MAX_IO_RETRIES as i64))]
+    io_retries: u32,
+
+    /// Store the first few source lines of each definition for previews (makes the index larger)
+    #[arg(long)]
+    capture_snippets: bool,
+
+    /// Keep only the nearest N enclosing classes/functions per symbol, to shrink indexes of
+    /// deeply nested code (editors show a leading "…" for the dropped ones)
+    #[arg(long, value_name = "N")]
+    max_parents: Option<usize>,
+
+    /// Also index Cython `.pyx` files, best-effort: they are parsed as Python, so `def`
+    /// functions and `class`es are found but `cdef`/`cpdef` declarations are not. Implies
+    /// recovering definitions from syntax errors in every file
+    #[arg(long)]
+    cython: bool,
+
+    /// Name modules relative to this directory instead of the scanned one for files under it,
+    /// e.g. `--source-root src` for a src layout. Relative to each scanned directory; repeatable
+    #[arg(long, value_name = "DIR")]
+    source_root: Vec<PathBuf>,
+
+    /// Only treat directories with an `__init__.py` as packages when naming modules, instead
+    /// of every directory between the scanned one and the file
+    #[arg(long)]
+    require_init: bool,
+
+    /// Skip generated files: those with a comment in their first few lines containing
+    /// "@generated" or "DO NOT EDIT", or the markers given with --generated-marker
+    #[arg(long)]
+    skip_generated: bool,
+
+    /// Header text that marks a file as generated, replacing the defaults; repeatable.
+    /// Implies --skip-generated
+    #[arg(long, value_name = "MARKER")]
+    generated_marker: Vec<String>,
+
+    /// Check this saved index against the current source instead of indexing: re-parse each
+    /// indexed file, report files whose symbols were added, removed or moved, and exit nonzero
+    /// if any have drifted. Pass the same collection flags the index was built with.
+    #[arg(long, value_name = "INDEX")]
+    validate: Option<PathBuf>,
+
+    /// Print a saved index's version, save time, base directory and symbol counts by kind,
+    /// without indexing. Indexes from before per-kind counts were stored are loaded in full
+    #[arg(long, value_name = "INDEX")]
+    index_info: Option<PathBuf>,
+
+    /// Record indexing spans to a Chrome trace JSON file (default: trace.json),
+    /// viewable in chrome://tracing or ui.perfetto.dev
+    #[arg(long, num_args = 0..=1, default_missing_value = "trace.json")]
+    profile: Option<PathBuf>,
+}
+
+/// Compare a saved index with the source it was built from, returning whether they differ
+fn validate_index(index_path: &Path, base_dir: &Path, options: &CollectOptions) -> Result<bool> {
+    let index = SymbolIndex::load(index_path)?;
+    let path_registry = &index.registry;
+
+    let drift = find_index_drift(&index.functions, &index.classes, path_registry, base_dir, options);
+    for file in &drift {
+        println!("{}", file.path.display());
+        if let Some(error) = &file.error {
+            println!("  error: {}", error);
+        }
+        for name in &file.added {
+            println!("  + {}", name);
+        }
+        for name in &file.removed {
+            println!("  - {}", name);
+        }
+        for name in &file.moved {
+            println!("  ~ {}", name);
+        }
+    }
+    println!("{} of {} indexed files have drifted", drift.len(), path_registry.paths.len());
+    Ok(!drift.is_empty())
 }
 
 fn main() -> Result<()> {
-    // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(EnvFilter::from_default_env())
-        .init();
-    
     // Parse command line arguments
     let args = Args::parse();
+    
+    // Initialize tracing; RUST_LOG and --quiet only filter log output, the profile records every span
+    let (profile_layer, _profile_guard) = match &args.profile {
+        Some(path) => {
+            let (layer, guard) = chrome_trace_layer(path);
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_filter(EnvFilter::from_default_env())
+                .with_filter(if args.quiet { LevelFilter::WARN } else { LevelFilter::TRACE }),
+        )
+        .with(profile_layer)
+        .init();
+    
     let start = Instant::now();
     
-    info!("Collecting Python files from {}", args.directory.display());
-    let files: Vec<_> = list_python_files(&args.directory, args.follow_links).collect();
-    info!("Found {} Python files in {}ms", files.len(), start.elapsed().as_millis());
+    // Module names are relative to the scan directories, so they must be resolved like the files
+    let directories = if args.absolute_paths {
+        args.directory.iter().map(|directory| directory.canonicalize()).collect::<Result<Vec<_>, _>>()?
+    } else {
+        args.directory.clone()
+    };
+    let (base_dir, source_roots) = scan_layout(&directories, &args.source_root);
+    
+    let options = CollectOptions {
+        threads: args.threads,
+        dynamic_class_aliases: args.dynamic_classes,
+        type_aliases: args.type_aliases,
+        public_only: args.public_only,
+        skip_methods: args.no_methods,
+        tests_only: args.tests_only.then(|| {
+            let mut patterns = TestPatterns::default();
+            if !args.test_name.is_empty() {
+                patterns.names = args.test_name.clone();
+            }
+            patterns
+        }),
+        time_budget: args.time_budget.map(Duration::from_millis),
+        cancel: None,
+        parse_timeout: args.parse_timeout_ms.map(Duration::from_millis),
+        max_symbols: args.max_symbols,
+        capture_snippet: args.capture_snippets,
+        io_retries: Some(args.io_retries),
+        max_parents: args.max_parents,
+        lenient: args.cython,
+        filter: args.ignore_names.as_deref().map(read_ignore_names).transpose()?.map(ignore_names_filter),
+        source_roots,
+        packages_require_init: args.require_init,
+        detect_main: args.list_entrypoints,
+        generated_markers: if !args.generated_marker.is_empty() {
+            args.generated_marker.clone()
+        } else if args.skip_generated {
+            DEFAULT_GENERATED_MARKERS.iter().map(|marker| marker.to_string()).collect()
+        } else {
+            Vec::new()
+        },
+    };
+    
+    if let Some(index_path) = &args.index_info {
+        let metadata = read_index_metadata(index_path)?;
+        println!("Version: {}", metadata.version);
+        match metadata.created_at {
+            Some(created_at) => println!("Created: {} (Unix time)", created_at),
+            None => println!("Created: unknown"),
+        }
+        match &metadata.base_dir {
+            Some(base_dir) => println!("Base directory: {}", base_dir.display()),
+            None => println!("Base directory: unknown"),
+        }
+        println!("Files: {}", metadata.files);
+        println!("Symbols: {}", metadata.symbols());
+        for (kind, count) in &metadata.kind_counts {
+            println!("  {:?}: {}", kind, count);
+        }
+        return Ok(());
+    }
+    
+    if let Some(index_path) = &args.validate {
+        if validate_index(index_path, &base_dir, &options)? {
+            eprintln!("Index {} is out of date", index_path.display());
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    
+    let discovery_span = info_span!("discover_files").entered();
+    let files: Vec<_> = if let Some(files_from) = &args.files_from {
+        info!("Reading file list from {}", files_from.display());
+        let files = read_file_list(files_from)?;
+        info!("Read {} files in {}ms", files.len(), start.elapsed().as_millis());
+        files
+    } else {
+        let mut files = Vec::new();
+        let mut errors = Vec::new();
+        for directory in &directories {
+            info!("Collecting Python files from {}", directory.display());
+            let (found, found_errors) = list_source_files_with_errors(directory, args.follow_links, args.cython);
+            files.extend(found);
+            errors.extend(found_errors);
+        }
+        // Overlapping directories would otherwise list the same files twice
+        let mut seen = HashSet::new();
+        files.retain(|file| seen.insert(file.clone()));
+        info!("Found {} Python files in {}ms", files.len(), start.elapsed().as_millis());
+        
+        // Missing symbols are confusing without knowing that part of the tree was unreadable,
+        // so report this even when logging is off
+        let permission_errors = errors.iter().filter(|e| e.permission_denied).count();
+        if permission_errors > 0 {
+            eprintln!("Warning: skipped {} directories due to permission errors", permission_errors);
+        }
+        if errors.len() > permission_errors {
+            eprintln!("Warning: skipped {} entries due to other discovery errors", errors.len() - permission_errors);
+        }
+        for error in &errors {
+            info!("Discovery error: {}", error.message);
+        }
+        if files.is_empty() {
+            let directories: Vec<_> = directories.iter().map(|directory| directory.display().to_string()).collect();
+            eprintln!("Warning: no Python files found in {}", directories.join(", "));
+        }
+        files
+    };
+    let files = if args.dedup_symlinks {
+        let found = files.len();
+        let files = dedup_symlinked_paths(files);
+        info!("Dropped {} paths resolving to an already listed file", found - files.len());
+        files
+    } else {
+        files
+    };
+    let files = if args.absolute_paths {
+        canonicalize_paths(files)
+    } else {
+        files
+    };
+    drop(discovery_span);
+    
+    if args.count_only {
+        info!("Counting definitions in parallel...");
+        let count_start = Instant::now();
+        let (num_functions, num_classes) = count_symbols_parallel(&files);
+        info!("Counting complete in {}ms", count_start.elapsed().as_millis());
+        println!("Functions: {}", num_functions);
+        println!("Classes: {}", num_classes);
+        info!("Total time: {}ms", start.elapsed().as_millis());
+        return Ok(());
+    }
     
     info!("Parsing Python files in parallel...");
     let parse_start = Instant::now();
-    let stats = SymbolStats::new();
+    let stats = if args.report_issues.is_some() {
+        SymbolStats::with_parse_issues()
+    } else {
+        SymbolStats::new()
+    };
     
-    parse_python_files_parallel(&files, &args.directory, &stats)?;
+    info_span!("parse_files", files = files.len()).in_scope(|| {
+        parse_python_files_parallel_with_options(&files, &base_dir, &stats, &options)
+    })?;
     
     let (num_functions, num_classes, syntax_errors, io_errors, other_errors) = stats.get_counts();
     
-    info!("Parsing complete in {}ms", parse_start.elapsed().as_millis());
+    let parse_time = parse_start.elapsed();
+    info!("Parsing complete in {}ms", parse_time.as_millis());
+    // How much of the available parallelism the parse used, to tell whether more cores would help
+    let threads = options.threads.unwrap_or_else(rayon::current_num_threads);
+    let busy_time = stats.parse_busy_time();
+    let speedup = busy_time.as_secs_f64() / parse_time.as_secs_f64().max(f64::EPSILON);
+    info!("Parse CPU time {}ms across {} threads: {:.2}x speedup, {:.0}% efficiency",
+          busy_time.as_millis(), threads, speedup, 100.0 * speedup / threads as f64);
     info!("Found {} functions and {} classes", num_functions, num_classes);
     info!("Errors: {} syntax, {} I/O, {} other", syntax_errors, io_errors, other_errors);
+    info!("{} files yielded no symbols", stats.snapshot().empty_files);
+    if !options.generated_markers.is_empty() {
+        info!("Skipped {} generated files", stats.snapshot().generated_files);
+    }
+    if options.parse_timeout.is_some() {
+        info!("{} files timed out while parsing", stats.snapshot().parse_timeouts);
+    }
+    if options.max_symbols.is_some() {
+        info!("Dropped {} symbols to stay within --max-symbols", stats.snapshot().pruned_symbols);
+    }
+    if args.list_empty_files {
+        // Keep stdout parseable when it carries the JSON stats
+        for path in stats.empty_files() {
+            eprintln!("No symbols: {}", path.display());
+        }
+    }
+    if args.list_entrypoints {
+        for path in stats.entry_points() {
+            println!("{}", path.display());
+        }
+    }
+    if stats.is_truncated() {
+        eprintln!("Warning: time budget ran out, so the index only covers some of the files");
+    }
+    if args.stats_json {
+        println!("{}", serde_json::to_string_pretty(&stats.snapshot())?);
+    }
+    
+    if let Some(report_path) = &args.report_issues {
+        let issues = stats.parse_issues();
+        let file = std::fs::File::create(report_path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), &issues)?;
+        info!("Wrote {} parse issues to {}", issues.len(), report_path.display());
+    }
     
-    println!("Memory usage for functions: {} bytes", mem::size_of::<Symbol>() * num_functions);
-    println!("Memory usage for classes: {} bytes", mem::size_of::<Symbol>() * num_classes);
+    // Keep stdout parseable when it carries the JSON stats or the entry points
+    if !args.stats_json && !args.list_entrypoints {
+        println!("Memory usage for functions: {} bytes", mem::size_of::<Symbol>() * num_functions);
+        println!("Memory usage for classes: {} bytes", mem::size_of::<Symbol>() * num_classes);
+    }
     
     // Print path registry stats
     let path_registry = stats.path_registry.lock().unwrap();
@@ -61,11 +457,24 @@ fn main() -> Result<()> {
     info!("Total path storage: {} bytes", path_registry.total_path_bytes());
     drop(path_registry);
     
-    // Save symbols if requested
-    if let Some(path) = &args.save {
+    if args.format == OutputFormat::Ctags {
+        let path = args.save.clone().unwrap_or_else(|| PathBuf::from("tags"));
+        let save_start = Instant::now();
+        info!("Writing ctags to {}...", path.display());
+        info_span!("save_index").in_scope(|| save_ctags(&path, &stats))?;
+        info!("Save complete in {}ms", save_start.elapsed().as_millis());
+    } else if let Some(path) = &args.save {
+        // Save symbols if requested
         let save_start = Instant::now();
-        info!("Saving symbols to {}...", path.display());
-        save_symbols(path, &stats)?;
+        let compression = if args.no_compress {
+            IndexCompression::None
+        } else {
+            IndexCompression::from_path(path)
+        };
+        info!("Saving symbols to {} ({:?})...", path.display(), compression);
+        info_span!("save_index").in_scope(|| {
+            save_symbols_with_base_dir(path, &stats, compression, Some(&base_dir))
+        })?;
         info!("Save complete in {}ms", save_start.elapsed().as_millis());
     }
     
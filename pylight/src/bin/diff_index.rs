@@ -0,0 +1,58 @@
+use anyhow::Result;
+use clap::Parser as ClapParser;
+use std::path::PathBuf;
+use symbol_experiments::python::diff_indexes;
+use symbol_experiments::index::SymbolIndex;
+
+/// Report the symbols added, removed and moved between two saved indexes
+#[derive(ClapParser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// The earlier index
+    old: PathBuf,
+
+    /// The later index
+    new: PathBuf,
+
+    /// Print the changed files as a JSON array instead of a summary
+    #[arg(long)]
+    json: bool,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let old = SymbolIndex::load(&args.old)?;
+    let old_symbols: Vec<_> = old.symbols().cloned().collect();
+    let new = SymbolIndex::load(&args.new)?;
+    let new_symbols: Vec<_> = new.symbols().cloned().collect();
+
+    let diff = diff_indexes(&old_symbols, &old.registry.paths, &new_symbols, &new.registry.paths);
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&diff)?);
+        return Ok(());
+    }
+
+    for file in &diff {
+        println!("{}", file.path.display());
+        for name in &file.added {
+            println!("  + {}", name);
+        }
+        for name in &file.removed {
+            println!("  - {}", name);
+        }
+        for name in &file.moved {
+            println!("  ~ {}", name);
+        }
+    }
+    let count = |f: fn(&_) -> usize| diff.iter().map(f).sum::<usize>();
+    println!(
+        "{} added, {} removed, {} moved across {} files",
+        count(|f| f.added.len()),
+        count(|f| f.removed.len()),
+        count(|f| f.moved.len()),
+        diff.len()
+    );
+    Ok(())
+}
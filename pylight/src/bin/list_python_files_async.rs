@@ -1,11 +1,10 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use async_walkdir::{Filtering, WalkDir};
 use clap::Parser as ClapParser;
 use futures_lite::StreamExt;
 use std::path::PathBuf;
 use std::time::Instant;
 use tracing::{debug, info};
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 
 #[derive(ClapParser, Debug, Clone)]
@@ -63,7 +62,7 @@ async fn main() -> Result<()> {
                 
                 // For files, only process Python files
                 if path.is_file() {
-                    if path.extension().map_or(false, |ext| ext == "py") {
+                    if path.extension().is_some_and(|ext| ext == "py") {
                         return Filtering::Continue;
                     }
                     return Filtering::Ignore;
@@ -77,7 +76,7 @@ async fn main() -> Result<()> {
     while let Some(entry) = entries.next().await {
         if let Ok(entry) = entry {
             let path = entry.path();
-            if path.is_file() && path.extension().map_or(false, |ext| ext == "py") {
+            if path.is_file() && path.extension().is_some_and(|ext| ext == "py") {
                 debug!("Found Python file: {}", path.display());
                 total_files += 1;
             }
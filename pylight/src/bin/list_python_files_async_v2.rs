@@ -1,9 +1,8 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use clap::Parser as ClapParser;
 use std::path::PathBuf;
 use std::time::Instant;
 use tracing::{debug, info};
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 use async_walkdir::{Filtering, WalkDir};
 use futures_lite::future::block_on;
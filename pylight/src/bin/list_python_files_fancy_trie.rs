@@ -2,7 +2,6 @@ use anyhow::Result;
 use clap::Parser as ClapParser;
 use std::time::Instant;
 use tracing::{debug, info};
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 use symbol_experiments::files::list_python_files_with_depth;
 use std::thread;
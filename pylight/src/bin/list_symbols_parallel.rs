@@ -8,7 +8,6 @@ use std::collections::HashSet;
 use tracing::{info, debug};
 use tree_sitter::{Parser, Node};
 use rayon::prelude::*;
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 
 #[derive(ClapParser, Debug)]
@@ -83,10 +82,8 @@ fn collect_symbols(node: Node, source: &str) -> (Vec<String>, Vec<String>) {
             _ => {}
         }
 
-        if !cursor.goto_next_sibling() {
-            if !cursor.goto_parent() {
-                break;
-            }
+        if !cursor.goto_next_sibling() && !cursor.goto_parent() {
+            break;
         }
     }
 
@@ -139,7 +136,7 @@ fn main() -> Result<()> {
             debug!("Scanned {} entries so far...", entries_processed);
         }
 
-        if entry.path().extension().map_or(false, |ext| ext == "py") {
+        if entry.path().extension().is_some_and(|ext| ext == "py") {
             python_files.push(entry.path().to_path_buf());
         }
     }
@@ -7,7 +7,6 @@ use std::time::Instant;
 use tracing::{info, debug};
 use tree_sitter::Parser;
 use rayon::prelude::*;
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 
 #[derive(ClapParser, Debug)]
@@ -95,7 +94,7 @@ fn main() -> Result<()> {
             debug!("Scanned {} entries so far...", entries_processed);
         }
 
-        if entry.path().extension().map_or(false, |ext| ext == "py") {
+        if entry.path().extension().is_some_and(|ext| ext == "py") {
             python_files.push(entry.path().to_path_buf());
         }
     }
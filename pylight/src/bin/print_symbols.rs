@@ -1,24 +1,24 @@
 use anyhow::Result;
+use std::io::{self, Write};
 use clap::Parser as ClapParser;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use tracing::info;
-use tracing_subscriber;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
-use flate2::read::GzDecoder;
-use std::io::BufReader;
-use std::fs::File;
-use bincode;
 use symbol_experiments::symbols::{
-    Symbol, SymbolStats, SymbolData, PathRegistry
+    Symbol, SymbolStats, PathRegistry, load_symbol_data, write_symbol_json_line
 };
-use symbol_experiments::files::list_python_files;
-use symbol_experiments::python::parse_python_files_parallel;
+use symbol_experiments::files::{list_python_files, scan_base_dir};
+use symbol_experiments::python::{parse_python_files_parallel, parse_python_files_streaming};
+use symbol_experiments::search::{should_colorize, symbol_color, symbol_color_legend};
+use crossterm::style::Stylize;
 
 #[derive(ClapParser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Directory to scan (defaults to current directory)
+    /// Directory to scan (defaults to current directory), or a single .py/.pyi file
     #[arg(short, long, default_value = ".")]
     directory: PathBuf,
 
@@ -29,17 +29,27 @@ struct Args {
     /// Load symbols from this file instead of scanning directory
     #[arg(short, long)]
     symbols_file: Option<PathBuf>,
+
+    /// Disable colored output (also disabled when stdout is not a terminal)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Only log warnings and errors. Caps whatever RUST_LOG asks for, so `RUST_LOG=info --quiet`
+    /// logs warnings; a stricter RUST_LOG still applies
+    #[arg(long)]
+    quiet: bool,
+
+    /// Print one JSON object per symbol per line (name, kind, file, line, module). Symbols
+    /// are written as they are parsed, for streaming into other tools.
+    #[arg(long)]
+    jsonl: bool,
 }
 
-fn load_symbols_from_file(path: &PathBuf) -> Result<(Vec<Symbol>, Vec<Symbol>, PathRegistry)> {
+fn load_symbols_from_file(path: &Path) -> Result<(Vec<Symbol>, Vec<Symbol>, PathRegistry)> {
     info!("Loading symbols from {}...", path.display());
     
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let decoder = GzDecoder::new(reader);
-    
     info!("Starting deserialization...");
-    let data: SymbolData = bincode::deserialize_from(decoder)?;
+    let data = load_symbol_data(path)?;
     
     info!("Converting to symbol collections...");
     let (functions, classes, paths) = data.into_symbols();
@@ -61,25 +71,56 @@ fn load_symbols_from_file(path: &PathBuf) -> Result<(Vec<Symbol>, Vec<Symbol>, P
     Ok((functions, classes, path_registry))
 }
 
-fn print_symbols(symbols: &[Symbol], path_registry: &PathRegistry) {
+fn print_symbols(symbols: &[Symbol], path_registry: &PathRegistry, color: bool) {
     for symbol in symbols {
         let path = path_registry.get_path(symbol.context.file_path_index);
-        println!("{:?}: {} ({}:{})", 
-                 symbol.context.symbol_type, 
-                 symbol.name, 
-                 path.display(), 
-                 symbol.context.line_number);
+        let kind = format!("{:?}", symbol.context.symbol_type);
+        if color {
+            let symbol_color = symbol_color(&symbol.context.symbol_type);
+            println!("{}: {} ({}:{})", 
+                     kind.with(symbol_color), 
+                     symbol.name.as_str().with(symbol_color).bold(), 
+                     path.display(), 
+                     symbol.context.line_number);
+        } else {
+            println!("{}: {} ({}:{})", 
+                     kind, 
+                     symbol.name, 
+                     path.display(), 
+                     symbol.context.line_number);
+        }
+    }
+}
+
+/// Write symbols as JSON lines, stopping quietly if the reader goes away (e.g. `| head`)
+fn print_symbols_jsonl(symbols: impl IntoIterator<Item = Symbol>, path_registry: &PathRegistry) -> Result<()> {
+    // Stdout is line buffered, so each symbol reaches the reader as soon as it is written
+    let mut stdout = io::stdout().lock();
+    for symbol in symbols {
+        match write_symbol_json_line(&mut stdout, &symbol, path_registry) {
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(()),
+            result => result?,
+        }
     }
+    stdout.flush().or_else(|e| if e.kind() == io::ErrorKind::BrokenPipe { Ok(()) } else { Err(e) })?;
+    Ok(())
 }
 
 fn main() -> Result<()> {
+    let args = Args::parse();
+
     // Initialize tracing
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
+        .finish()
+        .with(if args.quiet { LevelFilter::WARN } else { LevelFilter::TRACE })
         .init();
     
-    let args = Args::parse();
     let start = Instant::now();
+    let color = !args.jsonl && should_colorize(args.no_color);
+    if color {
+        println!("{}", symbol_color_legend());
+    }
     
     // Decide whether to load from file or scan directory
     if let Some(symbols_file) = &args.symbols_file {
@@ -88,12 +129,17 @@ fn main() -> Result<()> {
         let (functions, classes, path_registry) = load_symbols_from_file(symbols_file)?;
         
         info!("Loaded {} functions and {} classes", functions.len(), classes.len());
+        let total = functions.len() + classes.len();
         
         // Print all symbols, one per line
-        print_symbols(&functions, &path_registry);
-        print_symbols(&classes, &path_registry);
+        if args.jsonl {
+            print_symbols_jsonl(functions.into_iter().chain(classes), &path_registry)?;
+        } else {
+            print_symbols(&functions, &path_registry, color);
+            print_symbols(&classes, &path_registry, color);
+        }
         
-        info!("Listed {} total symbols", functions.len() + classes.len());
+        info!("Listed {} total symbols", total);
     } else {
         // Scan directory
         info!("Scanning directory: {}", args.directory.display());
@@ -102,11 +148,23 @@ fn main() -> Result<()> {
         let python_files: Vec<_> = list_python_files(&args.directory, args.follow_links).collect();
         info!("Found {} Python files", python_files.len());
         
+        if args.jsonl {
+            // Stream symbols as files finish parsing instead of collecting them all first
+            let mut path_registry = PathRegistry::new();
+            for path in &python_files {
+                path_registry.register_path(path.clone());
+            }
+            let symbols = parse_python_files_streaming(&python_files, scan_base_dir(&args.directory));
+            print_symbols_jsonl(symbols, &path_registry)?;
+            info!("Processing complete in {}ms", start.elapsed().as_millis());
+            return Ok(());
+        }
+        
         // Parse Python files and collect symbols
         let stats = SymbolStats::new();
         
         // Process files and print symbols as they're found
-        parse_python_files_parallel(&python_files, &args.directory, &stats)?;
+        parse_python_files_parallel(&python_files, scan_base_dir(&args.directory), &stats)?;
         
         // Get final counts
         let functions = stats.functions.lock().unwrap();
@@ -114,8 +172,8 @@ fn main() -> Result<()> {
         let path_registry = stats.path_registry.lock().unwrap();
         
         // Print all symbols to ensure complete output
-        print_symbols(&functions.iter().cloned().collect::<Vec<_>>(), &path_registry);
-        print_symbols(&classes.iter().cloned().collect::<Vec<_>>(), &path_registry);
+        print_symbols(&functions.iter().cloned().collect::<Vec<_>>(), &path_registry, color);
+        print_symbols(&classes.iter().cloned().collect::<Vec<_>>(), &path_registry, color);
         
         info!("Found and listed {} functions and {} classes", 
              functions.len(), classes.len());
@@ -1,18 +1,20 @@
-use anyhow::{Context, Result};
+use anyhow::Result;
 use clap::Parser as ClapParser;
 use std::path::PathBuf;
 use std::time::Instant;
 use tokio::fs;
-use tokio::task;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinSet;
 use tracing::{debug, info};
-use tracing_subscriber;
 use tracing_subscriber::EnvFilter;
 use walkdir::WalkDir;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::sync::Mutex;
 use tokio::io::AsyncReadExt;
 
+/// Most paths a worker takes from the channel at once
+const RECV_BATCH: usize = 16;
+
 #[derive(ClapParser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -78,7 +80,7 @@ async fn main() -> Result<()> {
     let last_progress = Arc::new(AtomicU64::new(0));
 
     // Determine number of parallel tasks
-    let num_tasks = args.parallel_tasks.unwrap_or_else(|| num_cpus::get());
+    let num_tasks = args.parallel_tasks.unwrap_or_else(num_cpus::get);
     info!("Using {} parallel tasks", num_tasks);
 
     // First, collect all Python files using synchronous walkdir
@@ -96,7 +98,7 @@ async fn main() -> Result<()> {
             debug!("Processed {} entries so far...", entries_processed);
         }
 
-        if entry.path().extension().map_or(false, |ext| ext == "py") {
+        if entry.path().extension().is_some_and(|ext| ext == "py") {
             python_files.push(entry.path().to_path_buf());
         }
     }
@@ -105,38 +107,51 @@ async fn main() -> Result<()> {
     info!("File discovery phase: Found {} Python files out of {} total entries in {:.2?}", 
           python_files.len(), entries_processed, discovery_duration);
 
-    // Process files in parallel using async tasks
+    // Process files with `num_tasks` long-lived workers pulling paths from a bounded channel,
+    // so at most `num_tasks` reads (and open files) are in flight and discovery can't get
+    // far ahead of them. Workers take paths a batch at a time to keep the shared receiver
+    // from becoming the bottleneck.
     let total_files = python_files.len() as u64;
-    let python_files = Arc::new(Mutex::new(python_files));
-    let mut handles = Vec::new();
+    let num_tasks = num_tasks.max(1);
+    let (sender, receiver) = mpsc::channel::<PathBuf>(num_tasks * RECV_BATCH);
+    let receiver = Arc::new(Mutex::new(receiver));
+    let mut workers = JoinSet::new();
 
     for _ in 0..num_tasks {
-        let python_files = Arc::clone(&python_files);
+        let receiver = Arc::clone(&receiver);
         let processed_files = Arc::clone(&processed_files);
         let total_bytes = Arc::clone(&total_bytes);
         let last_progress = Arc::clone(&last_progress);
 
-        handles.push(task::spawn(async move {
-            while let Some(path) = {
-                let mut files = python_files.lock().unwrap();
-                files.pop()
-            } {
-                if let Err(e) = process_file(
-                    path,
-                    Arc::clone(&processed_files),
-                    Arc::clone(&total_bytes),
-                    Arc::clone(&last_progress),
-                    total_files,
-                ).await {
-                    debug!("Error processing file: {}", e);
+        workers.spawn(async move {
+            let mut batch = Vec::with_capacity(RECV_BATCH);
+            // Only hold the lock while waiting for paths, not while reading them
+            while receiver.lock().await.recv_many(&mut batch, RECV_BATCH).await > 0 {
+                for path in batch.drain(..) {
+                    let result = process_file(
+                        path,
+                        Arc::clone(&processed_files),
+                        Arc::clone(&total_bytes),
+                        Arc::clone(&last_progress),
+                        total_files,
+                    ).await;
+                    if let Err(e) = result {
+                        debug!("Error processing file: {}", e);
+                    }
                 }
             }
-        }));
+        });
+    }
+
+    for path in python_files {
+        sender.send(path).await?;
     }
+    // Closing the channel lets each worker finish once it's drained
+    drop(sender);
 
-    // Wait for all tasks to complete
-    for handle in handles {
-        handle.await?;
+    // Wait for all workers to complete
+    while let Some(result) = workers.join_next().await {
+        result?;
     }
 
     let total_duration = start_time.elapsed();
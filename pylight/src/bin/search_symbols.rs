@@ -1,15 +1,13 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
 use clap::Parser as ClapParser;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 use std::collections::HashSet;
 use tracing::info;
-use tracing_subscriber;
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
 use tracing_subscriber::EnvFilter;
-use flate2::read::GzDecoder;
-use std::io::{self, BufReader, Write};
-use std::fs::File;
-use bincode;
+use std::io::{self, Write};
 use crossterm::{
     event::{self, Event, KeyCode, KeyModifiers},
     terminal::{self, ClearType},
@@ -17,18 +15,27 @@ use crossterm::{
     ExecutableCommand,
 };
 use symbol_experiments::symbols::{
-    Symbol, SymbolStats, SymbolData, PathRegistry, SymbolType
+    Symbol, SymbolStats, PathRegistry, SymbolType, find_duplicate_names, list_modules,
 };
-use symbol_experiments::files::list_python_files;
-use symbol_experiments::python::parse_python_files_parallel;
-use symbol_experiments::search::{search_symbols, print_symbol, SearchAlgorithm, SearchMetrics};
+use symbol_experiments::index::SymbolIndex;
+use symbol_experiments::files::{list_python_files, scan_layout};
+use symbol_experiments::python::{parse_python_files_parallel_with_options, CollectOptions};
+use symbol_experiments::search::{
+    display_path, export_results, format_grouped_results, group_results_by_class, order_results,
+    search_symbols_with_options, print_symbol_colored, should_colorize, suggest_symbol_names,
+    symbol_color_legend, top_result_location, ResultOrder, ResultTemplate, ResultsFormat, SearchAlgorithm, SearchMetrics, SearchOptions,
+    MAX_SUGGESTIONS,
+};
+use symbol_experiments::search_skim::SkimPreset;
+use symbol_experiments::search_regex::compile_query;
 
 #[derive(ClapParser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Directory to scan (defaults to current directory)
+    /// Directory to scan (defaults to current directory), or a single .py/.pyi file.
+    /// Repeat to scan several, each naming its modules as if scanned alone
     #[arg(short, long, default_value = ".")]
-    directory: PathBuf,
+    directory: Vec<PathBuf>,
 
     /// Whether to follow symbolic links
     #[arg(short, long)]
@@ -38,11 +45,16 @@ struct Args {
     #[arg(short, long)]
     load: Option<PathBuf>,
 
+    /// Move the loaded index's paths from the directory it was built in to this one,
+    /// e.g. after copying a project and its index elsewhere
+    #[arg(long, value_name = "NEWROOT", requires = "load")]
+    rebase: Option<PathBuf>,
+
     /// Print all symbols without searching
     #[arg(short, long)]
     print: bool,
 
-    /// Search query (if not provided with --print, will print all symbols)
+    /// Search query; space-separated terms must all match (if not provided with --print, will print all symbols)
     #[arg(short, long)]
     search: Option<String>,
     
@@ -50,13 +62,76 @@ struct Args {
     #[arg(short, long)]
     interactive: bool,
     
-    /// Search algorithm to use (skim or nucleo)
+    /// Search algorithm to use (skim, nucleo or regex)
     #[arg(long, default_value = "skim")]
     algorithm: SearchAlgorithm,
+
+    /// Scoring weights for the skim algorithm (default or word-boundary-heavy)
+    #[arg(long, default_value = "default")]
+    skim_preset: SkimPreset,
     
     /// Show performance metrics for search operations
     #[arg(short, long)]
     metrics: bool,
+
+    /// List every module with a representative file, sorted by module name
+    #[arg(long)]
+    list_modules: bool,
+
+    /// List names defined more than N times, with every location, most-defined first
+    #[arg(long, value_name = "N")]
+    duplicates: Option<usize>,
+
+    /// Number of threads to parse with (defaults to the global rayon pool)
+    #[arg(long)]
+    threads: Option<usize>,
+
+    /// Disable colored output (also disabled when stdout is not a terminal)
+    #[arg(long)]
+    no_color: bool,
+
+    /// Only log warnings and errors. Caps whatever RUST_LOG asks for, so `RUST_LOG=info --quiet`
+    /// logs warnings; a stricter RUST_LOG still applies
+    #[arg(long)]
+    quiet: bool,
+
+    /// Ignore accents when matching, so "cafe" finds `café`
+    #[arg(long)]
+    fold_diacritics: bool,
+
+    /// Show matching methods indented under their class instead of as a flat list
+    #[arg(long)]
+    group_by_class: bool,
+
+    /// Only show these kinds of symbols, comma-separated
+    /// (function, class, method, nested_function, nested_class, type_alias)
+    #[arg(long, value_delimiter = ',')]
+    kinds: Vec<SymbolType>,
+
+    /// Show file paths relative to --directory, or the directories' common parent when
+    /// repeated (paths outside it are shown in full)
+    #[arg(long)]
+    relative: bool,
+
+    /// Print each result (with --print or --search) as this template instead of the usual
+    /// details, e.g. "{file}:{line}: {name}" for a quickfix list. Placeholders: {name},
+    /// {kind}, {file}, {line}, {module} and {score}; {{ and }} are literal braces
+    #[arg(long, value_name = "TEMPLATE", conflicts_with_all = ["interactive", "group_by_class"])]
+    format_template: Option<String>,
+
+    /// Print only `file:line` of the best --search result and nothing else, exiting with
+    /// status 1 (and printing nothing) when there is no match. For editor "jump" commands.
+    #[arg(long, requires = "search", conflicts_with_all = ["interactive", "group_by_class", "format_template", "metrics"])]
+    first: bool,
+
+    /// Show only the best N --search results (all matches are still counted)
+    #[arg(long, value_name = "N", requires = "search", conflicts_with = "interactive")]
+    top: Option<usize>,
+
+    /// Order --search results by 'score' (best first), 'name' or 'file' (path, then line).
+    /// With --top, the best N are picked by score before reordering
+    #[arg(long, value_name = "ORDER", requires = "search", conflicts_with_all = ["interactive", "first"])]
+    sort: Option<ResultOrder>,
 }
 
 /// Print the search metrics
@@ -69,38 +144,35 @@ fn print_metrics(metrics: &SearchMetrics) {
     println!("  Total time: {}ms", metrics.total_time_ms);
 }
 
-fn load_symbols_from_file(path: &PathBuf) -> Result<(HashSet<Symbol>, HashSet<Symbol>, PathRegistry)> {
-    info!("Loading symbols from {}...", path.display());
-    
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let decoder = GzDecoder::new(reader);
-    
-    info!("Starting deserialization...");
-    let data: SymbolData = bincode::deserialize_from(decoder)?;
-    
-    info!("Converting to symbol collections...");
-    let (functions, classes, paths) = data.into_symbols();
-    
-    // Create a new PathRegistry and populate it with the exact same paths in the same order
-    let mut path_registry = PathRegistry::new();
-    
-    // First register all paths to ensure they have the same indices as when saved
-    for (i, path) in paths.iter().enumerate() {
-        let index = path_registry.register_path(path.clone());
-        // Sanity check - make sure the index matches the position
-        assert_eq!(index, i, "Path registry indexing error: expected index {} for path {}, got {}", 
-                  i, path.display(), index);
+fn load_symbols_from_file(path: &Path, rebase: Option<&Path>) -> Result<(HashSet<Symbol>, HashSet<Symbol>, PathRegistry)> {
+    let mut index = SymbolIndex::load(path)?;
+    if let Some(new_root) = rebase {
+        index.rebase(new_root).with_context(|| format!("Failed to rebase {}", path.display()))?;
     }
-    
-    info!("Load complete! Loaded {} functions, {} classes, and {} paths", 
-          functions.len(), classes.len(), paths.len());
+    Ok((index.functions, index.classes, index.registry))
+}
+
+/// Environment variable naming the file Ctrl+S exports results to (`.csv` for CSV, else JSON)
+const EXPORT_PATH_VAR: &str = "SYMBOL_SEARCH_EXPORT";
+const DEFAULT_EXPORT_PATH: &str = "search_results.json";
 
-    Ok((
-        functions.into_iter().collect(),
-        classes.into_iter().collect(),
-        path_registry
-    ))
+/// Write the current results to the export file, returning a status line for the UI
+fn export_interactive_results(results: &[(Symbol, i64)], path_registry: &PathRegistry) -> String {
+    let path = std::env::var_os(EXPORT_PATH_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_EXPORT_PATH));
+    let written = std::fs::File::create(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let mut writer = io::BufWriter::new(file);
+            export_results(&mut writer, results, path_registry, ResultsFormat::from_path(&path))?;
+            writer.flush()?;
+            Ok(())
+        });
+    match written {
+        Ok(()) => format!("Exported {} results to {}", results.len(), path.display()),
+        Err(e) => format!("Export to {} failed: {}", path.display(), e),
+    }
 }
 
 fn run_interactive_mode(
@@ -109,12 +181,17 @@ fn run_interactive_mode(
     path_registry: &PathRegistry,
     algorithm: SearchAlgorithm,
     show_metrics: bool,
+    options: &SearchOptions,
 ) -> Result<()> {
     // Enter terminal raw mode for character-by-character input
     terminal::enable_raw_mode()?;
     let mut stdout = io::stdout();
     
     let mut query = String::new();
+    let mut algorithm = algorithm;
+    let mut results = Vec::new();
+    // Outcome of the last export, shown until the query changes
+    let mut export_status: Option<String> = None;
     
     // Main loop
     loop {
@@ -126,13 +203,18 @@ fn run_interactive_mode(
         stdout.execute(cursor::MoveTo(0, 0))?;
         writeln!(stdout, "Interactive symbol search mode ({}). Results update as you type.", algorithm)?;
         stdout.execute(cursor::MoveTo(0, 1))?;
-        writeln!(stdout, "Use Backspace to delete, Esc or Ctrl+C to exit.")?;
+        writeln!(stdout, "Use Backspace to delete, Tab to switch algorithm, Ctrl+S to export results, Esc or Ctrl+C to exit.")?;
         stdout.execute(cursor::MoveTo(0, 2))?;
         writeln!(stdout, "----------------------------------------------------------------")?;
         
         // Print search prompt
         stdout.execute(cursor::MoveTo(0, 3))?;
         write!(stdout, "Search: {}", query)?;
+        if let Some(status) = &export_status {
+            stdout.execute(cursor::MoveTo(0, 4))?;
+            write!(stdout, "{}", status)?;
+            stdout.execute(cursor::MoveTo(8 + query.chars().count() as u16, 3))?;
+        }
         stdout.flush()?;
         
         // Show search results if we have a query
@@ -140,7 +222,8 @@ fn run_interactive_mode(
             // Position cursor to start showing results
             stdout.execute(cursor::MoveTo(0, 5))?;
             
-            let (results, metrics) = search_symbols(&query, functions, classes, path_registry, false, algorithm);
+            let metrics;
+            (results, metrics) = search_symbols_with_options(&query, functions, classes, path_registry, false, algorithm, options);
             writeln!(stdout, "Found {} matches:", results.len())?;
             
             if show_metrics {
@@ -213,6 +296,7 @@ fn run_interactive_mode(
                 writeln!(stdout, "... and {} more results", results.len() - result_limit)?;
             }
         } else {
+            results.clear();
             // Type to start searching...
             stdout.execute(cursor::MoveTo(0, 5))?;
             writeln!(stdout, "Type to start searching...")?;
@@ -229,14 +313,27 @@ fn run_interactive_mode(
                 // Exit on Ctrl+C
                 KeyCode::Char('c') if key_event.modifiers.contains(KeyModifiers::CONTROL) => break,
                 
+                // Export the current results; failures are reported in the UI so the
+                // terminal is always restored on exit
+                KeyCode::Char('s') if key_event.modifiers.contains(KeyModifiers::CONTROL) => {
+                    export_status = Some(export_interactive_results(&results, path_registry));
+                },
+                
+                // Cycle the search algorithm and re-run the query with it
+                KeyCode::Tab => {
+                    algorithm = algorithm.next();
+                },
+                
                 // Handle character input
                 KeyCode::Char(c) => {
                     query.push(c);
+                    export_status = None;
                 },
                 
                 // Handle backspace
                 KeyCode::Backspace => {
                     query.pop();
+                    export_status = None;
                 },
                 
                 // Ignore other keys
@@ -254,30 +351,41 @@ fn run_interactive_mode(
 }
 
 fn main() -> Result<()> {
+    let args = Args::parse();
+
     // Initialize tracing
     tracing_subscriber::fmt()
         .with_env_filter(EnvFilter::from_default_env())
+        .finish()
+        .with(if args.quiet || args.first { LevelFilter::WARN } else { LevelFilter::TRACE })
         .init();
     
-    let args = Args::parse();
     let start = Instant::now();
     
     info!("Using search algorithm: {}", args.algorithm);
     
+    let (base_dir, source_roots) = scan_layout(&args.directory, &[]);
+    
     // Decide whether to load from file or scan directory
     let (functions, classes, path_registry) = if let Some(load_path) = &args.load {
         info!("Loading symbols from file: {}", load_path.display());
-        load_symbols_from_file(load_path)?
+        load_symbols_from_file(load_path, args.rebase.as_deref())?
     } else {
-        info!("Scanning directory: {}", args.directory.display());
-        
         // Find all Python files
-        let python_files: Vec<_> = list_python_files(&args.directory, args.follow_links).collect();
+        let mut python_files = Vec::new();
+        for directory in &args.directory {
+            info!("Scanning directory: {}", directory.display());
+            python_files.extend(list_python_files(directory, args.follow_links));
+        }
+        // Overlapping directories would otherwise list the same files twice
+        let mut seen = HashSet::new();
+        python_files.retain(|file| seen.insert(file.clone()));
         info!("Found {} Python files", python_files.len());
         
         // Parse Python files and collect symbols
         let stats = SymbolStats::new();
-        parse_python_files_parallel(&python_files, &args.directory, &stats)?;
+        let options = CollectOptions { threads: args.threads, source_roots, ..Default::default() };
+        parse_python_files_parallel_with_options(&python_files, &base_dir, &stats, &options)?;
         
         let functions = stats.functions.lock().unwrap().clone();
         let classes = stats.classes.lock().unwrap().clone();
@@ -289,45 +397,128 @@ fn main() -> Result<()> {
     info!("Processing complete in {}ms", start.elapsed().as_millis());
     info!("Found {} functions and {} classes", functions.len(), classes.len());
     
+    let search_options = SearchOptions {
+        fold_diacritics: args.fold_diacritics,
+        kinds: (!args.kinds.is_empty()).then(|| args.kinds.iter().cloned().collect()),
+        skim_preset: args.skim_preset,
+        // --first only ever needs the best result
+        max_results: if args.first { Some(1) } else { args.top },
+        ..Default::default()
+    };
+    
     // Enter interactive mode if requested
     if args.interactive {
         info!("Entering interactive mode...");
-        run_interactive_mode(&functions, &classes, &path_registry, args.algorithm, args.metrics)?;
+        run_interactive_mode(&functions, &classes, &path_registry, args.algorithm, args.metrics, &search_options)?;
         return Ok(());
     }
     
-    // Handle printing all symbols or searching
-    if args.print {
+    // Reject a bad template before doing the search
+    let template = args.format_template.as_deref().map(ResultTemplate::parse).transpose()?;
+    let color = template.is_none() && should_colorize(args.no_color);
+    // Indexes may store absolute paths, which only match a resolved root
+    let root = args.relative.then(|| {
+        let stores_absolute_paths = path_registry.paths.first().is_some_and(|p| p.is_absolute());
+        if stores_absolute_paths {
+            base_dir.canonicalize().unwrap_or_else(|_| base_dir.clone())
+        } else {
+            base_dir.clone()
+        }
+    });
+    
+    // Handle listing modules, printing all symbols or searching
+    if args.list_modules {
+        for (module, path) in list_modules(&functions, &classes, &path_registry) {
+            println!("{} | {}", module, path.display());
+        }
+    } else if let Some(more_than) = args.duplicates {
+        for (name, definitions) in find_duplicate_names(&functions, &classes, &path_registry, more_than) {
+            println!("{} ({} definitions)", name, definitions.len());
+            for symbol in definitions {
+                let path = path_registry.get_path(symbol.context.file_path_index);
+                println!("  {}:{}", display_path(path, root.as_deref()).display(), symbol.context.line_number);
+            }
+        }
+    } else if args.print {
         // Print all symbols
+        if color {
+            println!("{}", symbol_color_legend());
+        }
         for symbol in functions.iter().chain(classes.iter()) {
-            print_symbol(symbol, &path_registry);
+            match &template {
+                Some(template) => println!("{}", template.render(symbol, &path_registry, None, root.as_deref())),
+                None => print_symbol_colored(symbol, &path_registry, color, root.as_deref()),
+            }
         }
     } else if let Some(query) = args.search {
         // Search for a specific query
         info!("Searching for: {}", query);
         
+        // Report a bad pattern up front instead of silently finding nothing
+        if args.algorithm == SearchAlgorithm::Regex {
+            compile_query(&query)?;
+        }
+        
         // Run search with benchmarking
         let search_start = Instant::now();
-        let (results, metrics) = search_symbols(&query, &functions, &classes, &path_registry, true, args.algorithm);
+        let debug = template.is_none() && !args.first;
+        let (mut results, metrics) = search_symbols_with_options(&query, &functions, &classes, &path_registry, debug, args.algorithm, &search_options);
         let search_time = search_start.elapsed();
         
-        println!("Found {} matches (search took {}ms):", results.len(), search_time.as_millis());
+        if args.first {
+            match top_result_location(&results, &path_registry, root.as_deref()) {
+                Some(location) => println!("{}", location),
+                None => std::process::exit(1),
+            }
+            return Ok(());
+        }
+        
+        if let Some(order) = args.sort {
+            order_results(&mut results, order, &path_registry);
+        }
+        
+        // Templated output is meant for other tools, so it gets nothing but the results
+        if template.is_none() {
+            if metrics.results_count > results.len() {
+                println!("Found {} matches (search took {}ms), showing the top {}:",
+                         metrics.results_count, search_time.as_millis(), results.len());
+            } else {
+                println!("Found {} matches (search took {}ms):", results.len(), search_time.as_millis());
+            }
+        }
+        
+        if results.is_empty() && template.is_none() {
+            let suggestions = suggest_symbol_names(&query, &functions, &classes, MAX_SUGGESTIONS);
+            if !suggestions.is_empty() {
+                println!("Did you mean: {}?", suggestions.join(", "));
+            }
+        }
         
         if args.metrics {
             print_metrics(&metrics);
             println!();
         }
         
-        for (symbol, score) in results {
-            print_symbol(&symbol, &path_registry);
-            println!("  Score: {}", score);
-            println!();
+        if let Some(template) = &template {
+            for (symbol, score) in &results {
+                println!("{}", template.render(symbol, &path_registry, Some(*score), root.as_deref()));
+            }
+        } else if args.group_by_class {
+            let groups = group_results_by_class(&results);
+            print!("{}", format_grouped_results(&groups, &path_registry, color, root.as_deref()));
+        } else {
+            for (symbol, score) in results {
+                print_symbol_colored(&symbol, &path_registry, color, root.as_deref());
+                println!("  Score: {}", score);
+                println!();
+            }
         }
     } else {
         // If neither --print nor --search is specified, print summary
         println!("Use --print to list all symbols or --search to search for symbols.");
         println!("Use --interactive for an interactive search experience.");
-        println!("Use --algorithm=[skim|nucleo] to select search algorithm (default: skim).");
+        println!("Use --list-modules to list all indexed modules.");
+        println!("Use --algorithm=[skim|nucleo|regex] to select search algorithm (default: skim).");
         println!("Use --metrics to display performance metrics.");
     }
     
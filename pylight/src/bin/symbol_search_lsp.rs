@@ -1,34 +1,52 @@
 use anyhow::Result;
-use bincode;
 use clap::Parser as ClapParser;
-use flate2::read::GzDecoder;
-use std::collections::HashSet;
-use std::fs::File;
-use std::io::{stderr, BufReader};
-use std::path::PathBuf;
-use std::sync::Arc;
+use crossbeam_channel::{select, Sender};
+use lru::LruCache;
+use std::collections::{HashMap, HashSet};
+use std::io::stderr;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 use tokio::runtime::Runtime;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 use url::Url;
 
 use lsp_server::{Connection, Message, Response, ResponseError, ErrorCode};
 use lsp_types::{
-    Location, OneOf, Position, Range, ServerCapabilities, SymbolInformation, SymbolKind,
+    CallHierarchyItem, CallHierarchyPrepareParams, CallHierarchyServerCapability, DidChangeWatchedFilesParams, DidChangeWorkspaceFoldersParams, DidSaveTextDocumentParams, DocumentHighlight,
+    DocumentHighlightKind, DocumentHighlightParams, DocumentSymbol, DocumentSymbolParams,
+    FileChangeType, FoldingRange, FoldingRangeParams, FoldingRangeProviderCapability, OneOf,
+    Position, Range, SemanticTokens, SemanticTokensOptions, SemanticTokensRangeParams,
+    SemanticTokensRangeResult, SemanticTokensServerCapabilities, ServerCapabilities,
+    SymbolInformation, TextDocumentSyncCapability,
+    TextDocumentPositionParams, TextDocumentSyncOptions, TextDocumentSyncSaveOptions, Uri,
+    WorkspaceFoldersChangeEvent, WorkspaceFoldersServerCapabilities, WorkspaceServerCapabilities,
     WorkspaceSymbolParams,
 };
 use serde_json::{self, Value};
 
-use symbol_experiments::files::list_python_files;
-use symbol_experiments::python::parse_python_files_parallel;
+use symbol_experiments::files::{canonicalize_paths, list_python_files, scan_base_dir};
+use symbol_experiments::line_index::{byte_to_utf16_column, utf16_to_byte_column};
+use symbol_experiments::lsp::{
+    parent_chain, semantic_tokens_legend, symbol_at_position, to_document_symbols, to_folding_ranges,
+    file_url, to_call_hierarchy_item, to_semantic_tokens, to_symbol_information, to_symbol_information_with_container, ContainerNameMode,
+    ContainerNameOptions,
+};
+use symbol_experiments::python::{
+    collect_file_symbols, create_python_parser, parse_python_files_parallel_with_options,
+    parse_python_files_parallel_with_threads, CollectOptions,
+};
 use symbol_experiments::search::{search_symbols, SearchAlgorithm};
-use symbol_experiments::symbols::{PathRegistry, Symbol, SymbolData, SymbolStats, SymbolType};
+use symbol_experiments::index::SymbolIndex;
+use symbol_experiments::symbols::{summarize_index, PathRegistry, Symbol, SymbolStats};
 
 #[derive(ClapParser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
-    /// Directory to scan (defaults to current directory)
+    /// Directory to scan (defaults to current directory), or a single .py/.pyi file
     #[arg(short, long, default_value = ".")]
     directory: PathBuf,
 
@@ -36,151 +54,229 @@ struct Args {
     #[arg(short, long)]
     follow_links: bool,
 
+    /// Resolve scanned files to absolute, canonicalized paths. Without it, relative paths are
+    /// resolved against the server's working directory when converted to URIs; has no effect
+    /// with --load
+    #[arg(long)]
+    absolute_paths: bool,
+
     /// Load symbols from this file instead of scanning directory
     #[arg(short, long)]
     load: Option<PathBuf>,
 
-    /// Search algorithm to use (skim or nucleo)
+    /// Search algorithm to use (skim, nucleo or regex)
     #[arg(long, default_value = "skim")]
     algorithm: SearchAlgorithm,
 
     /// Listen on this TCP port instead of using stdio
     #[arg(long)]
     port: Option<u16>,
-}
 
-/// Load symbols from a previously saved file
-fn load_symbols_from_file(
-    path: &PathBuf,
-) -> Result<(HashSet<Symbol>, HashSet<Symbol>, PathRegistry)> {
-    info!("Loading symbols from {}...", path.display());
+    /// Number of threads to parse with (defaults to the global rayon pool)
+    #[arg(long)]
+    threads: Option<usize>,
 
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
-    let decoder = GzDecoder::new(reader);
+    /// Append the match score to symbol names in workspace/symbol results, for tuning search.
+    /// Clients can also enable this with `{"showScores": true}` in initializationOptions.
+    #[arg(long)]
+    show_scores: bool,
 
-    info!("Starting deserialization...");
-    let data: SymbolData = bincode::deserialize_from(decoder)?;
+    /// Answer an empty workspace/symbol query with the first symbols by kind and name, for
+    /// editors that pre-populate their picker. Clients can also enable this with
+    /// `{"emptyQueryReturnsAll": true}` in initializationOptions.
+    #[arg(long)]
+    empty_query_returns_all: bool,
+
+    /// What to show as each workspace symbol's container: 'auto' (parent classes/functions,
+    /// else the package), 'parents' or 'module'. Clients can also set this with
+    /// `{"containerName": "module"}` in initializationOptions.
+    #[arg(long, default_value = "auto")]
+    container_name: ContainerNameMode,
+
+    /// Separator between parent class/function names in container names (e.g. '::').
+    /// Clients can also set this with `{"containerSeparator": "::"}` in initializationOptions.
+    #[arg(long, default_value = ".")]
+    container_separator: String,
+
+    /// Show each workspace symbol's fully-qualified name (e.g. `pkg.module.Class.method`)
+    /// instead of the bare name, for editors that hide the container. Clients can also
+    /// enable this with `{"qualifiedNames": true}` in initializationOptions.
+    #[arg(long)]
+    qualified_names: bool,
 
-    info!("Converting to symbol collections...");
-    let (functions, classes, paths) = data.into_symbols();
+    /// Remember the results of this many recent workspace/symbol queries, so retyping a query
+    /// (e.g. after a backspace) skips the search. 0 disables the cache
+    #[arg(long, default_value_t = DEFAULT_QUERY_CACHE_SIZE)]
+    query_cache_size: usize,
 
-    // Create a new PathRegistry and populate it with the exact same paths in the same order
-    let mut path_registry = PathRegistry::new();
+    /// Answer non-empty workspace/symbol queries shorter than this many characters with no
+    /// results instead of searching, to skip the flood of matches for a first keystroke.
+    /// Clients can also set this with `{"minQueryLength": 3}` in initializationOptions.
+    #[arg(long, default_value_t = 1)]
+    min_query_len: usize,
 
-    // First register all paths to ensure they have the same indices as when saved
-    for (i, path) in paths.iter().enumerate() {
-        let index = path_registry.register_path(path.clone());
-        // Sanity check - make sure the index matches the position
-        assert_eq!(
-            index,
-            i,
-            "Path registry indexing error: expected index {} for path {}, got {}",
-            i,
-            path.display(),
-            index
-        );
-    }
+    /// Print a JSON summary of the loaded index (functions, classes, files, modules) and
+    /// exit without starting the server
+    #[arg(long)]
+    print_stats_and_exit: bool,
+}
 
-    info!(
-        "Load complete! Loaded {} functions, {} classes, and {} paths",
-        functions.len(),
-        classes.len(),
-        paths.len()
-    );
+/// Whether the client asked for match scores in symbol names via initializationOptions
+fn show_scores_from_initialize_params(params: &Value) -> Option<bool> {
+    params
+        .get("initializationOptions")?
+        .get("showScores")?
+        .as_bool()
+}
 
-    Ok((
-        functions.into_iter().collect(),
-        classes.into_iter().collect(),
-        path_registry,
-    ))
+/// Whether the client asked for symbols on empty queries via initializationOptions
+fn empty_query_returns_all_from_initialize_params(params: &Value) -> Option<bool> {
+    params
+        .get("initializationOptions")?
+        .get("emptyQueryReturnsAll")?
+        .as_bool()
 }
 
-/// Convert a Symbol to an LSP SymbolInformation
-fn to_lsp_symbol_information(
-    symbol: &Symbol,
-    path_registry: &PathRegistry,
-    score: i64,
-) -> Option<SymbolInformation> {
-    // Return Option
-    let file_path: &PathBuf = path_registry.get_path(symbol.context.file_path_index);
-    let url = Url::from_file_path(file_path).ok()?; // Convert PathBuf to Url (Uri)
-    let uri = match url.as_str().parse() {
-        Ok(url) => url,
-        Err(_) => {
-            tracing::error!("Failed to convert path to URI: {}", file_path.display());
-            return None; // Skip this symbol if conversion fails
+/// The minimum query length the client asked for via initializationOptions
+fn min_query_len_from_initialize_params(params: &Value) -> Option<usize> {
+    params
+        .get("initializationOptions")?
+        .get("minQueryLength")?
+        .as_u64()
+        .map(|len| len as usize)
+}
+
+/// The container name mode the client asked for via initializationOptions, if valid
+fn container_name_mode_from_initialize_params(params: &Value) -> Option<ContainerNameMode> {
+    let mode = params
+        .get("initializationOptions")?
+        .get("containerName")?
+        .as_str()?;
+    match mode.parse() {
+        Ok(mode) => Some(mode),
+        Err(e) => {
+            warn!("Ignoring containerName initialization option: {}", e);
+            None
         }
-    };
+    }
+}
 
-    // Determine symbol kind based on the symbol type
-    let symbol_kind = match symbol.context.symbol_type {
-        SymbolType::Class | SymbolType::NestedClass => SymbolKind::CLASS,
-        SymbolType::Function | SymbolType::Method => SymbolKind::FUNCTION,
-        _ => SymbolKind::VARIABLE, // Default fallback
-    };
+/// The container name separator the client asked for via initializationOptions
+fn container_separator_from_initialize_params(params: &Value) -> Option<String> {
+    params
+        .get("initializationOptions")?
+        .get("containerSeparator")?
+        .as_str()
+        .map(str::to_string)
+}
 
-    // Create the symbol location - we only have line number, so both start and end positions use the same line
-    let location = Location {
-        uri,
-        range: Range {
-            start: Position {
-                line: (symbol.context.line_number as u32).saturating_sub(1), // Convert to 0-based indexing
-                character: 0,
-            },
-            end: Position {
-                line: (symbol.context.line_number as u32).saturating_sub(1),
-                character: 0, // Keep character 0 for simplicity
-            },
-        },
-    };
+/// Whether the client asked for fully-qualified symbol names via initializationOptions
+fn qualified_names_from_initialize_params(params: &Value) -> Option<bool> {
+    params
+        .get("initializationOptions")?
+        .get("qualifiedNames")?
+        .as_bool()
+}
 
-    // Build the container name from the parent context or module
-    let container_name = if !symbol.context.parent_context.is_empty() {
-        symbol
-            .context
-            .parent_context
-            .iter()
-            .map(|p| p.name.clone())
-            .collect::<Vec<_>>()
-            .join(".")
-    } else {
-        symbol.context.fully_qualified_module.clone()
-    };
+/// Load symbols from a previously saved file
+fn load_symbols_from_file(
+    path: &Path,
+) -> Result<(HashSet<Symbol>, HashSet<Symbol>, PathRegistry)> {
+    let index = SymbolIndex::load(path)?;
+    Ok((index.functions, index.classes, index.registry))
+}
 
-    // Include score in the symbol details for debugging
-    let name_with_score = if cfg!(debug_assertions) {
-        format!("{} ({})", symbol.name, score)
-    } else {
-        symbol.name.clone()
-    };
+/// Most symbols returned for a single workspace/symbol request
+const MAX_WORKSPACE_SYMBOLS: usize = 100;
 
-    // Replace deprecated field with tags, but keep deprecated field as None
-    Some(SymbolInformation {
-        name: name_with_score,
-        kind: symbol_kind,
-        tags: None, // Use tags instead of deprecated field
-        location,
-        container_name: Some(container_name),
-        deprecated: None, // Explicitly set deprecated to None
-    })
+/// Recent workspace/symbol queries whose results are kept when --query-cache-size is not given
+const DEFAULT_QUERY_CACHE_SIZE: usize = 64;
+
+/// Results of recent workspace/symbol queries, evicting the least recently used query
+/// once `capacity` are stored. Cleared whenever files change, since results may go stale.
+struct QueryCache {
+    // None when the capacity is 0 and nothing is cached
+    results: Option<LruCache<String, Vec<SymbolInformation>>>,
+    // Bumped by every clear, so searches that started before it cannot store their results
+    generation: u64,
 }
 
-/// Handle a workspace symbol request from the LSP client asynchronously
-async fn handle_workspace_symbol_request_async(
+impl QueryCache {
+    fn new(capacity: usize) -> Self {
+        QueryCache { results: NonZeroUsize::new(capacity).map(LruCache::new), generation: 0 }
+    }
+
+    /// The current generation, to capture when a search starts and pass to `insert`
+    fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    fn get(&mut self, query: &str) -> Option<Vec<SymbolInformation>> {
+        self.results.as_mut()?.get(query).cloned()
+    }
+
+    /// Store `results` for `query`, unless the cache was cleared since the search started
+    /// at `generation`
+    fn insert(&mut self, query: String, results: Vec<SymbolInformation>, generation: u64) {
+        if generation != self.generation {
+            return;
+        }
+        if let Some(cache) = &mut self.results {
+            cache.put(query, results);
+        }
+    }
+
+    fn clear(&mut self) {
+        if let Some(cache) = &mut self.results {
+            cache.clear();
+        }
+        self.generation += 1;
+    }
+}
+
+/// The first `MAX_WORKSPACE_SYMBOLS` symbols ordered by kind, then name, for an empty query
+fn list_all_workspace_symbols(
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+    container_options: &ContainerNameOptions,
+) -> Vec<SymbolInformation> {
+    let mut symbols: Vec<&Symbol> = functions.iter().chain(classes.iter()).collect();
+    symbols.sort_by(|a, b| {
+        a.context.symbol_type.kind_name().cmp(b.context.symbol_type.kind_name())
+            .then_with(|| a.name.cmp(&b.name))
+            .then_with(|| path_registry.get_path(a.context.file_path_index).cmp(path_registry.get_path(b.context.file_path_index)))
+            .then(a.context.line_number.cmp(&b.context.line_number))
+    });
+    symbols
+        .into_iter()
+        .filter_map(|symbol| to_symbol_information_with_container(symbol, path_registry, 0, false, container_options))
+        .take(MAX_WORKSPACE_SYMBOLS)
+        .collect()
+}
+
+/// Handle a workspace symbol request from the LSP client
+#[allow(clippy::too_many_arguments)]
+fn handle_workspace_symbol_request(
     params: WorkspaceSymbolParams,
-    functions: Arc<HashSet<Symbol>>,
-    classes: Arc<HashSet<Symbol>>,
-    path_registry: Arc<PathRegistry>,
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
     algorithm: SearchAlgorithm,
+    include_score: bool,
+    container_options: &ContainerNameOptions,
+    empty_query_returns_all: bool,
 ) -> Vec<SymbolInformation> {
     info!(
-        "Handling workspace symbol request asynchronously: query='{}'",
+        "Handling workspace symbol request: query='{}'",
         params.query
     );
 
-    // If the query is empty, return an empty result
+    // An empty query returns nothing unless the client wants an initial listing
     if params.query.is_empty() {
+        if empty_query_returns_all {
+            return list_all_workspace_symbols(functions, classes, path_registry, container_options);
+        }
         return Vec::new();
     }
 
@@ -188,9 +284,9 @@ async fn handle_workspace_symbol_request_async(
     let search_start = Instant::now();
     let (results, metrics) = search_symbols(
         &params.query,
-        &functions,
-        &classes,
-        &path_registry,
+        functions,
+        classes,
+        path_registry,
         false,
         algorithm,
     );
@@ -210,30 +306,558 @@ async fn handle_workspace_symbol_request_async(
         metrics.total_time_ms
     );
 
-    // truncate results to 100 symbols
-    let max_results = 100;
-    if result_count > max_results {
-        info!("Truncating results to {} symbols", max_results);
+    if result_count > MAX_WORKSPACE_SYMBOLS {
+        info!("Truncating results to {} symbols", MAX_WORKSPACE_SYMBOLS);
     }
 
     // Convert the results to LSP format, filtering out None values from conversion errors
     let lsp_symbols: Vec<SymbolInformation> = results
         .iter()
-        .filter_map(|(symbol, score)| to_lsp_symbol_information(symbol, &path_registry, *score)) // Use filter_map
-        .take(max_results)
+        .filter_map(|(symbol, score)| {
+            to_symbol_information_with_container(symbol, path_registry, *score, include_score, container_options)
+        })
+        .take(MAX_WORKSPACE_SYMBOLS)
         .collect();
 
     info!("Converted {} symbols to LSP format", lsp_symbols.len());
     lsp_symbols
 }
 
+/// Handle a workspace symbol request from the LSP client asynchronously
+#[allow(clippy::too_many_arguments)]
+async fn handle_workspace_symbol_request_async(
+    params: WorkspaceSymbolParams,
+    functions: Arc<HashSet<Symbol>>,
+    classes: Arc<HashSet<Symbol>>,
+    path_registry: Arc<PathRegistry>,
+    algorithm: SearchAlgorithm,
+    include_score: bool,
+    container_options: ContainerNameOptions,
+    empty_query_returns_all: bool,
+    min_query_len: usize,
+) -> Vec<SymbolInformation> {
+    // Empty queries are left to `empty_query_returns_all`; blank ones match nothing
+    let query_len = params.query.trim().chars().count();
+    if query_len == 0 && !params.query.is_empty() {
+        return Vec::new();
+    }
+    if query_len > 0 && query_len < min_query_len {
+        info!("Skipping search for query '{}' shorter than {} characters", params.query, min_query_len);
+        return Vec::new();
+    }
+    handle_workspace_symbol_request(
+        params,
+        &functions,
+        &classes,
+        &path_registry,
+        algorithm,
+        include_score,
+        &container_options,
+        empty_query_returns_all,
+    )
+}
+
+/// Find the Python identifier touching byte offset `column` in `line`
+fn identifier_at(line: &str, column: usize) -> Option<String> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    let before = line.get(..column)?.chars().next_back();
+
+    // Allow the cursor to sit just after the identifier, as editors often report it there
+    let cursor = if line[column..].chars().next().is_some_and(is_ident) {
+        column
+    } else if let Some(before) = before.filter(|&c| is_ident(c)) {
+        column - before.len_utf8()
+    } else {
+        return None;
+    };
+
+    let start = line[..cursor].char_indices()
+        .rev()
+        .take_while(|&(_, c)| is_ident(c))
+        .last()
+        .map_or(cursor, |(offset, _)| offset);
+    let end = line[cursor..].char_indices()
+        .find(|&(_, c)| !is_ident(c))
+        .map_or(line.len(), |(offset, _)| cursor + offset);
+    let word = &line[start..end];
+    if word.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(word.to_string())
+}
+
+/// UTF-16 column of `name` as a whole word on a definition line, after `def`/`class` if present
+fn name_column(line: &str, name: &str) -> Option<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_';
+    line.match_indices(name)
+        .find(|(idx, _)| {
+            let before = line[..*idx].chars().next_back();
+            let after = line[idx + name.len()..].chars().next();
+            !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+        })
+        .map(|(idx, _)| byte_to_utf16_column(line, idx))
+}
+
+/// Handle a document highlight request by highlighting every definition of the name
+/// under the cursor in the same file. Usages are not tracked, only definitions.
+fn handle_document_highlight_request(
+    params: DocumentHighlightParams,
+    symbols_by_uri: &SymbolsByUri,
+) -> Vec<DocumentHighlight> {
+    let position = params.text_document_position_params.position;
+    let uri = params.text_document_position_params.text_document.uri;
+    let symbols = symbols_for_uri(symbols_by_uri, &uri);
+    if symbols.is_empty() {
+        info!("Document highlight for file without symbols: {}", uri.as_str());
+        return Vec::new();
+    }
+    let Some(path) = uri_to_path(&uri) else {
+        return Vec::new();
+    };
+
+    // The index only stores line numbers, so read the file to find the word and its columns
+    let source = match std::fs::read_to_string(&path) {
+        Ok(source) => source,
+        Err(e) => {
+            tracing::error!("Failed to read {}: {}", path.display(), e);
+            return Vec::new();
+        }
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let Some(name) = lines
+        .get(position.line as usize)
+        .and_then(|line| identifier_at(line, utf16_to_byte_column(line, position.character as usize)))
+    else {
+        return Vec::new();
+    };
+
+    let mut highlights: Vec<DocumentHighlight> = symbols
+        .iter()
+        .filter(|symbol| symbol.name == name)
+        .filter_map(|symbol| {
+            let line = symbol.context.line_number.checked_sub(1)?;
+            let column = name_column(lines.get(line)?, &name)?;
+            Some(DocumentHighlight {
+                range: Range {
+                    start: Position { line: line as u32, character: column as u32 },
+                    end: Position {
+                        line: line as u32,
+                        character: (column + name.encode_utf16().count()) as u32,
+                    },
+                },
+                kind: Some(DocumentHighlightKind::TEXT),
+            })
+        })
+        .collect();
+    highlights.sort_by_key(|h| h.range.start.line);
+    highlights
+}
+
+/// Symbols grouped by the URI of the file defining them, so per-document requests
+/// are a single map lookup instead of a scan over every symbol
+type SymbolsByUri = HashMap<String, Vec<Symbol>>;
+
+fn uri_to_path(uri: &Uri) -> Option<PathBuf> {
+    Url::parse(uri.as_str()).ok()?.to_file_path().ok()
+}
+
+/// Normalize a file URI so that client-sent and server-built URIs for the same file are equal
+/// (clients differ in which characters they percent-encode)
+fn uri_key(uri: &Uri) -> String {
+    uri_to_path(uri)
+        .and_then(|path| Url::from_file_path(path).ok())
+        .map(|url| url.to_string())
+        .unwrap_or_else(|| uri.as_str().to_string())
+}
+
+/// Build the URI index, converting each file path to a URI only once
+fn build_symbols_by_uri(
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+) -> SymbolsByUri {
+    let mut by_file: HashMap<usize, Vec<Symbol>> = HashMap::new();
+    for symbol in functions.iter().chain(classes.iter()) {
+        by_file.entry(symbol.context.file_path_index).or_default().push(symbol.clone());
+    }
+
+    by_file
+        .into_iter()
+        .filter_map(|(file_index, symbols)| {
+            let url = file_url(path_registry.get_path(file_index))?;
+            Some((url.to_string(), symbols))
+        })
+        .collect()
+}
+
+fn symbols_for_uri<'a>(symbols_by_uri: &'a SymbolsByUri, uri: &Uri) -> &'a [Symbol] {
+    symbols_by_uri.get(&uri_key(uri)).map(Vec::as_slice).unwrap_or(&[])
+}
+
+/// Directory module names of `path` are relative to: the innermost added workspace folder
+/// containing it, as `collect_folder_symbols` used, or else the directory indexed at startup
+fn module_base_dir<'a>(path: &Path, workspace_folders: &'a [PathBuf], base_dir: &'a Path) -> &'a Path {
+    workspace_folders.iter()
+        .filter(|folder| path.starts_with(folder))
+        .max_by_key(|folder| folder.components().count())
+        .map_or(base_dir, PathBuf::as_path)
+}
+
+/// Drop the symbols of the file at `file_index` from the sets workspace/symbol searches
+fn remove_file_symbols(file_index: usize, functions: &mut HashSet<Symbol>, classes: &mut HashSet<Symbol>) {
+    let keep = |symbol: &Symbol| symbol.context.file_path_index != file_index;
+    functions.retain(keep);
+    classes.retain(keep);
+}
+
+/// Re-parse one file after it changed on disk and replace its symbols, both in the sets
+/// workspace/symbol searches and in the URI index. Only files already in the path registry
+/// (indexed at startup or in an added workspace folder) can be refreshed.
+fn refresh_symbols_for_uri(
+    uri: &Uri,
+    functions: &mut HashSet<Symbol>,
+    classes: &mut HashSet<Symbol>,
+    symbols_by_uri: &mut SymbolsByUri,
+    path_registry: &PathRegistry,
+    base_dir: &Path,
+    workspace_folders: &[PathBuf],
+) {
+    let key = uri_key(uri);
+    let Some(path) = uri_to_path(uri) else {
+        return;
+    };
+    let Some(file_index) = path_registry.index_of(&path) else {
+        info!("Not refreshing symbols for unindexed file: {}", path.display());
+        return;
+    };
+
+    let base_dir = module_base_dir(&path, workspace_folders, base_dir);
+    let result = create_python_parser().and_then(|mut parser| {
+        collect_file_symbols(&mut parser, &path, base_dir, file_index, &CollectOptions::default())
+    });
+    remove_file_symbols(file_index, functions, classes);
+    match result {
+        Ok((new_functions, new_classes)) => {
+            info!("Refreshed {} symbols for {}", new_functions.len() + new_classes.len(), path.display());
+            symbols_by_uri.insert(key, new_functions.iter().chain(&new_classes).cloned().collect());
+            functions.extend(new_functions);
+            classes.extend(new_classes);
+        }
+        Err(e) => {
+            // Most likely deleted or unreadable; stop serving stale symbols for it
+            tracing::warn!("Failed to refresh symbols for {}: {}", path.display(), e);
+            symbols_by_uri.remove(&key);
+        }
+    }
+}
+
+/// Drop the symbols of a file the client reported as deleted
+fn remove_symbols_for_uri(
+    uri: &Uri,
+    functions: &mut HashSet<Symbol>,
+    classes: &mut HashSet<Symbol>,
+    symbols_by_uri: &mut SymbolsByUri,
+    path_registry: &PathRegistry,
+) {
+    symbols_by_uri.remove(&uri_key(uri));
+    if let Some(file_index) = uri_to_path(uri).and_then(|path| path_registry.index_of(&path)) {
+        remove_file_symbols(file_index, functions, classes);
+    }
+}
+
+/// Whether `path` (absolute, or relative to the working directory) is inside `folder`
+fn is_in_folder(path: &Path, folder: &Path) -> bool {
+    std::path::absolute(path).is_ok_and(|path| path.starts_with(folder))
+}
+
+/// Drop the symbols of every file in `folder`, e.g. after the client removed it from the
+/// workspace. Paths stay registered, so indexes held by in-flight searches remain valid.
+/// Returns the number of symbols removed.
+fn remove_folder_symbols(
+    folder: &Path,
+    functions: &mut HashSet<Symbol>,
+    classes: &mut HashSet<Symbol>,
+    symbols_by_uri: &mut SymbolsByUri,
+    path_registry: &PathRegistry,
+) -> usize {
+    let before = functions.len() + classes.len();
+    let keep = |symbol: &Symbol| !is_in_folder(path_registry.get_path(symbol.context.file_path_index), folder);
+    functions.retain(keep);
+    classes.retain(keep);
+    symbols_by_uri.retain(|uri, _| {
+        Url::parse(uri).ok()
+            .and_then(|url| url.to_file_path().ok())
+            .is_none_or(|path| !path.starts_with(folder))
+    });
+    before - functions.len() - classes.len()
+}
+
+/// Parse the Python files in `folder`, with module names relative to it, into their own
+/// registry. Stops early, leaving the result incomplete, once `cancel` is set.
+fn collect_folder_symbols(folder: &Path, cancel: &Arc<AtomicBool>) -> Result<SymbolStats> {
+    let python_files: Vec<PathBuf> = list_python_files(folder, false)
+        .take_while(|_| !cancel.load(Ordering::Relaxed))
+        .collect();
+    let stats = SymbolStats::new();
+    let options = CollectOptions { cancel: Some(cancel.clone()), ..Default::default() };
+    parse_python_files_parallel_with_options(&python_files, folder, &stats, &options)?;
+    Ok(stats)
+}
+
+/// Add the symbols `collect_folder_symbols` found in `folder` to the server's index, replacing
+/// any the folder already had. Returns the number of symbols added.
+fn merge_folder_symbols(
+    folder: &Path,
+    stats: &SymbolStats,
+    functions: &mut HashSet<Symbol>,
+    classes: &mut HashSet<Symbol>,
+    symbols_by_uri: &mut SymbolsByUri,
+    path_registry: &mut PathRegistry,
+) -> usize {
+    // Re-adding a folder must not duplicate what is already indexed
+    remove_folder_symbols(folder, functions, classes, symbols_by_uri, path_registry);
+
+    // The new symbols point into their own registry; move them over to the server's
+    let folder_registry = stats.path_registry.lock().unwrap();
+    let mut reindex = |symbols: &HashSet<Symbol>| -> HashSet<Symbol> {
+        symbols.iter().cloned().map(|mut symbol| {
+            let path = folder_registry.get_path(symbol.context.file_path_index).clone();
+            symbol.context.file_path_index = path_registry.register_path(path);
+            symbol
+        }).collect()
+    };
+    let new_functions = reindex(&stats.functions.lock().unwrap());
+    let new_classes = reindex(&stats.classes.lock().unwrap());
+
+    symbols_by_uri.extend(build_symbols_by_uri(&new_functions, &new_classes, path_registry));
+    let added = new_functions.len() + new_classes.len();
+    functions.extend(new_functions);
+    classes.extend(new_classes);
+    added
+}
+
+/// An added workspace folder, parsed on a worker thread
+struct IndexedFolder {
+    folder: PathBuf,
+    cancel: Arc<AtomicBool>,
+    stats: Result<SymbolStats>,
+}
+
+/// Indexes added workspace folders on worker threads, so the message loop keeps answering
+/// requests meanwhile, and cancels the indexing of folders removed before it finishes.
+/// Finished folders arrive on the receiver paired with `sender`.
+struct FolderIndexer {
+    // Cancel flags of the folders still being indexed
+    in_progress: HashMap<PathBuf, Arc<AtomicBool>>,
+    sender: Sender<IndexedFolder>,
+}
+
+impl FolderIndexer {
+    fn new(sender: Sender<IndexedFolder>) -> Self {
+        FolderIndexer { in_progress: HashMap::new(), sender }
+    }
+
+    /// Start indexing `folder`, cancelling any earlier indexing of it that is still running
+    fn start(&mut self, folder: PathBuf) {
+        self.cancel(&folder);
+        let cancel = Arc::new(AtomicBool::new(false));
+        self.in_progress.insert(folder.clone(), cancel.clone());
+        let sender = self.sender.clone();
+        std::thread::spawn(move || {
+            let stats = collect_folder_symbols(&folder, &cancel);
+            // Fails only once the server has stopped listening
+            let _ = sender.send(IndexedFolder { folder, cancel, stats });
+        });
+    }
+
+    /// Stop indexing `folder`, if it is still in progress
+    fn cancel(&mut self, folder: &Path) {
+        if let Some(cancel) = self.in_progress.remove(folder) {
+            info!("Cancelling indexing of workspace folder {}", folder.display());
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Whether `indexed` should be added to the index, i.e. its folder was neither removed
+    /// nor added again since its indexing started
+    fn finish(&mut self, indexed: &IndexedFolder) -> bool {
+        match self.in_progress.get(&indexed.folder) {
+            Some(cancel) if Arc::ptr_eq(cancel, &indexed.cancel) => {
+                self.in_progress.remove(&indexed.folder);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Add a folder `FolderIndexer` finished indexing, keeping `workspace_folders` as the added
+/// folders refreshed files take module names from
+fn add_indexed_folder(
+    indexed: IndexedFolder,
+    functions: &mut HashSet<Symbol>,
+    classes: &mut HashSet<Symbol>,
+    symbols_by_uri: &mut SymbolsByUri,
+    path_registry: &mut PathRegistry,
+    workspace_folders: &mut Vec<PathBuf>,
+) {
+    let folder = indexed.folder;
+    match indexed.stats {
+        Ok(stats) => {
+            let added = merge_folder_symbols(&folder, &stats, functions, classes, symbols_by_uri, path_registry);
+            info!("Indexed {} symbols of workspace folder {}", added, folder.display());
+        }
+        Err(e) => tracing::error!("Failed to index workspace folder {}: {}", folder.display(), e),
+    }
+    if !workspace_folders.contains(&folder) {
+        workspace_folders.push(folder);
+    }
+}
+
+/// Apply a workspace folder change: drop the symbols of removed folders, cancelling their
+/// indexing if it is still running, and start indexing added ones in the background
+fn handle_workspace_folders_change(
+    event: WorkspaceFoldersChangeEvent,
+    functions: &mut HashSet<Symbol>,
+    classes: &mut HashSet<Symbol>,
+    symbols_by_uri: &mut SymbolsByUri,
+    path_registry: &PathRegistry,
+    workspace_folders: &mut Vec<PathBuf>,
+    folder_indexer: &mut FolderIndexer,
+) {
+    for folder in event.removed {
+        let Some(path) = uri_to_path(&folder.uri) else {
+            warn!("Ignoring removed workspace folder with a non-file URI: {}", folder.uri.as_str());
+            continue;
+        };
+        folder_indexer.cancel(&path);
+        workspace_folders.retain(|folder| *folder != path);
+        let removed = remove_folder_symbols(&path, functions, classes, symbols_by_uri, path_registry);
+        info!("Removed {} symbols of workspace folder {}", removed, path.display());
+    }
+    for folder in event.added {
+        let Some(path) = uri_to_path(&folder.uri) else {
+            warn!("Ignoring added workspace folder with a non-file URI: {}", folder.uri.as_str());
+            continue;
+        };
+        info!("Indexing workspace folder {}", path.display());
+        folder_indexer.start(path);
+    }
+}
+
+/// Handle a document symbol request with a nested outline of the file's definitions
+fn handle_document_symbol_request(
+    params: DocumentSymbolParams,
+    symbols_by_uri: &SymbolsByUri,
+) -> Vec<DocumentSymbol> {
+    to_document_symbols(symbols_for_uri(symbols_by_uri, &params.text_document.uri))
+}
+
+/// Handle a folding range request using the definition spans stored in the index
+fn handle_folding_range_request(
+    params: FoldingRangeParams,
+    symbols_by_uri: &SymbolsByUri,
+) -> Vec<FoldingRange> {
+    to_folding_ranges(symbols_for_uri(symbols_by_uri, &params.text_document.uri))
+}
+
+/// Handle a semantic tokens range request by marking the names of the file's definitions.
+/// Only definitions are covered; the file is read to place the names within their lines.
+fn handle_semantic_tokens_range_request(
+    params: SemanticTokensRangeParams,
+    symbols_by_uri: &SymbolsByUri,
+) -> SemanticTokensRangeResult {
+    let symbols = symbols_for_uri(symbols_by_uri, &params.text_document.uri);
+    let source = match uri_to_path(&params.text_document.uri) {
+        Some(path) if !symbols.is_empty() => std::fs::read_to_string(&path).unwrap_or_else(|e| {
+            tracing::error!("Failed to read {}: {}", path.display(), e);
+            String::new()
+        }),
+        _ => String::new(),
+    };
+    SemanticTokensRangeResult::Tokens(SemanticTokens {
+        result_id: None,
+        data: to_semantic_tokens(symbols, &source, Some(params.range)),
+    })
+}
+
+/// Custom request returning the definitions enclosing a position, for a breadcrumb trail
+const BREADCRUMBS_METHOD: &str = "pylight/breadcrumbs";
+
+/// Handle a breadcrumbs request: the definitions containing the cursor, outermost first
+/// (e.g. class, then method), or nothing when the cursor is outside every definition
+fn handle_breadcrumbs_request(
+    params: TextDocumentPositionParams,
+    symbols_by_uri: &SymbolsByUri,
+    path_registry: &PathRegistry,
+) -> Vec<SymbolInformation> {
+    let symbols = symbols_for_uri(symbols_by_uri, &params.text_document.uri);
+    let Some(file_path_index) = symbols.first().map(|s| s.context.file_path_index) else {
+        return Vec::new();
+    };
+    let line = params.position.line as usize + 1;
+    let Some(symbol) = symbol_at_position(symbols, file_path_index, line) else {
+        return Vec::new();
+    };
+
+    let mut trail = parent_chain(symbol, symbols);
+    trail.reverse();
+    trail.push(symbol);
+    trail
+        .into_iter()
+        .filter_map(|s| to_symbol_information(s, path_registry, 0, false))
+        .collect()
+}
+
+/// Handle a prepareCallHierarchy request with the definition under the cursor: one named by
+/// the identifier there (preferring a definition on the cursor's line), else the innermost
+/// definition containing the cursor. Calls are not tracked yet, so the incoming and outgoing
+/// calls of the item are always empty; this only lets editors open the hierarchy view.
+fn handle_prepare_call_hierarchy_request(
+    params: CallHierarchyPrepareParams,
+    symbols_by_uri: &SymbolsByUri,
+    path_registry: &PathRegistry,
+) -> Vec<CallHierarchyItem> {
+    let position = params.text_document_position_params.position;
+    let uri = params.text_document_position_params.text_document.uri;
+    let symbols = symbols_for_uri(symbols_by_uri, &uri);
+    let Some(file_path_index) = symbols.first().map(|s| s.context.file_path_index) else {
+        return Vec::new();
+    };
+    let line = position.line as usize + 1;
+
+    let name = uri_to_path(&uri)
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .and_then(|source| {
+            let text = source.lines().nth(position.line as usize)?;
+            identifier_at(text, utf16_to_byte_column(text, position.character as usize))
+        });
+    let named = name.and_then(|name| {
+        symbols
+            .iter()
+            .filter(|symbol| symbol.name == name)
+            .min_by_key(|symbol| (symbol.context.line_number != line, symbol.context.line_number))
+    });
+    let Some(symbol) = named.or_else(|| symbol_at_position(symbols, file_path_index, line)) else {
+        return Vec::new();
+    };
+    to_call_hierarchy_item(symbol, path_registry).into_iter().collect()
+}
+
 /// Main LSP server loop
+#[allow(clippy::too_many_arguments)]
 fn run_server(
     functions: HashSet<Symbol>,
     classes: HashSet<Symbol>,
     path_registry: PathRegistry,
     algorithm: SearchAlgorithm,
     port: Option<u16>, // Added port argument
+    show_scores: bool,
+    container_name: ContainerNameOptions,
+    empty_query_returns_all: bool,
+    base_dir: PathBuf,
+    query_cache_size: usize,
+    min_query_len: usize,
 ) -> Result<()> {
     info!(
         "Starting LSP server with {} functions and {} classes",
@@ -245,10 +869,25 @@ fn run_server(
     // Create a tokio runtime for handling async tasks
     let rt = Runtime::new()?;
     
-    // Wrap our data structures in Arc for sharing between threads
-    let functions = Arc::new(functions);
-    let classes = Arc::new(classes);
-    let path_registry = Arc::new(path_registry);
+    // Per-document requests are answered on this thread, so the URI index needs no locking
+    let index_start = Instant::now();
+    let mut symbols_by_uri = build_symbols_by_uri(&functions, &classes, &path_registry);
+    info!(
+        "Indexed symbols for {} documents in {}ms",
+        symbols_by_uri.len(),
+        index_start.elapsed().as_millis()
+    );
+    
+    // Wrap our data structures in Arc for sharing between threads. Workspace folder changes
+    // replace them copy-on-write, leaving in-flight searches with the index they started on.
+    let mut functions = Arc::new(functions);
+    let mut classes = Arc::new(classes);
+    let mut path_registry = Arc::new(path_registry);
+    // Folders added since startup, whose files take module names relative to the folder
+    let mut workspace_folders: Vec<PathBuf> = Vec::new();
+    let query_cache = Arc::new(Mutex::new(QueryCache::new(query_cache_size)));
+    let (indexed_sender, indexed_receiver) = crossbeam_channel::unbounded();
+    let mut folder_indexer = FolderIndexer::new(indexed_sender);
 
     // Create the LSP connection based on whether a port is specified
     let (connection, io_threads) = if let Some(port) = port {
@@ -265,21 +904,83 @@ fn run_server(
     // Handle the initialize request from the client
     let server_capabilities = serde_json::to_value(ServerCapabilities {
         workspace_symbol_provider: Some(OneOf::Left(true)), // Indicate we support workspace symbol requests
+        document_highlight_provider: Some(OneOf::Left(true)),
+        folding_range_provider: Some(FoldingRangeProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        // Definition names only, for the visible range
+        semantic_tokens_provider: Some(SemanticTokensServerCapabilities::SemanticTokensOptions(SemanticTokensOptions {
+            legend: semantic_tokens_legend(),
+            range: Some(true),
+            ..SemanticTokensOptions::default()
+        })),
+        // Only saves are needed, to refresh the symbols of changed files
+        text_document_sync: Some(TextDocumentSyncCapability::Options(TextDocumentSyncOptions {
+            save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+            ..TextDocumentSyncOptions::default()
+        })),
+        // Anchors only; incoming and outgoing calls are empty until references are tracked
+        call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+        // Added folders are indexed and removed ones dropped
+        workspace: Some(WorkspaceServerCapabilities {
+            workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                supported: Some(true),
+                change_notifications: Some(OneOf::Left(true)),
+            }),
+            file_operations: None,
+        }),
         // We're not handling other capabilities
         ..ServerCapabilities::default()
     })?;
 
     // Process initialize request
-    let _initialize_result = connection.initialize(server_capabilities)?;
+    let initialize_params = connection.initialize(server_capabilities)?;
     info!("LSP server initialized successfully");
 
+    // Client configuration takes precedence over the command line
+    let include_score = show_scores_from_initialize_params(&initialize_params).unwrap_or(show_scores);
+    if include_score {
+        info!("Including match scores in symbol names");
+    }
+    let list_on_empty_query = empty_query_returns_all_from_initialize_params(&initialize_params)
+        .unwrap_or(empty_query_returns_all);
+    let container_options = ContainerNameOptions {
+        mode: container_name_mode_from_initialize_params(&initialize_params).unwrap_or(container_name.mode),
+        separator: container_separator_from_initialize_params(&initialize_params).unwrap_or(container_name.separator),
+        qualified_names: qualified_names_from_initialize_params(&initialize_params).unwrap_or(container_name.qualified_names),
+    };
+    info!("Using container name options: {:?}", container_options);
+    let min_query_len = min_query_len_from_initialize_params(&initialize_params).unwrap_or(min_query_len);
+
     // Main message loop
     info!("Entering main message loop");
     
     // Clone connection.sender for use in async tasks
     let sender = connection.sender.clone();
     
-    for msg in &connection.receiver {
+    loop {
+        let msg = select! {
+            recv(connection.receiver) -> msg => match msg {
+                Ok(msg) => msg,
+                Err(_) => break,
+            },
+            // Never disconnected, since `folder_indexer` holds a sender
+            recv(indexed_receiver) -> indexed => {
+                if let Ok(indexed) = indexed {
+                    if folder_indexer.finish(&indexed) {
+                        query_cache.lock().unwrap().clear();
+                        add_indexed_folder(
+                            indexed,
+                            Arc::make_mut(&mut functions),
+                            Arc::make_mut(&mut classes),
+                            &mut symbols_by_uri,
+                            Arc::make_mut(&mut path_registry),
+                            &mut workspace_folders,
+                        );
+                    }
+                }
+                continue;
+            },
+        };
         match msg {
             Message::Request(req) => {
                 if connection.handle_shutdown(&req)? {
@@ -302,7 +1003,12 @@ fn run_server(
                         let path_registry_clone = path_registry.clone();
                         let sender_clone = sender.clone();
                         let req_id = req.id.clone();
-                        let alg = algorithm.clone();
+                        let alg = algorithm;
+                        let show_score = include_score;
+                        let list_all = list_on_empty_query;
+                        let min_len = min_query_len;
+                        let container = container_options.clone();
+                        let cache = query_cache.clone();
                         
                         match serde_json::from_value::<WorkspaceSymbolParams>(req.params) {
                             Ok(params) => {
@@ -311,15 +1017,36 @@ fn run_server(
                                     params.query
                                 );
                                 
+                                let (cached, generation) = {
+                                    let mut cache = query_cache.lock().unwrap();
+                                    (cache.get(&params.query), cache.generation())
+                                };
+                                if let Some(symbols) = cached {
+                                    info!("Answering query '{}' from cache with {} results", params.query, symbols.len());
+                                    let resp = Response {
+                                        id: req_id,
+                                        result: Some(serde_json::to_value(symbols)?),
+                                        error: None,
+                                    };
+                                    connection.sender.send(Message::Response(resp))?;
+                                    continue;
+                                }
+                                
                                 // Spawn an async task to handle the request
                                 rt.spawn(async move {
+                                    let query = params.query.clone();
                                     let symbols = handle_workspace_symbol_request_async(
                                         params,
                                         functions_clone,
                                         classes_clone,
                                         path_registry_clone,
                                         alg,
+                                        show_score,
+                                        container,
+                                        list_all,
+                                        min_len,
                                     ).await;
+                                    cache.lock().unwrap().insert(query, symbols.clone(), generation);
                                     
                                     let symbol_count = symbols.len();
                                     info!("Async search completed with {} results", symbol_count);
@@ -371,6 +1098,174 @@ fn run_server(
                         }
                     },
 
+                    // Cheap enough to answer inline: one file read and a scan of the index
+                    "textDocument/documentHighlight" => {
+                        let resp = match serde_json::from_value::<DocumentHighlightParams>(req.params) {
+                            Ok(params) => {
+                                let highlights = handle_document_highlight_request(params, &symbols_by_uri);
+                                info!("Sending {} document highlights", highlights.len());
+                                Response {
+                                    id: req.id,
+                                    result: Some(serde_json::to_value(highlights)?),
+                                    error: None,
+                                }
+                            },
+                            Err(e) => {
+                                tracing::error!("Failed to parse documentHighlight params: {}", e);
+                                Response {
+                                    id: req.id,
+                                    result: None,
+                                    error: Some(ResponseError {
+                                        code: ErrorCode::InvalidParams as i32,
+                                        message: format!("Invalid params: {}", e),
+                                        data: None,
+                                    }),
+                                }
+                            }
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    },
+
+                    "textDocument/documentSymbol" => {
+                        let resp = match serde_json::from_value::<DocumentSymbolParams>(req.params) {
+                            Ok(params) => {
+                                let symbols = handle_document_symbol_request(params, &symbols_by_uri);
+                                Response {
+                                    id: req.id,
+                                    result: Some(serde_json::to_value(symbols)?),
+                                    error: None,
+                                }
+                            },
+                            Err(e) => {
+                                tracing::error!("Failed to parse documentSymbol params: {}", e);
+                                Response {
+                                    id: req.id,
+                                    result: None,
+                                    error: Some(ResponseError {
+                                        code: ErrorCode::InvalidParams as i32,
+                                        message: format!("Invalid params: {}", e),
+                                        data: None,
+                                    }),
+                                }
+                            }
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    },
+
+                    "textDocument/foldingRange" => {
+                        let resp = match serde_json::from_value::<FoldingRangeParams>(req.params) {
+                            Ok(params) => {
+                                let ranges = handle_folding_range_request(params, &symbols_by_uri);
+                                Response {
+                                    id: req.id,
+                                    result: Some(serde_json::to_value(ranges)?),
+                                    error: None,
+                                }
+                            },
+                            Err(e) => {
+                                tracing::error!("Failed to parse foldingRange params: {}", e);
+                                Response {
+                                    id: req.id,
+                                    result: None,
+                                    error: Some(ResponseError {
+                                        code: ErrorCode::InvalidParams as i32,
+                                        message: format!("Invalid params: {}", e),
+                                        data: None,
+                                    }),
+                                }
+                            }
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    },
+
+                    "textDocument/semanticTokens/range" => {
+                        let resp = match serde_json::from_value::<SemanticTokensRangeParams>(req.params) {
+                            Ok(params) => {
+                                let tokens = handle_semantic_tokens_range_request(params, &symbols_by_uri);
+                                Response {
+                                    id: req.id,
+                                    result: Some(serde_json::to_value(tokens)?),
+                                    error: None,
+                                }
+                            },
+                            Err(e) => {
+                                tracing::error!("Failed to parse semanticTokens/range params: {}", e);
+                                Response {
+                                    id: req.id,
+                                    result: None,
+                                    error: Some(ResponseError {
+                                        code: ErrorCode::InvalidParams as i32,
+                                        message: format!("Invalid params: {}", e),
+                                        data: None,
+                                    }),
+                                }
+                            }
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    },
+
+                    BREADCRUMBS_METHOD => {
+                        let resp = match serde_json::from_value::<TextDocumentPositionParams>(req.params) {
+                            Ok(params) => {
+                                let trail = handle_breadcrumbs_request(params, &symbols_by_uri, &path_registry);
+                                Response {
+                                    id: req.id,
+                                    result: Some(serde_json::to_value(trail)?),
+                                    error: None,
+                                }
+                            },
+                            Err(e) => {
+                                tracing::error!("Failed to parse breadcrumbs params: {}", e);
+                                Response {
+                                    id: req.id,
+                                    result: None,
+                                    error: Some(ResponseError {
+                                        code: ErrorCode::InvalidParams as i32,
+                                        message: format!("Invalid params: {}", e),
+                                        data: None,
+                                    }),
+                                }
+                            }
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    },
+
+                    "textDocument/prepareCallHierarchy" => {
+                        let resp = match serde_json::from_value::<CallHierarchyPrepareParams>(req.params) {
+                            Ok(params) => {
+                                let items = handle_prepare_call_hierarchy_request(params, &symbols_by_uri, &path_registry);
+                                Response {
+                                    id: req.id,
+                                    result: Some(serde_json::to_value(items)?),
+                                    error: None,
+                                }
+                            },
+                            Err(e) => {
+                                tracing::error!("Failed to parse prepareCallHierarchy params: {}", e);
+                                Response {
+                                    id: req.id,
+                                    result: None,
+                                    error: Some(ResponseError {
+                                        code: ErrorCode::InvalidParams as i32,
+                                        message: format!("Invalid params: {}", e),
+                                        data: None,
+                                    }),
+                                }
+                            }
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    },
+
+                    // Calls are not tracked yet, so there are never any
+                    "callHierarchy/incomingCalls" | "callHierarchy/outgoingCalls" => {
+                        let resp = Response {
+                            id: req.id,
+                            result: Some(Value::Array(Vec::new())),
+                            error: None,
+                        };
+                        connection.sender.send(Message::Response(resp))?;
+                    },
+
                     // For any other requests we don't handle, respond with null
                     _ => {
                         info!("Received unsupported request: {}", req.method);
@@ -388,6 +1283,68 @@ fn run_server(
             },
             Message::Notification(not) => {
                 info!("Received notification: {}", not.method);
+                match not.method.as_str() {
+                    "textDocument/didSave" => {
+                        query_cache.lock().unwrap().clear();
+                        match serde_json::from_value::<DidSaveTextDocumentParams>(not.params) {
+                            Ok(params) => refresh_symbols_for_uri(
+                                &params.text_document.uri,
+                                Arc::make_mut(&mut functions),
+                                Arc::make_mut(&mut classes),
+                                &mut symbols_by_uri,
+                                &path_registry,
+                                &base_dir,
+                                &workspace_folders,
+                            ),
+                            Err(e) => tracing::error!("Failed to parse didSave params: {}", e),
+                        }
+                    },
+                    "workspace/didChangeWatchedFiles" => {
+                        query_cache.lock().unwrap().clear();
+                        match serde_json::from_value::<DidChangeWatchedFilesParams>(not.params) {
+                            Ok(params) => {
+                                for change in params.changes {
+                                    if change.typ == FileChangeType::DELETED {
+                                        remove_symbols_for_uri(
+                                            &change.uri,
+                                            Arc::make_mut(&mut functions),
+                                            Arc::make_mut(&mut classes),
+                                            &mut symbols_by_uri,
+                                            &path_registry,
+                                        );
+                                    } else {
+                                        refresh_symbols_for_uri(
+                                            &change.uri,
+                                            Arc::make_mut(&mut functions),
+                                            Arc::make_mut(&mut classes),
+                                            &mut symbols_by_uri,
+                                            &path_registry,
+                                            &base_dir,
+                                            &workspace_folders,
+                                        );
+                                    }
+                                }
+                            },
+                            Err(e) => tracing::error!("Failed to parse didChangeWatchedFiles params: {}", e),
+                        }
+                    },
+                    "workspace/didChangeWorkspaceFolders" => {
+                        query_cache.lock().unwrap().clear();
+                        match serde_json::from_value::<DidChangeWorkspaceFoldersParams>(not.params) {
+                            Ok(params) => handle_workspace_folders_change(
+                                params.event,
+                                Arc::make_mut(&mut functions),
+                                Arc::make_mut(&mut classes),
+                                &mut symbols_by_uri,
+                                &path_registry,
+                                &mut workspace_folders,
+                                &mut folder_indexer,
+                            ),
+                            Err(e) => tracing::error!("Failed to parse didChangeWorkspaceFolders params: {}", e),
+                        }
+                    },
+                    _ => {}
+                }
             }
         }
     }
@@ -416,21 +1373,33 @@ fn main() -> Result<()> {
 
     info!("Starting LSP server with args: {:?}", args);
 
+    // Module names are relative to the scan directory, so it must be resolved like the files
+    let directory = if args.absolute_paths && args.load.is_none() {
+        args.directory.canonicalize()?
+    } else {
+        args.directory.clone()
+    };
+
     // Decide whether to load from file or scan directory
     let (functions, classes, path_registry) = if let Some(load_path) = &args.load {
         info!("Loading symbols from file: {}", load_path.display());
         load_symbols_from_file(load_path)?
     } else {
-        info!("Scanning directory: {}", args.directory.display());
+        info!("Scanning directory: {}", directory.display());
 
         // Find all Python files
         let python_files: Vec<PathBuf> =
-            list_python_files(&args.directory, args.follow_links).collect();
+            list_python_files(&directory, args.follow_links).collect();
+        let python_files = if args.absolute_paths {
+            canonicalize_paths(python_files)
+        } else {
+            python_files
+        };
         info!("Found {} Python files", python_files.len());
 
         // Parse Python files and collect symbols
         let stats = SymbolStats::new();
-        parse_python_files_parallel(&python_files, &args.directory, &stats)?;
+        parse_python_files_parallel_with_threads(&python_files, scan_base_dir(&directory), &stats, args.threads)?;
 
         let functions = stats.functions.lock().unwrap().clone();
         let classes = stats.classes.lock().unwrap().clone();
@@ -449,8 +1418,30 @@ fn main() -> Result<()> {
         classes.len()
     );
 
+    if args.print_stats_and_exit {
+        let summary = summarize_index(&functions, &classes, &path_registry);
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
     // Run the LSP server with the loaded symbols
-    run_server(functions, classes, path_registry, args.algorithm, args.port)?;
+    run_server(
+        functions,
+        classes,
+        path_registry,
+        args.algorithm,
+        args.port,
+        args.show_scores,
+        ContainerNameOptions {
+            mode: args.container_name,
+            separator: args.container_separator,
+            qualified_names: args.qualified_names,
+        },
+        args.empty_query_returns_all,
+        scan_base_dir(&directory).to_path_buf(),
+        args.query_cache_size,
+        args.min_query_len,
+    )?;
 
     Ok(())
 }
@@ -460,9 +1451,10 @@ mod tests {
     use super::*;
     use flate2::write::GzEncoder;
     use flate2::Compression;
-    use lsp_types::{Position, Range, SymbolKind, Uri};
+    use lsp_types::{Position, Range, SymbolKind, Uri, WorkspaceFolder};
     use serde_json::{json, Value};
     use std::collections::HashSet;
+    use std::fs::File;
     use std::io::{self, BufRead, BufReader, Write};
     use std::path::PathBuf;
     use std::process::{Command, Stdio};
@@ -515,6 +1507,7 @@ mod tests {
             context: SymbolContext {
                 symbol_type: kind,
                 line_number: line,
+                end_line_number: line,
                 file_path_index: file_index,
                 parent_context: parent
                     .map(|p| {
@@ -527,6 +1520,7 @@ mod tests {
                     .unwrap_or_default(),
                 fully_qualified_module: module.to_string(),
                 module: module.to_string(),
+                ..Default::default()
             },
         }
     }
@@ -601,107 +1595,463 @@ mod tests {
     }
 
     #[test]
-    fn test_to_lsp_symbol_information_conversion() -> Result<(), Box<dyn std::error::Error>> {
-        let registry = create_test_path_registry();
-        let symbol = create_test_symbol("my_function", SymbolType::Function, 10, 0, None, "file1");
-        let score = 100;
+    fn test_show_scores_from_initialize_params() {
+        assert_eq!(
+            show_scores_from_initialize_params(&json!({ "initializationOptions": { "showScores": true } })),
+            Some(true)
+        );
+        assert_eq!(
+            show_scores_from_initialize_params(&json!({ "initializationOptions": { "showScores": false } })),
+            Some(false)
+        );
+        assert_eq!(show_scores_from_initialize_params(&json!({ "initializationOptions": {} })), None);
+        assert_eq!(show_scores_from_initialize_params(&json!({ "processId": null })), None);
+    }
 
-        let lsp_info_opt = to_lsp_symbol_information(&symbol, &registry, score);
+    #[test]
+    fn test_container_name_mode_from_initialize_params() {
+        assert_eq!(
+            container_name_mode_from_initialize_params(&json!({ "initializationOptions": { "containerName": "module" } })),
+            Some(ContainerNameMode::Module)
+        );
+        assert_eq!(
+            container_name_mode_from_initialize_params(&json!({ "initializationOptions": { "containerName": "bogus" } })),
+            None
+        );
+        assert_eq!(container_name_mode_from_initialize_params(&json!({ "processId": null })), None);
+        assert_eq!(
+            container_separator_from_initialize_params(&json!({ "initializationOptions": { "containerSeparator": "::" } })),
+            Some("::".to_string())
+        );
+    }
 
-        assert!(lsp_info_opt.is_some());
-        let lsp_info = lsp_info_opt.unwrap();
+    #[test]
+    fn test_identifier_at() {
+        let line = "    def get_value(self):";
+        assert_eq!(identifier_at(line, 8), Some("get_value".to_string()));
+        assert_eq!(identifier_at(line, 12), Some("get_value".to_string()));
+        // Just past the end of the word
+        assert_eq!(identifier_at(line, 17), Some("get_value".to_string()));
+        assert_eq!(identifier_at(line, 0), None);
+        assert_eq!(identifier_at(line, 100), None);
+
+        // Editors send UTF-16 columns: the emoji is one char but two code units
+        let line = "s = \"\u{1F600}\"; total = 1";
+        let just_after_total = "s = \"\u{1F600}\"; total".encode_utf16().count();
+        assert_eq!(identifier_at(line, utf16_to_byte_column(line, just_after_total)), Some("total".to_string()));
+        assert_eq!(identifier_at(line, utf16_to_byte_column(line, 6)), None, "Inside the emoji");
+        assert_eq!(identifier_at("caf\u{e9}_menu()", utf16_to_byte_column("caf\u{e9}_menu()", 4)), Some("caf\u{e9}_menu".to_string()));
+
+        let line = "def \u{1F600}_unused(): pass; def total(): pass";
+        assert_eq!(name_column(line, "total"), Some(line.find("total").unwrap() - 2));
+    }
 
-        let expected_name = if cfg!(debug_assertions) {
-            "my_function (100)".to_string()
-        } else {
-            "my_function".to_string()
-        };
-        assert_eq!(lsp_info.name, expected_name);
-        assert_eq!(lsp_info.kind, SymbolKind::FUNCTION);
-        let expected_uri: Uri = Url::from_file_path(registry.get_path(0))
-            .unwrap()
-            .as_str()
-            .parse()?;
-        assert_eq!(lsp_info.location.uri, expected_uri);
+    #[test]
+    fn test_document_highlight_same_named_methods() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("shapes.py");
+        std::fs::write(&file_path, r#"class Circle:
+    def area(self):
+        return 3.14
+
+class Square:
+    def area(self):
+        return 1.0
+
+def area_total():
+    pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_threads(std::slice::from_ref(&file_path), temp_dir.path(), &stats, None)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let registry = stats.path_registry.lock().unwrap();
+
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        let params: DocumentHighlightParams = serde_json::from_value(json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": 1, "character": 10 }
+        }))?;
+
+        let symbols_by_uri = build_symbols_by_uri(&functions, &classes, &registry);
+        let highlights = handle_document_highlight_request(params, &symbols_by_uri);
+        let ranges: Vec<Range> = highlights.iter().map(|h| h.range).collect();
         assert_eq!(
-            lsp_info.location.range,
-            Range {
-                start: Position {
-                    line: 9,
-                    character: 0
+            ranges,
+            vec![
+                Range {
+                    start: Position { line: 1, character: 8 },
+                    end: Position { line: 1, character: 12 },
                 },
-                end: Position {
-                    line: 9,
-                    character: 0
+                Range {
+                    start: Position { line: 5, character: 8 },
+                    end: Position { line: 5, character: 12 },
                 },
-            }
+            ]
         );
-        assert_eq!(lsp_info.container_name, Some("file1".to_string()));
-        assert!(lsp_info.tags.is_none());
         Ok(())
     }
 
     #[test]
-    fn test_to_lsp_symbol_information_class_conversion() -> Result<(), Box<dyn std::error::Error>> {
-        let registry = create_test_path_registry();
-        let symbol = create_test_symbol("MyClass", SymbolType::Class, 25, 1, None, "file2");
-        let score = 50;
+    fn test_breadcrumbs_request_nested_method() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("shapes.py");
+        std::fs::write(&file_path, r#"class Circle:
+    def area(self):
+        return 3.14
+
+def area_total():
+    pass
+"#)?;
 
-        let lsp_info_opt = to_lsp_symbol_information(&symbol, &registry, score);
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_threads(std::slice::from_ref(&file_path), temp_dir.path(), &stats, None)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let registry = stats.path_registry.lock().unwrap();
+        let symbols_by_uri = build_symbols_by_uri(&functions, &classes, &registry);
+
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        let request = |line: u32| -> Result<Vec<String>> {
+            let params: TextDocumentPositionParams = serde_json::from_value(json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": 8 }
+            }))?;
+            Ok(handle_breadcrumbs_request(params, &symbols_by_uri, &registry)
+                .into_iter()
+                .map(|s| s.name)
+                .collect())
+        };
+
+        assert_eq!(request(2)?, vec!["Circle", "area"]);
+        assert_eq!(request(5)?, vec!["area_total"]);
+        assert!(request(3)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_prepare_call_hierarchy_returns_symbol_under_cursor() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("billing.py");
+        std::fs::write(&file_path, r#"class Invoice:
+    def total(self):
+        return tax(1)
 
-        assert!(lsp_info_opt.is_some());
-        let lsp_info = lsp_info_opt.unwrap();
+def tax(amount):
+    return amount
 
-        let expected_name = if cfg!(debug_assertions) {
-            "MyClass (50)".to_string()
-        } else {
-            "MyClass".to_string()
+def label():
+    return "😀😀" + tax(2)
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_threads(std::slice::from_ref(&file_path), temp_dir.path(), &stats, None)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let registry = stats.path_registry.lock().unwrap();
+        let symbols_by_uri = build_symbols_by_uri(&functions, &classes, &registry);
+
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        let prepare = |line: u32, character: u32| -> Result<Vec<CallHierarchyItem>> {
+            let params: CallHierarchyPrepareParams = serde_json::from_value(json!({
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character }
+            }))?;
+            Ok(handle_prepare_call_hierarchy_request(params, &symbols_by_uri, &registry))
         };
-        assert_eq!(lsp_info.name, expected_name);
-        assert_eq!(lsp_info.kind, SymbolKind::CLASS);
-        let expected_uri: Uri = Url::from_file_path(registry.get_path(1))
-            .unwrap()
-            .as_str()
-            .parse()?;
-        assert_eq!(lsp_info.location.uri, expected_uri);
-        assert_eq!(lsp_info.location.range.start.line, 24);
-        assert_eq!(lsp_info.container_name, Some("file2".to_string()));
+
+        // On a definition's name
+        let items = prepare(1, 9)?;
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].name, "total");
+        assert_eq!(items[0].kind, SymbolKind::FUNCTION);
+        assert_eq!(items[0].uri, uri);
+        assert_eq!(items[0].selection_range.start.line, 1);
+
+        // On a call, the called definition
+        let items = prepare(2, 16)?;
+        assert_eq!(items[0].name, "tax");
+        assert_eq!(items[0].range.start.line, 4);
+
+        // Elsewhere inside a definition, the innermost one containing the cursor
+        let items = prepare(2, 9)?;
+        assert_eq!(items[0].name, "total");
+        assert!(prepare(3, 0)?.is_empty());
+
+        // Columns count UTF-16 code units, two for each emoji before the call
+        let just_after_tax = "    return \"\u{1F600}\u{1F600}\" + tax".encode_utf16().count() as u32;
+        assert_eq!(prepare(8, just_after_tax)?[0].name, "tax");
         Ok(())
     }
 
     #[test]
-    fn test_to_lsp_symbol_information_method_conversion() -> Result<(), Box<dyn std::error::Error>>
-    {
-        let registry = create_test_path_registry();
-        let symbol = create_test_symbol(
-            "my_method",
-            SymbolType::Method,
-            30,
-            1,
-            Some("MyClass"),
-            "file2",
-        );
-        let score = 75;
+    fn test_folding_range_request_class_with_two_methods() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("counter.py");
+        let other_path = temp_dir.path().join("other.py");
+        std::fs::write(&file_path, r#"class Counter:
+    def increment(self):
+        self.count += 1
+
+    def reset(self):
+        self.count = 0
+"#)?;
+        std::fs::write(&other_path, "def elsewhere():\n    pass\n")?;
 
-        let lsp_info_opt = to_lsp_symbol_information(&symbol, &registry, score);
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_threads(&[file_path.clone(), other_path], temp_dir.path(), &stats, None)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let registry = stats.path_registry.lock().unwrap();
+
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        let params: FoldingRangeParams = serde_json::from_value(json!({
+            "textDocument": { "uri": uri }
+        }))?;
+
+        let symbols_by_uri = build_symbols_by_uri(&functions, &classes, &registry);
+        let ranges = handle_folding_range_request(params, &symbols_by_uri);
+        assert_eq!(ranges.len(), 3);
+        let lines: Vec<(u32, u32)> = ranges.iter().map(|r| (r.start_line, r.end_line)).collect();
+        assert_eq!(lines, vec![(0, 5), (1, 2), (4, 5)]);
+        Ok(())
+    }
 
-        assert!(lsp_info_opt.is_some());
-        let lsp_info = lsp_info_opt.unwrap();
+    #[test]
+    fn test_document_symbols_by_uri_and_refresh_on_save() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("service.py");
+        std::fs::write(&file_path, "class Service:\n    def start(self):\n        pass\n")?;
 
-        let expected_name = if cfg!(debug_assertions) {
-            "my_method (75)".to_string()
-        } else {
-            "my_method".to_string()
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_threads(std::slice::from_ref(&file_path), temp_dir.path(), &stats, None)?;
+        let mut functions = stats.functions.lock().unwrap().clone();
+        let mut classes = stats.classes.lock().unwrap().clone();
+        let registry = stats.path_registry.lock().unwrap();
+        let mut symbols_by_uri = build_symbols_by_uri(&functions, &classes, &registry);
+        assert_eq!(symbols_by_uri.len(), 1);
+
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        let params: DocumentSymbolParams = serde_json::from_value(json!({
+            "textDocument": { "uri": uri }
+        }))?;
+        let outline = handle_document_symbol_request(params.clone(), &symbols_by_uri);
+        assert_eq!(outline.len(), 1);
+        assert_eq!(outline[0].name, "Service");
+        assert_eq!(outline[0].children.as_ref().unwrap()[0].name, "start");
+
+        // A saved edit is picked up without re-indexing everything
+        std::fs::write(&file_path, "class Service:\n    def start(self):\n        pass\n\n    def stop(self):\n        pass\n")?;
+        refresh_symbols_for_uri(&uri, &mut functions, &mut classes, &mut symbols_by_uri, &registry, temp_dir.path(), &[]);
+        let outline = handle_document_symbol_request(params.clone(), &symbols_by_uri);
+        let methods: Vec<&str> = outline[0].children.as_ref().unwrap().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(methods, vec!["start", "stop"]);
+
+        // Deleted files stop returning symbols
+        std::fs::remove_file(&file_path)?;
+        refresh_symbols_for_uri(&uri, &mut functions, &mut classes, &mut symbols_by_uri, &registry, temp_dir.path(), &[]);
+        assert!(handle_document_symbol_request(params, &symbols_by_uri).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_workspace_symbol_search_after_save_and_delete() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("jobs.py");
+        std::fs::write(&file_path, "def old_name():\n    pass\n")?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_threads(std::slice::from_ref(&file_path), temp_dir.path(), &stats, None)?;
+        let mut functions = stats.functions.lock().unwrap().clone();
+        let mut classes = stats.classes.lock().unwrap().clone();
+        let registry = stats.path_registry.lock().unwrap();
+        let mut symbols_by_uri = build_symbols_by_uri(&functions, &classes, &registry);
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        let search = |query: &str, functions: &HashSet<Symbol>, classes: &HashSet<Symbol>| {
+            let params = WorkspaceSymbolParams { query: query.to_string(), ..Default::default() };
+            handle_workspace_symbol_request(params, functions, classes, &registry, SearchAlgorithm::Skim, false, &ContainerNameOptions::default(), false)
         };
-        assert_eq!(lsp_info.name, expected_name);
-        assert_eq!(lsp_info.kind, SymbolKind::FUNCTION);
-        let expected_uri: Uri = Url::from_file_path(registry.get_path(1))
-            .unwrap()
-            .as_str()
-            .parse()?;
-        assert_eq!(lsp_info.location.uri, expected_uri);
-        assert_eq!(lsp_info.location.range.start.line, 29);
-        assert_eq!(lsp_info.container_name, Some("MyClass".to_string()));
+        assert_eq!(search("old_name", &functions, &classes).len(), 1);
+
+        // A rename saved to disk replaces the old name in searches
+        std::fs::write(&file_path, "def new_name():\n    pass\n\nclass Added:\n    pass\n")?;
+        refresh_symbols_for_uri(&uri, &mut functions, &mut classes, &mut symbols_by_uri, &registry, temp_dir.path(), &[]);
+        assert!(search("old_name", &functions, &classes).is_empty());
+        assert_eq!(search("new_name", &functions, &classes).len(), 1);
+        assert_eq!(search("Added", &functions, &classes).len(), 1);
+
+        // A deleted file's symbols are no longer found
+        std::fs::remove_file(&file_path)?;
+        remove_symbols_for_uri(&uri, &mut functions, &mut classes, &mut symbols_by_uri, &registry);
+        assert!(functions.is_empty() && classes.is_empty());
+        assert!(search("new_name", &functions, &classes).is_empty());
+        assert!(symbols_for_uri(&symbols_by_uri, &uri).is_empty());
+        Ok(())
+    }
+
+    /// Apply a workspace folder change as run_server does, waiting for added folders to be indexed
+    fn change_workspace_folders(
+        event: WorkspaceFoldersChangeEvent,
+        functions: &mut HashSet<Symbol>,
+        classes: &mut HashSet<Symbol>,
+        symbols_by_uri: &mut SymbolsByUri,
+        registry: &mut PathRegistry,
+        workspace_folders: &mut Vec<PathBuf>,
+    ) {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut folder_indexer = FolderIndexer::new(sender);
+        let added = event.added.len();
+        handle_workspace_folders_change(event, functions, classes, symbols_by_uri, registry, workspace_folders, &mut folder_indexer);
+        for indexed in receiver.iter().take(added) {
+            assert!(folder_indexer.finish(&indexed));
+            add_indexed_folder(indexed, functions, classes, symbols_by_uri, registry, workspace_folders);
+        }
+    }
+
+    #[test]
+    fn test_workspace_folder_removal_drops_its_symbols() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let app_dir = temp_dir.path().join("app");
+        let lib_dir = temp_dir.path().join("lib");
+        std::fs::create_dir_all(&app_dir)?;
+        std::fs::create_dir_all(&lib_dir)?;
+        std::fs::write(app_dir.join("main.py"), "def run():\n    pass\n")?;
+        std::fs::write(lib_dir.join("util.py"), "class Helper:\n    def help(self):\n        pass\n")?;
+        let folder = |path: &Path| -> Result<WorkspaceFolder> {
+            Ok(WorkspaceFolder { uri: Url::from_file_path(path).unwrap().as_str().parse()?, name: String::new() })
+        };
+
+        let (mut functions, mut classes) = (HashSet::new(), HashSet::new());
+        let mut symbols_by_uri = SymbolsByUri::new();
+        let mut registry = PathRegistry::new();
+        let mut workspace_folders = Vec::new();
+        let added = WorkspaceFoldersChangeEvent { added: vec![folder(&app_dir)?, folder(&lib_dir)?], removed: vec![] };
+        change_workspace_folders(added, &mut functions, &mut classes, &mut symbols_by_uri, &mut registry, &mut workspace_folders);
+        assert_eq!(functions.len(), 2);
+        assert_eq!(classes.len(), 1);
+        assert_eq!(symbols_by_uri.len(), 2);
+
+        let removed = WorkspaceFoldersChangeEvent { added: vec![], removed: vec![folder(&lib_dir)?] };
+        change_workspace_folders(removed, &mut functions, &mut classes, &mut symbols_by_uri, &mut registry, &mut workspace_folders);
+        let names: Vec<&str> = functions.iter().chain(classes.iter()).map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["run"]);
+        assert_eq!(symbols_by_uri.len(), 1);
+        let params = WorkspaceSymbolParams { query: "Helper".to_string(), ..Default::default() };
+        let results = handle_workspace_symbol_request(params, &functions, &classes, &registry, SearchAlgorithm::Skim, false, &ContainerNameOptions::default(), false);
+        assert!(results.is_empty());
+
+        // Adding a folder twice does not duplicate its symbols
+        let added = WorkspaceFoldersChangeEvent { added: vec![folder(&app_dir)?], removed: vec![] };
+        change_workspace_folders(added, &mut functions, &mut classes, &mut symbols_by_uri, &mut registry, &mut workspace_folders);
+        assert_eq!(functions.len(), 1);
+        assert_eq!(workspace_folders, vec![app_dir]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_saving_a_file_in_an_added_folder_keeps_its_module_names() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let base_dir = temp_dir.path().join("server");
+        let lib_dir = temp_dir.path().join("lib");
+        std::fs::create_dir_all(&base_dir)?;
+        std::fs::create_dir_all(lib_dir.join("pkg"))?;
+        let file_path = lib_dir.join("pkg").join("util.py");
+        std::fs::write(&file_path, "def helper():\n    pass\n")?;
+
+        let (mut functions, mut classes) = (HashSet::new(), HashSet::new());
+        let mut symbols_by_uri = SymbolsByUri::new();
+        let mut registry = PathRegistry::new();
+        let mut workspace_folders = Vec::new();
+        let lib_uri: Uri = Url::from_file_path(&lib_dir).unwrap().as_str().parse()?;
+        let added = WorkspaceFoldersChangeEvent { added: vec![WorkspaceFolder { uri: lib_uri, name: String::new() }], removed: vec![] };
+        change_workspace_folders(added, &mut functions, &mut classes, &mut symbols_by_uri, &mut registry, &mut workspace_folders);
+
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        assert_eq!(symbols_for_uri(&symbols_by_uri, &uri)[0].context.module_path(), "pkg.util");
+
+        std::fs::write(&file_path, "def helper():\n    pass\n\ndef added_later():\n    pass\n")?;
+        refresh_symbols_for_uri(&uri, &mut functions, &mut classes, &mut symbols_by_uri, &registry, &base_dir, &workspace_folders);
+        let refreshed = symbols_for_uri(&symbols_by_uri, &uri);
+        assert_eq!(refreshed.len(), 2);
+        assert!(refreshed.iter().all(|symbol| symbol.context.module_path() == "pkg.util"), "{:?}", refreshed);
+        Ok(())
+    }
+
+    #[test]
+    fn test_removing_a_folder_cancels_its_indexing() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let lib_dir = temp_dir.path().join("lib");
+        std::fs::create_dir_all(&lib_dir)?;
+        for i in 0..50 {
+            std::fs::write(lib_dir.join(format!("module_{}.py", i)), format!("def function_{}():\n    pass\n", i))?;
+        }
+        let lib_folder = WorkspaceFolder { uri: Url::from_file_path(&lib_dir).unwrap().as_str().parse()?, name: String::new() };
+
+        let (mut functions, mut classes) = (HashSet::new(), HashSet::new());
+        let mut symbols_by_uri = SymbolsByUri::new();
+        let registry = PathRegistry::new();
+        let mut workspace_folders = Vec::new();
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        let mut folder_indexer = FolderIndexer::new(sender);
+
+        // The folder is removed while it may still be indexing
+        let added = WorkspaceFoldersChangeEvent { added: vec![lib_folder.clone()], removed: vec![] };
+        handle_workspace_folders_change(added, &mut functions, &mut classes, &mut symbols_by_uri, &registry, &mut workspace_folders, &mut folder_indexer);
+        let removed = WorkspaceFoldersChangeEvent { added: vec![], removed: vec![lib_folder.clone()] };
+        handle_workspace_folders_change(removed, &mut functions, &mut classes, &mut symbols_by_uri, &registry, &mut workspace_folders, &mut folder_indexer);
+        let indexed = receiver.recv()?;
+        assert!(indexed.cancel.load(Ordering::Relaxed));
+        assert!(!folder_indexer.finish(&indexed));
+
+        // Adding it twice in a row keeps only the second indexing
+        let added = WorkspaceFoldersChangeEvent { added: vec![lib_folder.clone(), lib_folder], removed: vec![] };
+        handle_workspace_folders_change(added, &mut functions, &mut classes, &mut symbols_by_uri, &registry, &mut workspace_folders, &mut folder_indexer);
+        let finished: Vec<IndexedFolder> = receiver.iter().take(2).filter(|indexed| folder_indexer.finish(indexed)).collect();
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].stats.as_ref().unwrap().functions.lock().unwrap().len(), 50);
+        assert!(functions.is_empty() && workspace_folders.is_empty(), "nothing is added before the result is merged");
+        Ok(())
+    }
+
+    #[test]
+    fn test_document_symbols_in_source_order() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let file_path = temp_dir.path().join("ordered.py");
+        let mut source = String::new();
+        // Enough definitions that hash order would almost surely differ from source order
+        for i in 0..20 {
+            source.push_str(&format!("class C{}:\n    def m{}_b(self):\n        pass\n    def m{}_a(self):\n        pass\n\n", i, i, i));
+            source.push_str(&format!("def f{}():\n    pass\n\n", i));
+        }
+        std::fs::write(&file_path, source)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_threads(std::slice::from_ref(&file_path), temp_dir.path(), &stats, None)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let registry = stats.path_registry.lock().unwrap();
+        let symbols_by_uri = build_symbols_by_uri(&functions, &classes, &registry);
+
+        let uri: Uri = Url::from_file_path(&file_path).unwrap().as_str().parse()?;
+        let params: DocumentSymbolParams = serde_json::from_value(json!({
+            "textDocument": { "uri": uri }
+        }))?;
+        let outline = handle_document_symbol_request(params, &symbols_by_uri);
+        assert_eq!(outline.len(), 40);
+
+        fn assert_ascending(symbols: &[DocumentSymbol]) {
+            let lines: Vec<u32> = symbols.iter().map(|s| s.range.start.line).collect();
+            assert!(lines.windows(2).all(|pair| pair[0] < pair[1]), "Out of order: {:?}", lines);
+            for symbol in symbols {
+                if let Some(children) = &symbol.children {
+                    assert_ascending(children);
+                }
+            }
+        }
+        assert_ascending(&outline);
+        let methods: Vec<&str> = outline[0].children.as_ref().unwrap().iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(methods, vec!["m0_b", "m0_a"]);
         Ok(())
     }
 
@@ -721,10 +2071,50 @@ mod tests {
             &classes,
             &registry,
             SearchAlgorithm::Skim,
+            false,
+            &ContainerNameOptions::default(),
+            false,
         );
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn test_empty_query_lists_symbols_when_enabled() {
+        let registry = create_test_path_registry();
+        let functions: HashSet<Symbol> = [
+            create_test_symbol("zeta", SymbolType::Function, 5, 0, None, "file1"),
+            create_test_symbol("alpha", SymbolType::Function, 20, 0, None, "file1"),
+            create_test_symbol("run", SymbolType::Method, 16, 1, Some("ClassB"), "file2"),
+        ]
+        .into_iter()
+        .collect();
+        let classes: HashSet<Symbol> = [create_test_symbol("ClassB", SymbolType::Class, 15, 1, None, "file2")]
+            .into_iter()
+            .collect();
+        let request = |empty_query_returns_all| {
+            handle_workspace_symbol_request(
+                WorkspaceSymbolParams::default(),
+                &functions,
+                &classes,
+                &registry,
+                SearchAlgorithm::Skim,
+                false,
+                &ContainerNameOptions::default(),
+                empty_query_returns_all,
+            )
+        };
+
+        // Off by default
+        assert!(request(false).is_empty());
+
+        let names: Vec<String> = request(true).into_iter().map(|s| s.name).collect();
+        assert_eq!(names, vec!["ClassB", "alpha", "zeta", "run"]);
+        assert_eq!(
+            empty_query_returns_all_from_initialize_params(&json!({ "initializationOptions": { "emptyQueryReturnsAll": true } })),
+            Some(true)
+        );
+    }
+
     #[test]
     fn test_handle_workspace_symbol_request_no_matches() {
         let registry = create_test_path_registry();
@@ -759,10 +2149,113 @@ mod tests {
             &classes,
             &registry,
             SearchAlgorithm::Skim,
+            false,
+            &ContainerNameOptions::default(),
+            false,
         );
         assert!(results.is_empty());
     }
 
+    #[test]
+    fn test_queries_below_min_length_are_not_searched() {
+        let registry = Arc::new(create_test_path_registry());
+        let functions: Arc<HashSet<Symbol>> = Arc::new(
+            [create_test_symbol("find_this_func", SymbolType::Function, 5, 0, None, "file1")]
+                .into_iter()
+                .collect(),
+        );
+        let classes = Arc::new(HashSet::new());
+        let rt = Runtime::new().unwrap();
+        let search = |query: &str, min_query_len| {
+            let params = WorkspaceSymbolParams { query: query.to_string(), ..Default::default() };
+            rt.block_on(handle_workspace_symbol_request_async(
+                params,
+                functions.clone(),
+                classes.clone(),
+                registry.clone(),
+                SearchAlgorithm::Skim,
+                false,
+                ContainerNameOptions::default(),
+                true,
+                min_query_len,
+            ))
+        };
+
+        // The default of 1 searches every non-empty query
+        assert_eq!(search("f", 1).len(), 1);
+        assert!(search("f", 2).is_empty());
+        assert!(search("fi", 3).is_empty());
+        assert_eq!(search("fin", 3).len(), 1);
+        // Empty queries still follow emptyQueryReturnsAll, but whitespace is not searched
+        assert_eq!(search("", 3).len(), 1);
+        assert!(search("   ", 1).is_empty());
+        assert!(search("\t", 3).is_empty());
+        assert_eq!(
+            min_query_len_from_initialize_params(&json!({ "initializationOptions": { "minQueryLength": 3 } })),
+            Some(3)
+        );
+        assert_eq!(min_query_len_from_initialize_params(&json!({ "initializationOptions": {} })), None);
+    }
+
+    #[test]
+    fn test_repeated_query_served_from_cache() {
+        let registry = create_test_path_registry();
+        let functions: HashSet<Symbol> = [create_test_symbol("find_this_func", SymbolType::Function, 5, 0, None, "file1")]
+            .into_iter()
+            .collect();
+        let classes = HashSet::new();
+        let search = |query: &str| {
+            let params = WorkspaceSymbolParams { query: query.to_string(), ..Default::default() };
+            handle_workspace_symbol_request(params, &functions, &classes, &registry, SearchAlgorithm::Skim, false, &ContainerNameOptions::default(), false)
+        };
+
+        let mut cache = QueryCache::new(2);
+        assert!(cache.get("find").is_none());
+        let results = search("find");
+        cache.insert("find".to_string(), results.clone(), cache.generation());
+        assert_eq!(cache.get("find"), Some(results));
+
+        // The least recently used query is evicted first
+        cache.insert("this".to_string(), search("this"), cache.generation());
+        assert!(cache.get("find").is_some());
+        cache.insert("func".to_string(), search("func"), cache.generation());
+        assert!(cache.get("this").is_none());
+        assert!(cache.get("find").is_some() && cache.get("func").is_some());
+
+        // File changes invalidate everything
+        cache.clear();
+        assert!(cache.get("find").is_none());
+
+        let mut disabled = QueryCache::new(0);
+        disabled.insert("find".to_string(), search("find"), disabled.generation());
+        assert!(disabled.get("find").is_none());
+    }
+
+    #[test]
+    fn test_search_finishing_after_a_clear_is_not_cached() {
+        let registry = create_test_path_registry();
+        let functions: HashSet<Symbol> = [create_test_symbol("find_this_func", SymbolType::Function, 5, 0, None, "file1")]
+            .into_iter()
+            .collect();
+        let classes = HashSet::new();
+        let mut cache = QueryCache::new(2);
+
+        // As in run_server: the generation is captured when the search is spawned...
+        let generation = cache.generation();
+        // ...a file is saved while it runs...
+        cache.clear();
+        // ...and its results, from the index before the save, arrive afterwards
+        let params = WorkspaceSymbolParams { query: "find".to_string(), ..Default::default() };
+        let stale = handle_workspace_symbol_request(params, &functions, &classes, &registry, SearchAlgorithm::Skim, false, &ContainerNameOptions::default(), false);
+        assert_eq!(stale.len(), 1);
+        cache.insert("find".to_string(), stale, generation);
+        assert!(cache.get("find").is_none());
+
+        // Searches started after the clear are cached again
+        cache.insert("find".to_string(), Vec::new(), cache.generation());
+        assert_eq!(cache.get("find"), Some(Vec::new()));
+    }
+
     #[test]
     fn test_handle_workspace_symbol_request_finds_symbol() {
         let registry = create_test_path_registry();
@@ -793,6 +2286,9 @@ mod tests {
             &classes,
             &registry,
             SearchAlgorithm::Skim,
+            false,
+            &ContainerNameOptions::default(),
+            false,
         );
         assert_eq!(results_func.len(), 1);
         assert!(results_func[0].name.starts_with("find_this_func"));
@@ -808,6 +2304,9 @@ mod tests {
             &classes,
             &registry,
             SearchAlgorithm::Skim,
+            false,
+            &ContainerNameOptions::default(),
+            false,
         );
         assert_eq!(results_class.len(), 1);
         assert!(results_class[0].name.starts_with("FindThisClass"));
@@ -823,6 +2322,9 @@ mod tests {
             &classes,
             &registry,
             SearchAlgorithm::Skim,
+            false,
+            &ContainerNameOptions::default(),
+            false,
         );
         let get_base_name =
             |s: &SymbolInformation| s.name.split(' ').next().unwrap_or("").to_string();
@@ -866,6 +2368,9 @@ mod tests {
             &classes,
             &registry,
             SearchAlgorithm::Nucleo,
+            false,
+            &ContainerNameOptions::default(),
+            false,
         );
 
         let get_base_name =
@@ -877,6 +2382,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::zombie_processes)]
     fn test_lsp_server_integration_symbol_search() -> Result<()> {
         ensure_binary_built();
 
@@ -1028,4 +2534,36 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_print_stats_and_exit() -> Result<()> {
+        ensure_binary_built();
+
+        let registry = create_test_path_registry();
+        let functions: HashSet<Symbol> = [
+            create_test_symbol("func_a", SymbolType::Function, 10, 0, None, "file1"),
+            create_test_symbol("func_b", SymbolType::Function, 20, 0, None, "file1"),
+        ]
+        .into_iter()
+        .collect();
+        let classes: HashSet<Symbol> =
+            [create_test_symbol("ClassA", SymbolType::Class, 5, 1, None, "file2")]
+                .into_iter()
+                .collect();
+
+        let temp_symbol_file = create_test_symbol_file(&functions, &classes, &registry)?;
+        let output = Command::new(get_binary_path())
+            .args(["--load", temp_symbol_file.path().to_str().unwrap(), "--print-stats-and-exit"])
+            .stdin(Stdio::null())
+            .output()?;
+        assert!(output.status.success());
+
+        let summary: Value = serde_json::from_slice(&output.stdout)?;
+        assert_eq!(
+            summary,
+            json!({ "functions": 2, "classes": 1, "files": 2, "modules": 2 })
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+/// A completed span in the Chrome trace event format ("X" = complete event)
+#[derive(Debug, Serialize)]
+struct TraceEvent {
+    name: &'static str,
+    cat: &'static str,
+    ph: &'static str,
+    /// Start time in microseconds since the layer was created
+    ts: u64,
+    dur: u64,
+    pid: u32,
+    tid: u64,
+    args: Map<String, Value>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFile<'a> {
+    trace_events: &'a [TraceEvent],
+}
+
+// Timing and fields stored in each span's extensions until it closes
+struct SpanTiming {
+    start: Instant,
+    tid: u64,
+    args: Map<String, Value>,
+}
+
+struct FieldVisitor<'a>(&'a mut Map<String, Value>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.into());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value).into());
+    }
+}
+
+// Small stable per-thread ids; std's ThreadId has no stable numeric form
+fn current_thread_id() -> u64 {
+    static NEXT_TID: AtomicU64 = AtomicU64::new(1);
+    thread_local! {
+        static TID: u64 = NEXT_TID.fetch_add(1, Ordering::Relaxed);
+    }
+    TID.with(|tid| *tid)
+}
+
+/// A `tracing` layer that records every span as a Chrome trace event, for viewing
+/// in chrome://tracing or Perfetto. Events are kept in memory and written out when the
+/// returned `ChromeTraceGuard` is dropped.
+pub struct ChromeTraceLayer {
+    epoch: Instant,
+    events: Arc<Mutex<Vec<TraceEvent>>>,
+}
+
+/// Writes the recorded trace to its file when dropped
+pub struct ChromeTraceGuard {
+    path: PathBuf,
+    events: Arc<Mutex<Vec<TraceEvent>>>,
+}
+
+/// Create a layer recording spans, and a guard that writes them to `path` as JSON
+pub fn chrome_trace_layer(path: impl Into<PathBuf>) -> (ChromeTraceLayer, ChromeTraceGuard) {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let layer = ChromeTraceLayer {
+        epoch: Instant::now(),
+        events: events.clone(),
+    };
+    let guard = ChromeTraceGuard {
+        path: path.into(),
+        events,
+    };
+    (layer, guard)
+}
+
+impl<S> Layer<S> for ChromeTraceLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut args = Map::new();
+        attrs.record(&mut FieldVisitor(&mut args));
+        span.extensions_mut().insert(SpanTiming {
+            start: Instant::now(),
+            tid: current_thread_id(),
+            args,
+        });
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(timing) = span.extensions_mut().remove::<SpanTiming>() else {
+            return;
+        };
+        let metadata = span.metadata();
+        let event = TraceEvent {
+            name: metadata.name(),
+            cat: metadata.target(),
+            ph: "X",
+            ts: timing.start.duration_since(self.epoch).as_micros() as u64,
+            dur: timing.start.elapsed().as_micros() as u64,
+            pid: std::process::id(),
+            tid: timing.tid,
+            args: timing.args,
+        };
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl ChromeTraceGuard {
+    /// Write the spans recorded so far
+    pub fn write(&self) -> anyhow::Result<()> {
+        let events = self.events.lock().unwrap();
+        let file = File::create(&self.path)?;
+        serde_json::to_writer(BufWriter::new(file), &TraceFile { trace_events: &events })?;
+        Ok(())
+    }
+}
+
+impl Drop for ChromeTraceGuard {
+    fn drop(&mut self) {
+        match self.write() {
+            Ok(()) => tracing::info!("Wrote trace to {}", self.path.display()),
+            Err(e) => tracing::error!("Failed to write trace to {}: {}", self.path.display(), e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use tempfile::tempdir;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_spans_written_as_chrome_trace() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let trace_path = temp_dir.path().join("trace.json");
+
+        let (layer, guard) = chrome_trace_layer(&trace_path);
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let _outer = tracing::info_span!("outer").entered();
+            let _inner = tracing::info_span!("inner", files = 3).entered();
+        });
+        drop(guard);
+
+        let trace: Value = serde_json::from_reader(File::open(&trace_path)?)?;
+        let events = trace["traceEvents"].as_array().unwrap();
+        assert_eq!(events.len(), 2);
+        // Spans are recorded as they close, innermost first
+        assert_eq!(events[0]["name"], "inner");
+        assert_eq!(events[0]["ph"], "X");
+        assert_eq!(events[0]["args"]["files"], 3);
+        assert_eq!(events[1]["name"], "outer");
+        assert!(events[1]["dur"].as_u64() >= events[0]["dur"].as_u64());
+        Ok(())
+    }
+}
@@ -1,9 +1,63 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
-use std::fs::{self, DirEntry};
+use std::fs::{self, DirEntry, File};
+use std::io::{self, BufRead, BufReader};
 use anyhow::{Context, Result};
 use walkdir::{WalkDir, DirEntry as WalkDirEntry};
 use tracing::debug;
 
+/// Whether a path names Python source or a stub (`.py`/`.pyi`), as accepted when a single
+/// file is given in place of a directory
+pub fn is_python_source(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "py" || ext == "pyi")
+}
+
+/// Whether a path names a Cython module (`.pyx`). These are only indexed on request, with
+/// the Python grammar: `def` functions and `class`es are found (given
+/// `CollectOptions::lenient`), while `cdef`/`cpdef` declarations are syntax errors to it.
+pub fn is_cython_source(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "pyx")
+}
+
+/// The directory module names are computed relative to when scanning `path`:
+/// the path itself for a directory, its parent for a single file
+pub fn scan_base_dir(path: &Path) -> &Path {
+    if path.is_file() {
+        path.parent().unwrap_or(Path::new(""))
+    } else {
+        path
+    }
+}
+
+/// The base directory and source roots (see `CollectOptions::source_roots`) for scanning
+/// `paths` in one pass, so each is named as if scanned alone: with several paths the base is
+/// their deepest common ancestor and each `scan_base_dir` becomes a source root relative to it.
+/// `source_roots` are relative to a scanned directory, so they are repeated under each one.
+pub fn scan_layout(paths: &[PathBuf], source_roots: &[PathBuf]) -> (PathBuf, Vec<PathBuf>) {
+    let scan_dirs: Vec<&Path> = paths.iter().map(|path| scan_base_dir(path)).collect();
+    if let [scan_dir] = scan_dirs.as_slice() {
+        return (scan_dir.to_path_buf(), source_roots.to_vec());
+    }
+    let mut base = scan_dirs.first().map(|dir| dir.to_path_buf()).unwrap_or_default();
+    for dir in &scan_dirs {
+        while !dir.starts_with(&base) {
+            if !base.pop() {
+                // No shared ancestor, e.g. a mix of relative and absolute paths
+                base = PathBuf::new();
+                break;
+            }
+        }
+    }
+    let roots = scan_dirs.iter()
+        .flat_map(|dir| {
+            let root = dir.strip_prefix(&base).unwrap_or(dir).to_path_buf();
+            std::iter::once(root.clone()).chain(source_roots.iter().map(move |extra| root.join(extra)))
+        })
+        .collect();
+    (base, roots)
+}
+
+/// List Python files under `directory`. A single `.py`/`.pyi` file is listed on its own.
 pub fn list_python_files(
     directory: &Path,
     follow_links: bool,
@@ -21,22 +75,88 @@ pub fn list_python_files_with_depth(
         .max_depth(max_depth)
         .into_iter()
         .filter_map(Result::ok)
-        .filter(|entry| {
-            let path = entry.path();
-            path.is_file() && path.extension().map_or(false, |ext| ext == "py")
-        })
+        .filter(is_python_file)
         .map(|entry| entry.path().to_path_buf())
 }
 
+/// A directory or file that could not be visited during discovery
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkError {
+    /// Path that failed, if known
+    pub path: Option<PathBuf>,
+    pub message: String,
+    pub permission_denied: bool,
+}
+
+impl From<walkdir::Error> for WalkError {
+    fn from(error: walkdir::Error) -> Self {
+        let permission_denied = error.io_error()
+            .is_some_and(|e| e.kind() == io::ErrorKind::PermissionDenied);
+        Self {
+            path: error.path().map(Path::to_path_buf),
+            message: error.to_string(),
+            permission_denied,
+        }
+    }
+}
+
+/// Like `list_python_files`, also listing Cython `.pyx` files when `cython` is set
+pub fn list_source_files(
+    directory: &Path,
+    follow_links: bool,
+    cython: bool,
+) -> impl Iterator<Item = PathBuf> {
+    WalkDir::new(directory)
+        .follow_links(follow_links)
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(move |entry| is_source_file(entry, cython))
+        .map(|entry| entry.path().to_path_buf())
+}
+
+/// Like `list_python_files`, but also returns the entries that could not be visited
+/// (permission errors, broken directories, symlink loops) instead of silently dropping them
+pub fn list_python_files_with_errors(
+    directory: &Path,
+    follow_links: bool,
+) -> (Vec<PathBuf>, Vec<WalkError>) {
+    list_source_files_with_errors(directory, follow_links, false)
+}
+
+/// Like `list_python_files_with_errors`, also listing Cython `.pyx` files when `cython` is set
+pub fn list_source_files_with_errors(
+    directory: &Path,
+    follow_links: bool,
+    cython: bool,
+) -> (Vec<PathBuf>, Vec<WalkError>) {
+    let mut files = Vec::new();
+    let mut errors = Vec::new();
+    for entry in WalkDir::new(directory).follow_links(follow_links) {
+        match entry {
+            Ok(entry) if is_source_file(&entry, cython) => files.push(entry.into_path()),
+            Ok(_) => {},
+            Err(e) => {
+                debug!("Skipping during discovery: {}", e);
+                errors.push(WalkError::from(e));
+            }
+        }
+    }
+    (files, errors)
+}
+
 pub fn list_python_files_recursive(
     directory: &Path,
     follow_links: bool,
 ) -> Result<Vec<PathBuf>> {
+    if directory.is_file() {
+        return Ok(is_python_source(directory).then(|| directory.to_path_buf()).into_iter().collect());
+    }
+    
     let mut files = Vec::new();
     
     visit_dirs(directory, &mut |entry| {
         let path = entry.path();
-        if path.is_file() && path.extension().map_or(false, |ext| ext == "py") {
+        if path.is_file() && path.extension().is_some_and(|ext| ext == "py") {
             files.push(path.to_path_buf());
             debug!("Added python file: {}", path.display());
         }
@@ -70,9 +190,92 @@ fn visit_dirs(
     Ok(())
 }
 
+/// Resolve paths to absolute, canonical form (symlinks resolved), so an index saved with them
+/// opens from any working directory. This ties the index to this machine's directory layout.
+/// Paths that cannot be resolved (e.g. removed since discovery) are kept as given.
+pub fn canonicalize_paths(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths.into_iter()
+        .map(|path| match path.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(e) => {
+                debug!("Keeping unresolved path {}: {}", path.display(), e);
+                path
+            }
+        })
+        .collect()
+}
+
+/// Drop paths that resolve to the same file as another path (e.g. a symlink to a module
+/// that is also reached directly), so its symbols are indexed once. Of each set of aliases
+/// the one that is not itself a symlink is kept, else the first; the kept paths stay as
+/// given and in order. Paths that cannot be resolved are kept.
+pub fn dedup_symlinked_paths(paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut kept: Vec<PathBuf> = Vec::with_capacity(paths.len());
+    let mut by_target: HashMap<PathBuf, usize> = HashMap::new();
+    for path in paths {
+        let target = match path.canonicalize() {
+            Ok(target) => target,
+            Err(e) => {
+                debug!("Keeping unresolved path {}: {}", path.display(), e);
+                kept.push(path);
+                continue;
+            }
+        };
+        match by_target.get(&target) {
+            Some(&slot) => {
+                debug!("{} is the same file as {}", path.display(), kept[slot].display());
+                if kept[slot].is_symlink() && !path.is_symlink() {
+                    kept[slot] = path;
+                }
+            }
+            None => {
+                by_target.insert(target, kept.len());
+                kept.push(path);
+            }
+        }
+    }
+    kept
+}
+
+/// Read a newline-separated list of files to index, skipping directory discovery.
+/// A path of `-` reads the list from stdin.
+pub fn read_file_list(source: &Path) -> Result<Vec<PathBuf>> {
+    if source == Path::new("-") {
+        parse_file_list(io::stdin().lock())
+    } else {
+        let file = File::open(source)
+            .with_context(|| format!("Failed to open file list: {}", source.display()))?;
+        parse_file_list(BufReader::new(file))
+    }
+}
+
+/// Parse a newline-separated list of paths, ignoring blank lines.
+/// Paths are not checked for existence here; missing files surface as I/O errors during parsing.
+pub fn parse_file_list<R: BufRead>(reader: R) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for line in reader.lines() {
+        let line = line.context("Failed to read file list")?;
+        let trimmed = line.trim();
+        if !trimmed.is_empty() {
+            files.push(PathBuf::from(trimmed));
+        }
+    }
+    Ok(files)
+}
+
+/// Whether a walked entry should be indexed. Stubs are only taken when given directly
+/// as the root, since walking a tree picks up the `.py` files they describe.
 pub fn is_python_file(entry: &WalkDirEntry) -> bool {
     let path = entry.path();
-    path.is_file() && path.extension().map_or(false, |ext| ext == "py")
+    if entry.depth() == 0 {
+        return path.is_file() && is_python_source(path);
+    }
+    path.is_file() && path.extension().is_some_and(|ext| ext == "py")
+}
+
+/// `is_python_file`, also accepting Cython `.pyx` files at any depth when `cython` is set
+pub fn is_source_file(entry: &WalkDirEntry, cython: bool) -> bool {
+    is_python_file(entry) || (cython && entry.path().is_file() && is_cython_source(entry.path()))
 }
 
 #[cfg(test)]
@@ -157,6 +360,146 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_list_single_python_file() -> Result<()> {
+        let temp_dir = tempdir()?;
+        create_test_files(temp_dir.path())?;
+        let file = temp_dir.path().join("dir1").join("file2.py");
+        let stub = temp_dir.path().join("stub.pyi");
+        File::create(&stub)?.write_all(b"def f() -> int: ...")?;
+        let not_python = temp_dir.path().join("non_python.txt");
+
+        assert_eq!(list_python_files(&file, false).collect::<Vec<_>>(), vec![file.clone()]);
+        assert_eq!(list_python_files(&stub, false).collect::<Vec<_>>(), vec![stub.clone()]);
+        assert_eq!(list_python_files_recursive(&file, false)?, vec![file.clone()]);
+        assert_eq!(list_python_files_with_errors(&stub, false), (vec![stub.clone()], Vec::new()));
+        assert!(list_python_files(&not_python, false).next().is_none());
+        assert!(list_python_files_recursive(&not_python, false)?.is_empty());
+
+        // Stubs inside a scanned tree are still skipped
+        assert_eq!(list_python_files(temp_dir.path(), false).count(), 3);
+
+        assert_eq!(scan_base_dir(&file), temp_dir.path().join("dir1"));
+        assert_eq!(scan_base_dir(temp_dir.path()), temp_dir.path());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_layout_names_modules_per_root() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let services = temp_dir.path().join("services");
+        let roots = vec![services.join("a"), services.join("b")];
+        create_dir_all(roots[0].join("billing"))?;
+        create_dir_all(roots[1].join("auth"))?;
+        File::create(roots[0].join("billing").join("invoice.py"))?.write_all(b"class Invoice:\n    pass\n")?;
+        File::create(roots[1].join("auth").join("login.py"))?.write_all(b"def login():\n    pass\n")?;
+
+        let (base_dir, source_roots) = scan_layout(&roots, &[]);
+        assert_eq!(base_dir, services);
+        assert_eq!(source_roots, vec![PathBuf::from("a"), PathBuf::from("b")]);
+
+        let files: Vec<PathBuf> = roots.iter().flat_map(|root| list_python_files(root, false)).collect();
+        let stats = crate::symbols::SymbolStats::new();
+        let options = crate::python::CollectOptions { source_roots, ..Default::default() };
+        crate::python::parse_python_files_parallel_with_options(&files, &base_dir, &stats, &options)?;
+        let class = stats.classes.lock().unwrap().iter().next().unwrap().clone();
+        let function = stats.functions.lock().unwrap().iter().next().unwrap().clone();
+        assert_eq!(class.context.module_path(), "billing.invoice");
+        assert_eq!(function.context.module_path(), "auth.login");
+
+        // One directory keeps its own layout, with extra source roots as given
+        let src = vec![PathBuf::from("src")];
+        assert_eq!(scan_layout(&roots[..1], &src), (roots[0].clone(), src.clone()));
+        // Extra source roots apply under each directory
+        let (_, source_roots) = scan_layout(&roots, &src);
+        assert_eq!(source_roots, vec![PathBuf::from("a"), PathBuf::from("a/src"), PathBuf::from("b"), PathBuf::from("b/src")]);
+        // Relative directories share a relative parent
+        let relative = [PathBuf::from("x/one"), PathBuf::from("x/two/three")];
+        assert_eq!(scan_layout(&relative, &[]).0, PathBuf::from("x"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_file_list() -> Result<()> {
+        let input = "a/one.py\n\n  b/two.py  \n/abs/three.py\n";
+        let files = parse_file_list(input.as_bytes())?;
+
+        assert_eq!(files, vec![
+            PathBuf::from("a/one.py"),
+            PathBuf::from("b/two.py"),
+            PathBuf::from("/abs/three.py"),
+        ]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_canonicalize_paths() -> Result<()> {
+        let temp_dir = tempdir()?;
+        create_test_files(temp_dir.path())?;
+
+        let relative = temp_dir.path().join("dir1").join("..").join("file1.py");
+        let missing = PathBuf::from("missing/test.py");
+        let paths = canonicalize_paths(vec![relative, missing.clone()]);
+
+        assert_eq!(paths[0], temp_dir.path().canonicalize()?.join("file1.py"));
+        assert!(paths[0].is_absolute());
+        assert_eq!(paths[1], missing);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_list_python_files_with_errors() -> Result<()> {
+        let temp_dir = tempdir()?;
+        create_test_files(temp_dir.path())?;
+
+        let (files, errors) = list_python_files_with_errors(temp_dir.path(), false);
+        assert_eq!(files.len(), 3);
+        assert!(errors.is_empty());
+
+        // A missing root is reported rather than looking like an empty tree
+        let missing = temp_dir.path().join("missing");
+        let (files, errors) = list_python_files_with_errors(&missing, false);
+        assert!(files.is_empty());
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path.as_deref(), Some(missing.as_path()));
+        assert!(!errors[0].permission_denied);
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_list_python_files_with_errors_permission_denied() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = tempdir()?;
+        create_test_files(temp_dir.path())?;
+        let locked = temp_dir.path().join("locked");
+        create_dir_all(&locked)?;
+        File::create(locked.join("hidden.py"))?;
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000))?;
+
+        // Privileged users (e.g. root in CI containers) can read the directory anyway
+        let readable = fs::read_dir(&locked).is_ok();
+        let (files, errors) = list_python_files_with_errors(temp_dir.path(), false);
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755))?;
+
+        if readable {
+            assert_eq!(files.len(), 4);
+        } else {
+            assert_eq!(files.len(), 3);
+            assert_eq!(errors.len(), 1);
+            assert!(errors[0].permission_denied);
+            assert_eq!(errors[0].path.as_deref(), Some(locked.as_path()));
+        }
+
+        Ok(())
+    }
+
     #[test]
     #[cfg(unix)]
     fn test_follow_symlinks() -> Result<()> {
@@ -174,4 +517,29 @@ mod tests {
         
         Ok(())
     }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_dedup_symlinked_paths() -> Result<()> {
+        let temp_dir = tempdir()?;
+        create_test_files(temp_dir.path())?;
+        let target = temp_dir.path().join("file1.py");
+        let alias = temp_dir.path().join("alias.py");
+        std::os::unix::fs::symlink(&target, &alias)?;
+
+        let files: Vec<PathBuf> = list_python_files(temp_dir.path(), false).collect();
+        assert_eq!(files.len(), 4, "The symlink is listed alongside its target");
+
+        let deduped = dedup_symlinked_paths(files);
+        assert_eq!(deduped.len(), 3);
+        assert!(deduped.contains(&target));
+        assert!(!deduped.contains(&alias));
+
+        // The real file wins even when the symlink comes first
+        let missing = PathBuf::from("missing/test.py");
+        let deduped = dedup_symlinked_paths(vec![alias.clone(), missing.clone(), target.clone()]);
+        assert_eq!(deduped, vec![target, missing]);
+
+        Ok(())
+    }
 } 
\ No newline at end of file
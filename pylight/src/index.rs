@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use anyhow::{Context as AnyhowContext, Result};
+use lsp_types::DocumentSymbol;
+use tracing::info;
+use crate::lsp::to_document_symbols;
+use crate::search::{search_symbols_with_options, SearchAlgorithm, SearchMetrics, SearchOptions};
+use crate::symbols::{
+    load_symbol_data, rebase_paths, summarize_index, symbol_sets, write_symbol_data, IndexCompression,
+    IndexSummary, PathRegistry, Symbol, SymbolData, SymbolStats,
+};
+
+/// An index's functions and classes together with the registry their `file_path_index`es
+/// point into, so the three can't get out of step. The free functions taking them
+/// separately remain, and the fields are public for code that still needs them apart.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolIndex {
+    pub functions: HashSet<Symbol>,
+    pub classes: HashSet<Symbol>,
+    pub registry: PathRegistry,
+    /// The scan directory module names were computed against, when known
+    pub base_dir: Option<PathBuf>,
+    /// Files with a `__main__` guard, if the index was collected with `detect_main`
+    pub entry_points: Vec<PathBuf>,
+}
+
+impl SymbolIndex {
+    pub fn new(functions: HashSet<Symbol>, classes: HashSet<Symbol>, registry: PathRegistry) -> Self {
+        Self {
+            functions,
+            classes,
+            registry,
+            ..Default::default()
+        }
+    }
+
+    /// Copy out what a parse collected into `stats`
+    pub fn from_stats(stats: &SymbolStats, base_dir: Option<&Path>) -> Self {
+        Self {
+            functions: stats.functions.lock().unwrap().clone(),
+            classes: stats.classes.lock().unwrap().clone(),
+            registry: stats.path_registry.lock().unwrap().clone(),
+            base_dir: base_dir.map(Path::to_path_buf),
+            entry_points: stats.entry_points(),
+        }
+    }
+
+    /// Unpack a deserialized index, checking its paths still map to the saved indices
+    pub fn from_data(data: SymbolData) -> Result<Self> {
+        let base_dir = data.base_dir().map(Path::to_path_buf);
+        let entry_points = data.entry_points().into_iter().map(Path::to_path_buf).collect();
+        let (functions, classes, paths) = data.into_symbols();
+        let registry = PathRegistry::from_saved_paths(paths)?;
+        let (functions, classes) = symbol_sets(functions, classes);
+        Ok(Self { functions, classes, registry, base_dir, entry_points })
+    }
+
+    /// Load an index saved by `save` or `save_symbols`
+    pub fn load(path: &Path) -> Result<Self> {
+        info!("Loading symbols from {}...", path.display());
+        let index = Self::from_data(load_symbol_data(path)?)
+            .with_context(|| format!("Failed to load {}", path.display()))?;
+        info!("Load complete! Loaded {} functions, {} classes, and {} paths",
+              index.functions.len(), index.classes.len(), index.registry.paths.len());
+        Ok(index)
+    }
+
+    pub fn save(&self, path: &Path, compression: IndexCompression) -> Result<()> {
+        let entry_points: HashSet<&PathBuf> = self.entry_points.iter().collect();
+        let data = SymbolData::with_entry_points(
+            self.functions.iter().cloned().collect(),
+            self.classes.iter().cloned().collect(),
+            self.registry.paths.clone(),
+            self.base_dir.clone(),
+            self.registry.paths.iter().map(|path| entry_points.contains(path)).collect(),
+        );
+        write_symbol_data(path, &data, compression)
+    }
+
+    /// Move the indexed paths from the directory the index was built in to `new_root`,
+    /// e.g. after copying a project and its index elsewhere
+    pub fn rebase(&mut self, new_root: &Path) -> Result<()> {
+        let Some(old_root) = self.base_dir.take() else {
+            anyhow::bail!("the index does not record the directory it was built from, so it cannot be rebased");
+        };
+        info!("Rebasing paths from {} to {}", old_root.display(), new_root.display());
+        let paths = rebase_paths(std::mem::take(&mut self.registry.paths), &old_root, new_root);
+        self.registry = PathRegistry::from_saved_paths(paths)?;
+        self.entry_points = rebase_paths(std::mem::take(&mut self.entry_points), &old_root, new_root);
+        self.base_dir = Some(new_root.to_path_buf());
+        Ok(())
+    }
+
+    /// Every function and class symbol
+    pub fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.functions.iter().chain(self.classes.iter())
+    }
+
+    pub fn search(
+        &self,
+        query: &str,
+        algorithm: SearchAlgorithm,
+        options: &SearchOptions,
+    ) -> (Vec<(Symbol, i64)>, SearchMetrics) {
+        search_symbols_with_options(query, &self.functions, &self.classes, &self.registry, false, algorithm, options)
+    }
+
+    /// The symbols defined in `path`, in line order (outer definitions first on a shared line)
+    pub fn symbols_in_file(&self, path: &Path) -> Vec<&Symbol> {
+        let Some(file_path_index) = self.registry.index_of(path) else {
+            return Vec::new();
+        };
+        let mut symbols: Vec<&Symbol> = self.symbols()
+            .filter(|symbol| symbol.context.file_path_index == file_path_index)
+            .collect();
+        symbols.sort_by_key(|s| (s.context.line_number, s.context.parent_context.len(), s.name.as_str()));
+        symbols
+    }
+
+    /// The outline of `path`, as for an LSP textDocument/documentSymbol request
+    pub fn document_symbols(&self, path: &Path) -> Vec<DocumentSymbol> {
+        to_document_symbols(self.symbols_in_file(path))
+    }
+
+    pub fn summary(&self) -> IndexSummary {
+        summarize_index(&self.functions, &self.classes, &self.registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::python::{parse_python_files_parallel_with_options, CollectOptions};
+
+    #[test]
+    fn test_symbol_index_round_trip() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let base_dir = temp_dir.path().join("project");
+        fs::create_dir_all(&base_dir)?;
+        let models = base_dir.join("models.py");
+        let script = base_dir.join("script.py");
+        fs::write(&models, "class User:\n    def save(self):\n        pass\n\ndef helper():\n    pass\n")?;
+        fs::write(&script, "if __name__ == \"__main__\":\n    pass\n")?;
+        let stats = SymbolStats::new();
+        let options = CollectOptions { detect_main: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&[models.clone(), script.clone()], &base_dir, &stats, &options)?;
+        let index = SymbolIndex::from_stats(&stats, Some(&base_dir));
+
+        let saved = temp_dir.path().join("symbols.idx");
+        index.save(&saved, IndexCompression::Gzip)?;
+        let mut loaded = SymbolIndex::load(&saved)?;
+        assert_eq!(loaded.functions, index.functions);
+        assert_eq!(loaded.classes, index.classes);
+        assert_eq!(loaded.registry.paths, index.registry.paths);
+        assert_eq!(loaded.entry_points, vec![script.clone()]);
+        assert_eq!(loaded.summary().functions, 2);
+
+        let names: Vec<&str> = loaded.symbols_in_file(&models).iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["User", "save", "helper"]);
+        let outline = loaded.document_symbols(&models);
+        assert_eq!(outline.len(), 2);
+        assert_eq!(outline[0].children.as_ref().unwrap()[0].name, "save");
+        assert!(loaded.symbols_in_file(Path::new("elsewhere.py")).is_empty());
+
+        let (results, _) = loaded.search("User.save", SearchAlgorithm::Skim, &SearchOptions::default());
+        assert_eq!(results[0].0.name, "save");
+
+        let moved = temp_dir.path().join("moved");
+        loaded.rebase(&moved)?;
+        assert_eq!(loaded.symbols_in_file(&moved.join("models.py")).len(), 3);
+        assert_eq!(loaded.entry_points, vec![moved.join("script.py")]);
+
+        let mut unrooted = SymbolIndex::new(HashSet::new(), HashSet::new(), PathRegistry::new());
+        assert!(unrooted.rebase(&moved).is_err());
+        Ok(())
+    }
+}
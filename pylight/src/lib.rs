@@ -1,6 +1,11 @@
+pub mod chrome_trace;
 pub mod files;
+pub mod index;
+pub mod line_index;
+pub mod lsp;
 pub mod path_trie;
 pub mod python;
 pub mod search;
+pub mod search_regex;
 pub mod search_skim;
 pub mod symbols;
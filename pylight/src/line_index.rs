@@ -0,0 +1,135 @@
+/// Precomputed byte offsets of line starts in a source file.
+///
+/// Built once per file in O(n), then answers offset -> (line, column) lookups in O(log n)
+/// instead of rescanning the source for every request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    // Byte offset at which each line starts; always begins with 0
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> Self {
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            source.bytes()
+                .enumerate()
+                .filter(|&(_, b)| b == b'\n')
+                .map(|(i, _)| i + 1),
+        );
+        Self { line_starts, len: source.len() }
+    }
+
+    /// Number of lines (a trailing newline starts a new, empty line)
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Map a byte offset to a 0-based (line, byte column) pair.
+    /// Offsets past the end of the source are clamped to the end.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.len);
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next_line) => next_line - 1,
+        };
+        (line, offset - self.line_starts[line])
+    }
+
+    /// Map a 0-based line and byte column back to a byte offset, if the line exists
+    pub fn offset(&self, line: usize, column: usize) -> Option<usize> {
+        let start = *self.line_starts.get(line)?;
+        Some((start + column).min(self.len))
+    }
+
+    /// Byte offset where `line` starts, if it exists
+    pub fn line_start(&self, line: usize) -> Option<usize> {
+        self.line_starts.get(line).copied()
+    }
+}
+
+/// Byte offset in `line` of a column counted in UTF-16 code units, as LSP positions are.
+/// A column inside a surrogate pair rounds down to its character; past the end gives the length.
+pub fn utf16_to_byte_column(line: &str, utf16_column: usize) -> usize {
+    let mut units = 0;
+    for (offset, c) in line.char_indices() {
+        units += c.len_utf16();
+        if units > utf16_column {
+            return offset;
+        }
+    }
+    line.len()
+}
+
+/// Column in UTF-16 code units of byte offset `byte_column` in `line`, for LSP positions
+pub fn byte_to_utf16_column(line: &str, byte_column: usize) -> usize {
+    line[..byte_column.min(line.len())].encode_utf16().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_col_lookup() {
+        let source = "def a():\n    pass\n\nclass B:\n    x = 1";
+        let index = LineIndex::new(source);
+
+        assert_eq!(index.line_count(), 5);
+        assert_eq!(index.line_col(0), (0, 0));
+        assert_eq!(index.line_col(4), (0, 4));
+        // The newline itself belongs to the line it ends
+        assert_eq!(index.line_col(8), (0, 8));
+        assert_eq!(index.line_col(9), (1, 0));
+        assert_eq!(index.line_col(13), (1, 4));
+        // Empty line
+        assert_eq!(index.line_col(18), (2, 0));
+        assert_eq!(index.line_col(19), (3, 0));
+        assert_eq!(index.line_col(source.len()), (4, 9));
+        // Clamped past the end
+        assert_eq!(index.line_col(source.len() + 100), (4, 9));
+    }
+
+    #[test]
+    fn test_offset_round_trip() {
+        let source = "a\nbb\n\nccc\n";
+        let index = LineIndex::new(source);
+
+        for offset in 0..=source.len() {
+            let (line, column) = index.line_col(offset);
+            assert_eq!(index.offset(line, column), Some(offset));
+        }
+        assert_eq!(index.line_start(3), Some(6));
+        assert_eq!(index.offset(10, 0), None);
+    }
+
+    #[test]
+    fn test_long_single_line() {
+        let source = "x".repeat(1_000_000);
+        let index = LineIndex::new(&source);
+
+        assert_eq!(index.line_count(), 1);
+        assert_eq!(index.line_col(999_999), (0, 999_999));
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        // U+1F600 is one char, four UTF-8 bytes and two UTF-16 code units
+        let line = "a\u{1F600}b";
+        assert_eq!(byte_to_utf16_column(line, 0), 0);
+        assert_eq!(byte_to_utf16_column(line, 1), 1);
+        assert_eq!(byte_to_utf16_column(line, 5), 3);
+        assert_eq!(byte_to_utf16_column(line, 6), 4);
+        assert_eq!(utf16_to_byte_column(line, 0), 0);
+        assert_eq!(utf16_to_byte_column(line, 1), 1);
+        assert_eq!(utf16_to_byte_column(line, 2), 1, "Inside the surrogate pair");
+        assert_eq!(utf16_to_byte_column(line, 3), 5);
+        assert_eq!(utf16_to_byte_column(line, 4), 6);
+        assert_eq!(utf16_to_byte_column(line, 100), 6);
+
+        for byte_column in [0, 1, 5, 6] {
+            assert_eq!(utf16_to_byte_column(line, byte_to_utf16_column(line, byte_column)), byte_column);
+        }
+    }
+}
@@ -0,0 +1,922 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use lsp_types::{
+    CallHierarchyItem, DocumentSymbol, FoldingRange, FoldingRangeKind, Location, OneOf, Position, Range,
+    SemanticToken, SemanticTokenModifier, SemanticTokenType, SemanticTokensLegend,
+    SymbolInformation, SymbolKind, SymbolTag, Uri, WorkspaceSymbol,
+};
+use url::Url;
+
+use crate::line_index::{byte_to_utf16_column, LineIndex};
+use crate::symbols::{PathRegistry, Symbol, SymbolType};
+
+/// Map our symbol types onto LSP symbol kinds
+pub fn symbol_kind(symbol_type: &SymbolType) -> SymbolKind {
+    match symbol_type {
+        SymbolType::Class | SymbolType::NestedClass => SymbolKind::CLASS,
+        SymbolType::Function | SymbolType::Method => SymbolKind::FUNCTION,
+        // An alias names a type, so it is shown like the classes it usually stands for
+        SymbolType::TypeAlias => SymbolKind::CLASS,
+        _ => SymbolKind::VARIABLE, // Default fallback
+    }
+}
+
+/// Set once a relative path could not be made absolute, so the warning is logged only once
+static ABSOLUTIZE_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// File URL for `path`. File URLs must be absolute, so relative paths (as stored by indexes
+/// built from a relative directory) are resolved against the current directory first.
+pub fn file_url(path: &Path) -> Option<Url> {
+    if path.is_absolute() {
+        return Url::from_file_path(path).ok();
+    }
+    match std::path::absolute(path) {
+        Ok(absolute) => Url::from_file_path(absolute).ok(),
+        Err(e) => {
+            if !ABSOLUTIZE_FAILED.swap(true, Ordering::Relaxed) {
+                tracing::warn!("Cannot resolve relative paths such as {}: {}", path.display(), e);
+            }
+            None
+        }
+    }
+}
+
+/// File URI of the file a symbol was defined in, if its path is representable
+pub fn symbol_uri(symbol: &Symbol, path_registry: &PathRegistry) -> Option<Uri> {
+    let file_path = path_registry.get_path(symbol.context.file_path_index);
+    let url = file_url(file_path)?;
+    match url.as_str().parse() {
+        Ok(uri) => Some(uri),
+        Err(_) => {
+            tracing::error!("Failed to convert path to URI: {}", file_path.display());
+            None
+        }
+    }
+}
+
+/// The range of a symbol's definition line; we only have line numbers, so it is empty at column 0
+pub fn symbol_range(symbol: &Symbol) -> Range {
+    let line = (symbol.context.line_number as u32).saturating_sub(1); // Convert to 0-based indexing
+    Range {
+        start: Position { line, character: 0 },
+        end: Position { line, character: 0 },
+    }
+}
+
+/// What workspace symbol results show as their container
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ContainerNameMode {
+    /// Parent classes/functions, or the package for top-level symbols
+    #[default]
+    ParentsOrModule,
+    /// Only parent classes/functions (e.g. `MyClass` for a method); none for top-level symbols
+    Parents,
+    /// The full dotted module path (e.g. `pkg.sub.file`)
+    Module,
+}
+
+impl std::str::FromStr for ContainerNameMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(ContainerNameMode::ParentsOrModule),
+            "parents" => Ok(ContainerNameMode::Parents),
+            "module" => Ok(ContainerNameMode::Module),
+            _ => Err(format!("Unknown container name mode: {}. Valid options are 'auto', 'parents' or 'module'", s)),
+        }
+    }
+}
+
+/// Container name settings for workspace symbol results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerNameOptions {
+    pub mode: ContainerNameMode,
+    /// Joins parent class/function names (e.g. `::` gives `Outer::Inner`)
+    pub separator: String,
+    /// Show the fully-qualified name (see `qualified_name`) as the symbol name, for editors
+    /// that show only the name, so same-named symbols can be told apart in the picker
+    pub qualified_names: bool,
+}
+
+impl Default for ContainerNameOptions {
+    fn default() -> Self {
+        Self {
+            mode: ContainerNameMode::default(),
+            separator: ".".to_string(),
+            qualified_names: false,
+        }
+    }
+}
+
+/// Shown before the parent names of symbols whose outer parents were dropped at collection
+pub const TRUNCATED_PARENTS_MARKER: &str = "…";
+
+fn parent_names(symbol: &Symbol, separator: &str) -> String {
+    let names = symbol
+        .context
+        .parent_context
+        .iter()
+        .map(|p| p.name.clone())
+        .collect::<Vec<_>>()
+        .join(separator);
+    if symbol.context.parents_truncated {
+        format!("{}{}{}", TRUNCATED_PARENTS_MARKER, separator, names)
+    } else {
+        names
+    }
+}
+
+/// Parent classes/functions joined with dots, falling back to the module
+pub fn container_name(symbol: &Symbol) -> String {
+    if !symbol.context.parent_context.is_empty() {
+        parent_names(symbol, ".")
+    } else {
+        symbol.context.fully_qualified_module.clone()
+    }
+}
+
+/// The container to show for a symbol in the given mode
+pub fn container_name_with_mode(symbol: &Symbol, mode: ContainerNameMode) -> Option<String> {
+    container_name_with_options(symbol, &ContainerNameOptions { mode, ..Default::default() })
+}
+
+/// Like `container_name_with_mode`, joining parent names with the configured separator.
+/// Module paths always stay dotted.
+pub fn container_name_with_options(symbol: &Symbol, options: &ContainerNameOptions) -> Option<String> {
+    let has_parents = !symbol.context.parent_context.is_empty();
+    match options.mode {
+        ContainerNameMode::ParentsOrModule if has_parents => Some(parent_names(symbol, &options.separator)),
+        ContainerNameMode::ParentsOrModule => Some(symbol.context.fully_qualified_module.clone()),
+        ContainerNameMode::Parents if has_parents => Some(parent_names(symbol, &options.separator)),
+        ContainerNameMode::Parents => None,
+        ContainerNameMode::Module => Some(symbol.context.module_path()),
+    }
+}
+
+/// Mark @deprecated symbols so editors can strike them through
+pub fn symbol_tags(symbol: &Symbol) -> Option<Vec<SymbolTag>> {
+    if symbol.context.has_decorator("deprecated") {
+        Some(vec![SymbolTag::DEPRECATED])
+    } else {
+        None
+    }
+}
+
+/// The dotted module path, enclosing classes/functions and name (e.g. "pkg.file.Outer.method")
+pub fn qualified_name(symbol: &Symbol) -> String {
+    let mut parts = vec![symbol.context.module_path()];
+    parts.extend(symbol.context.parent_context.iter().map(|p| p.name.clone()));
+    parts.push(symbol.name.clone());
+    parts.join(".")
+}
+
+fn display_name(symbol: &Symbol, score: i64, include_score: bool) -> String {
+    display_name_with_qualification(symbol, score, include_score, false)
+}
+
+fn display_name_with_qualification(symbol: &Symbol, score: i64, include_score: bool, qualified: bool) -> String {
+    let name = if qualified { qualified_name(symbol) } else { symbol.name.clone() };
+    if include_score {
+        format!("{} ({})", name, score)
+    } else {
+        name
+    }
+}
+
+/// Convert a Symbol to an LSP SymbolInformation.
+/// With `include_score`, the match score is appended to the name, which helps when tuning search.
+pub fn to_symbol_information(
+    symbol: &Symbol,
+    path_registry: &PathRegistry,
+    score: i64,
+    include_score: bool,
+) -> Option<SymbolInformation> {
+    to_symbol_information_with_container(symbol, path_registry, score, include_score, &ContainerNameOptions::default())
+}
+
+/// Like `to_symbol_information`, choosing what to show as the container name
+pub fn to_symbol_information_with_container(
+    symbol: &Symbol,
+    path_registry: &PathRegistry,
+    score: i64,
+    include_score: bool,
+    container_options: &ContainerNameOptions,
+) -> Option<SymbolInformation> {
+    let location = Location {
+        uri: symbol_uri(symbol, path_registry)?,
+        range: symbol_range(symbol),
+    };
+
+    // Replace deprecated field with tags, but keep deprecated field as None
+    #[allow(deprecated)]
+    Some(SymbolInformation {
+        name: display_name_with_qualification(symbol, score, include_score, container_options.qualified_names),
+        kind: symbol_kind(&symbol.context.symbol_type),
+        tags: symbol_tags(symbol),
+        location,
+        container_name: container_name_with_options(symbol, container_options),
+        deprecated: None, // Explicitly set deprecated to None
+    })
+}
+
+/// Convert a Symbol to an LSP WorkspaceSymbol (the newer workspace/symbol response type)
+pub fn to_workspace_symbol(
+    symbol: &Symbol,
+    path_registry: &PathRegistry,
+    score: i64,
+    include_score: bool,
+) -> Option<WorkspaceSymbol> {
+    let location = Location {
+        uri: symbol_uri(symbol, path_registry)?,
+        range: symbol_range(symbol),
+    };
+
+    Some(WorkspaceSymbol {
+        name: display_name(symbol, score, include_score),
+        kind: symbol_kind(&symbol.context.symbol_type),
+        tags: symbol_tags(symbol),
+        container_name: Some(container_name(symbol)),
+        location: OneOf::Left(location),
+        data: None,
+    })
+}
+
+/// Convert a Symbol to the item a call hierarchy is anchored on
+pub fn to_call_hierarchy_item(symbol: &Symbol, path_registry: &PathRegistry) -> Option<CallHierarchyItem> {
+    Some(CallHierarchyItem {
+        name: symbol.name.clone(),
+        kind: symbol_kind(&symbol.context.symbol_type),
+        tags: symbol_tags(symbol),
+        detail: symbol_detail(symbol),
+        uri: symbol_uri(symbol, path_registry)?,
+        range: symbol_range(symbol),
+        selection_range: symbol_range(symbol),
+        data: None,
+    })
+}
+
+/// Build a DocumentSymbol outline from the symbols of a single file.
+/// Methods and nested definitions become children of their enclosing class or function;
+/// siblings are ordered by line. Columns are not indexed, so definitions sharing a line
+/// (e.g. `A = make(); B = make()` aliases) fall back to name order to stay deterministic.
+pub fn to_document_symbols<'a>(symbols: impl IntoIterator<Item = &'a Symbol>) -> Vec<DocumentSymbol> {
+    let mut symbols: Vec<&Symbol> = symbols.into_iter().collect();
+    symbols.sort_by_key(|s| (s.context.line_number, s.context.parent_context.len(), s.name.as_str()));
+
+    // A definition is identified by its name and line, which is how parents are recorded
+    let mut children: HashMap<(&str, usize), Vec<&Symbol>> = HashMap::new();
+    let mut roots = Vec::new();
+    for &symbol in &symbols {
+        match symbol.context.parent_context.last() {
+            Some(parent) => children
+                .entry((parent.name.as_str(), parent.line_number))
+                .or_default()
+                .push(symbol),
+            None => roots.push(symbol),
+        }
+    }
+
+    fn build(symbol: &Symbol, children: &HashMap<(&str, usize), Vec<&Symbol>>) -> DocumentSymbol {
+        let nested = children
+            .get(&(symbol.name.as_str(), symbol.context.line_number))
+            .map(|nested| nested.iter().map(|child| build(child, children)).collect());
+
+        #[allow(deprecated)]
+        DocumentSymbol {
+            name: symbol.name.clone(),
+            detail: symbol_detail(symbol),
+            kind: symbol_kind(&symbol.context.symbol_type),
+            tags: symbol_tags(symbol),
+            deprecated: None,
+            range: symbol_range(symbol),
+            selection_range: symbol_range(symbol),
+            children: nested,
+        }
+    }
+
+    roots.into_iter().map(|symbol| build(symbol, &children)).collect()
+}
+
+/// Outline detail: the signature, marked when the method is abstract
+fn symbol_detail(symbol: &Symbol) -> Option<String> {
+    match (&symbol.context.signature, symbol.context.is_abstract) {
+        (Some(signature), true) => Some(format!("abstract {}", signature)),
+        (Some(signature), false) => Some(signature.clone()),
+        (None, true) => Some("abstract".to_string()),
+        (None, false) => None,
+    }
+}
+
+/// Folding ranges for the multi-line definitions among the symbols of a single file.
+/// Nested definitions produce nested ranges; the folded text starts after the `def`/`class` line.
+pub fn to_folding_ranges<'a>(symbols: impl IntoIterator<Item = &'a Symbol>) -> Vec<FoldingRange> {
+    let mut ranges: Vec<FoldingRange> = symbols
+        .into_iter()
+        .filter(|symbol| symbol.context.end_line_number > symbol.context.line_number)
+        .map(|symbol| FoldingRange {
+            start_line: (symbol.context.line_number as u32).saturating_sub(1),
+            start_character: None,
+            end_line: (symbol.context.end_line_number as u32).saturating_sub(1),
+            end_character: None,
+            kind: Some(FoldingRangeKind::Region),
+            collapsed_text: None,
+        })
+        .collect();
+    ranges.sort_by_key(|range| (range.start_line, range.end_line));
+    ranges
+}
+
+/// Token types and modifiers used by `to_semantic_tokens`, in the order their indices refer to
+pub fn semantic_tokens_legend() -> SemanticTokensLegend {
+    SemanticTokensLegend {
+        token_types: vec![
+            SemanticTokenType::FUNCTION,
+            SemanticTokenType::CLASS,
+            SemanticTokenType::METHOD,
+            SemanticTokenType::TYPE,
+        ],
+        token_modifiers: vec![SemanticTokenModifier::DEFINITION],
+    }
+}
+
+/// Index into `semantic_tokens_legend().token_types`
+fn semantic_token_type(symbol_type: &SymbolType) -> u32 {
+    match symbol_type {
+        SymbolType::Function | SymbolType::NestedFunction => 0,
+        SymbolType::Class | SymbolType::NestedClass => 1,
+        SymbolType::Method => 2,
+        SymbolType::TypeAlias => 3,
+    }
+}
+
+/// Semantic tokens for the names of the definitions among the symbols of one file, limited to
+/// those starting within `range` when given. This only covers definitions; references to
+/// them and everything else in the file are left to the editor's own highlighting.
+///
+/// `source` must be the file the symbols were collected from: symbols without a stored name
+/// range, or whose range no longer holds their name (the file changed since indexing), are
+/// skipped. Columns are in UTF-16 code units, the LSP default.
+pub fn to_semantic_tokens<'a>(
+    symbols: impl IntoIterator<Item = &'a Symbol>,
+    source: &str,
+    range: Option<Range>,
+) -> Vec<SemanticToken> {
+    // Names were located in the source with any BOM removed
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let line_index = LineIndex::new(source);
+    let mut tokens: Vec<(u32, u32, u32, u32)> = symbols
+        .into_iter()
+        .filter_map(|symbol| {
+            let (start, end) = (symbol.context.name_start_byte?, symbol.context.name_end_byte?);
+            if source.get(start..end) != Some(symbol.name.as_str()) {
+                return None;
+            }
+            let (line, byte_column) = line_index.line_col(start);
+            let line_start = line_index.line_start(line)?;
+            let column = byte_to_utf16_column(&source[line_start..], byte_column);
+            let length = symbol.name.encode_utf16().count();
+            Some((line as u32, column as u32, length as u32, semantic_token_type(&symbol.context.symbol_type)))
+        })
+        .filter(|&(line, column, _, _)| {
+            let position = Position { line, character: column };
+            range.is_none_or(|range| range.start <= position && position < range.end)
+        })
+        .collect();
+    tokens.sort_unstable();
+    tokens.dedup();
+
+    // Each token is encoded relative to the one before it
+    let mut previous = (0, 0);
+    tokens
+        .into_iter()
+        .map(|(line, column, length, token_type)| {
+            let delta_line = line - previous.0;
+            let delta_start = if delta_line == 0 { column - previous.1 } else { column };
+            previous = (line, column);
+            SemanticToken {
+                delta_line,
+                delta_start,
+                length,
+                token_type,
+                token_modifiers_bitset: 1,
+            }
+        })
+        .collect()
+}
+
+/// The innermost symbol in file `file_path_index` whose definition spans `line` (1-based),
+/// e.g. the method containing the cursor rather than its class
+pub fn symbol_at_position<'a>(
+    symbols: impl IntoIterator<Item = &'a Symbol>,
+    file_path_index: usize,
+    line: usize,
+) -> Option<&'a Symbol> {
+    symbols
+        .into_iter()
+        .filter(|symbol| {
+            symbol.context.file_path_index == file_path_index
+                && symbol.context.line_number <= line
+                && line <= symbol.context.end_line_number
+        })
+        // Enclosing definitions start earlier, so the latest start is the innermost
+        .max_by_key(|symbol| (symbol.context.line_number, symbol.context.parent_context.len()))
+}
+
+/// The symbols in file `file_path_index` affected by an edit to lines `start..=end` (1-based):
+/// those defined within the range and those enclosing or overlapping it, outermost first.
+/// Only these need refreshing after a small edit, e.g. one method rather than its whole class.
+pub fn symbols_in_line_range<'a>(
+    symbols: impl IntoIterator<Item = &'a Symbol>,
+    file_path_index: usize,
+    start: usize,
+    end: usize,
+) -> Vec<&'a Symbol> {
+    let mut affected: Vec<&Symbol> = symbols
+        .into_iter()
+        .filter(|symbol| {
+            symbol.context.file_path_index == file_path_index
+                && symbol.context.line_number <= end
+                && start <= symbol.context.end_line_number
+        })
+        .collect();
+    affected.sort_by_key(|symbol| (symbol.context.line_number, symbol.context.parent_context.len(), symbol.name.as_str()));
+    affected
+}
+
+/// The symbols enclosing `symbol`, innermost first, looked up from its `parent_context`.
+/// Parents missing from `symbols` (e.g. filtered out of the index) are skipped.
+pub fn parent_chain<'a>(symbol: &Symbol, symbols: impl IntoIterator<Item = &'a Symbol>) -> Vec<&'a Symbol> {
+    let by_definition: HashMap<(&str, usize), &Symbol> = symbols
+        .into_iter()
+        .filter(|s| s.context.file_path_index == symbol.context.file_path_index)
+        .map(|s| ((s.name.as_str(), s.context.line_number), s))
+        .collect();
+    symbol
+        .context
+        .parent_context
+        .iter()
+        .rev()
+        .filter_map(|parent| by_definition.get(&(parent.name.as_str(), parent.line_number)).copied())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::python::parse_python_files_parallel;
+    use crate::symbols::{ParentContext, SymbolContext, SymbolStats};
+    use anyhow::Result;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    // Helper function to create a simple PathRegistry for tests
+    fn create_test_path_registry() -> PathRegistry {
+        let mut registry = PathRegistry::new();
+        registry.register_path(PathBuf::from("/test/path/file1.py"));
+        registry.register_path(PathBuf::from("/test/path/file2.py"));
+        registry
+    }
+
+    // Helper function to create a sample symbol
+    fn create_test_symbol(
+        name: &str,
+        kind: SymbolType,
+        line: usize,
+        file_index: usize,
+        parent: Option<&str>,
+        module: &str,
+    ) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            context: SymbolContext {
+                symbol_type: kind,
+                line_number: line,
+                end_line_number: line,
+                file_path_index: file_index,
+                parent_context: parent
+                    .map(|p| {
+                        vec![ParentContext {
+                            name: p.to_string(),
+                            line_number: 0,
+                            symbol_type: SymbolType::Function,
+                        }]
+                    })
+                    .unwrap_or_default(),
+                fully_qualified_module: module.to_string(),
+                module: module.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
+    fn file_uri(registry: &PathRegistry, index: usize) -> Uri {
+        Url::from_file_path(registry.get_path(index))
+            .unwrap()
+            .as_str()
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_to_symbol_information_conversion() {
+        let registry = create_test_path_registry();
+        let symbol = create_test_symbol("my_function", SymbolType::Function, 10, 0, None, "file1");
+
+        let lsp_info = to_symbol_information(&symbol, &registry, 100, false).unwrap();
+
+        assert_eq!(lsp_info.name, "my_function");
+        assert_eq!(lsp_info.kind, SymbolKind::FUNCTION);
+        assert_eq!(lsp_info.location.uri, file_uri(&registry, 0));
+        assert_eq!(
+            lsp_info.location.range,
+            Range {
+                start: Position { line: 9, character: 0 },
+                end: Position { line: 9, character: 0 },
+            }
+        );
+        assert_eq!(lsp_info.container_name, Some("file1".to_string()));
+        assert!(lsp_info.tags.is_none());
+
+        let with_score = to_symbol_information(&symbol, &registry, 100, true).unwrap();
+        assert_eq!(with_score.name, "my_function (100)");
+    }
+
+    #[test]
+    fn test_to_symbol_information_class_conversion() {
+        let registry = create_test_path_registry();
+        let symbol = create_test_symbol("MyClass", SymbolType::Class, 25, 1, None, "file2");
+
+        let lsp_info = to_symbol_information(&symbol, &registry, 50, false).unwrap();
+
+        assert_eq!(lsp_info.name, "MyClass");
+        assert_eq!(lsp_info.kind, SymbolKind::CLASS);
+        assert_eq!(lsp_info.location.uri, file_uri(&registry, 1));
+        assert_eq!(lsp_info.location.range.start.line, 24);
+        assert_eq!(lsp_info.container_name, Some("file2".to_string()));
+    }
+
+    #[test]
+    fn test_to_symbol_information_method_conversion() {
+        let registry = create_test_path_registry();
+        let symbol = create_test_symbol(
+            "my_method",
+            SymbolType::Method,
+            30,
+            1,
+            Some("MyClass"),
+            "file2",
+        );
+
+        let lsp_info = to_symbol_information(&symbol, &registry, 75, false).unwrap();
+
+        assert_eq!(lsp_info.name, "my_method");
+        assert_eq!(lsp_info.kind, SymbolKind::FUNCTION);
+        assert_eq!(lsp_info.location.range.start.line, 29);
+        assert_eq!(lsp_info.container_name, Some("MyClass".to_string()));
+    }
+
+    #[test]
+    fn test_container_name_modes_for_nested_method() {
+        let registry = create_test_path_registry();
+        let mut symbol = create_test_symbol("my_method", SymbolType::Method, 30, 1, Some("Inner"), "file2");
+        symbol.context.parent_context.insert(0, ParentContext {
+            name: "Outer".to_string(),
+            line_number: 0,
+            symbol_type: SymbolType::Class,
+        });
+        symbol.context.fully_qualified_module = "pkg".to_string();
+
+        let container = |mode| {
+            let options = ContainerNameOptions { mode, ..Default::default() };
+            to_symbol_information_with_container(&symbol, &registry, 0, false, &options)
+                .unwrap()
+                .container_name
+        };
+        assert_eq!(container(ContainerNameMode::ParentsOrModule), Some("Outer.Inner".to_string()));
+        assert_eq!(container(ContainerNameMode::Parents), Some("Outer.Inner".to_string()));
+        assert_eq!(container(ContainerNameMode::Module), Some("pkg.file2".to_string()));
+
+        // Top-level symbols only differ in the parents mode
+        let function = create_test_symbol("my_function", SymbolType::Function, 10, 0, None, "file1");
+        assert_eq!(container_name_with_mode(&function, ContainerNameMode::ParentsOrModule), Some("file1".to_string()));
+        assert_eq!(container_name_with_mode(&function, ContainerNameMode::Parents), None);
+
+        assert_eq!("parents".parse(), Ok(ContainerNameMode::Parents));
+        assert_eq!("Module".parse(), Ok(ContainerNameMode::Module));
+        assert_eq!("auto".parse(), Ok(ContainerNameMode::ParentsOrModule));
+        assert!("full".parse::<ContainerNameMode>().is_err());
+    }
+
+    #[test]
+    fn test_container_name_custom_separator() {
+        let registry = create_test_path_registry();
+        let mut symbol = create_test_symbol("my_method", SymbolType::Method, 30, 1, Some("Inner"), "file2");
+        symbol.context.parent_context.insert(0, ParentContext {
+            name: "Outer".to_string(),
+            line_number: 0,
+            symbol_type: SymbolType::Class,
+        });
+        symbol.context.fully_qualified_module = "pkg".to_string();
+
+        let options = ContainerNameOptions { separator: "::".to_string(), ..Default::default() };
+        let info = to_symbol_information_with_container(&symbol, &registry, 0, false, &options).unwrap();
+        assert_eq!(info.container_name, Some("Outer::Inner".to_string()));
+
+        // Module paths are unaffected by the separator
+        let options = ContainerNameOptions { mode: ContainerNameMode::Module, separator: " > ".to_string(), ..Default::default() };
+        assert_eq!(container_name_with_options(&symbol, &options), Some("pkg.file2".to_string()));
+        // Parents dropped at collection are marked
+        symbol.context.truncate_parents(1);
+        let options = ContainerNameOptions { separator: "::".to_string(), ..Default::default() };
+        assert_eq!(container_name_with_options(&symbol, &options), Some("…::Inner".to_string()));
+    }
+
+    #[test]
+    fn test_qualified_names_option() {
+        let registry = create_test_path_registry();
+        let mut symbol = create_test_symbol("my_method", SymbolType::Method, 30, 1, Some("MyClass"), "file2");
+        symbol.context.fully_qualified_module = "pkg".to_string();
+
+        let info = to_symbol_information_with_container(&symbol, &registry, 0, false, &ContainerNameOptions::default()).unwrap();
+        assert_eq!(info.name, "my_method");
+
+        let options = ContainerNameOptions { qualified_names: true, ..Default::default() };
+        let info = to_symbol_information_with_container(&symbol, &registry, 42, false, &options).unwrap();
+        assert_eq!(info.name, "pkg.file2.MyClass.my_method");
+        // The container is still shown separately
+        assert_eq!(info.container_name, Some("MyClass".to_string()));
+        let info = to_symbol_information_with_container(&symbol, &registry, 42, true, &options).unwrap();
+        assert_eq!(info.name, "pkg.file2.MyClass.my_method (42)");
+
+        // Top-level modules have no package prefix
+        let mut function = create_test_symbol("helper", SymbolType::Function, 1, 0, None, "file1");
+        function.context.fully_qualified_module = String::new();
+        assert_eq!(qualified_name(&function), "file1.helper");
+    }
+
+    #[test]
+    fn test_relative_path_resolves_against_current_dir() {
+        let mut registry = PathRegistry::new();
+        registry.register_path(PathBuf::from("relative/file.py"));
+        let symbol = create_test_symbol("f", SymbolType::Function, 1, 0, None, "file");
+
+        let expected = Url::from_file_path(std::env::current_dir().unwrap().join("relative/file.py")).unwrap();
+        let info = to_symbol_information(&symbol, &registry, 0, false).unwrap();
+        assert_eq!(info.location.uri.as_str(), expected.as_str());
+        assert!(to_workspace_symbol(&symbol, &registry, 0, false).is_some());
+    }
+
+    #[test]
+    fn test_to_workspace_symbol_conversion() {
+        let registry = create_test_path_registry();
+        let symbol = create_test_symbol("MyClass", SymbolType::Class, 25, 1, None, "file2");
+
+        let workspace_symbol = to_workspace_symbol(&symbol, &registry, 50, false).unwrap();
+
+        assert_eq!(workspace_symbol.name, "MyClass");
+        assert_eq!(workspace_symbol.kind, SymbolKind::CLASS);
+        assert_eq!(workspace_symbol.container_name, Some("file2".to_string()));
+        match workspace_symbol.location {
+            OneOf::Left(location) => {
+                assert_eq!(location.uri, file_uri(&registry, 1));
+                assert_eq!(location.range.start.line, 24);
+            }
+            OneOf::Right(_) => panic!("Expected a full location"),
+        }
+    }
+
+    #[test]
+    fn test_deprecated_symbol_has_deprecated_tag() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("legacy.py");
+        std::fs::write(&file_path, r#"
+from typing_extensions import deprecated
+
+@deprecated("use new_function instead")
+def old_function():
+    pass
+
+def new_function():
+    pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], temp_dir.path(), &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let registry = stats.path_registry.lock().unwrap();
+
+        let old_function = functions.iter().find(|f| f.name == "old_function").unwrap();
+        let lsp_info = to_symbol_information(old_function, &registry, 0, false).unwrap();
+        assert_eq!(lsp_info.tags, Some(vec![SymbolTag::DEPRECATED]));
+
+        let new_function = functions.iter().find(|f| f.name == "new_function").unwrap();
+        let lsp_info = to_symbol_information(new_function, &registry, 0, false).unwrap();
+        assert!(lsp_info.tags.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_to_document_symbols_nesting() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("shapes.py");
+        std::fs::write(&file_path, r#"class Shape:
+    def area(self):
+        def helper():
+            pass
+        return 0
+
+    def perimeter(self):
+        return 0
+
+def make_shape():
+    pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], temp_dir.path(), &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        let outline = to_document_symbols(functions.iter().chain(classes.iter()));
+
+        let names: Vec<&str> = outline.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["Shape", "make_shape"]);
+
+        let methods = outline[0].children.as_ref().unwrap();
+        let method_names: Vec<&str> = methods.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(method_names, vec!["area", "perimeter"]);
+        assert_eq!(methods[0].children.as_ref().unwrap()[0].name, "helper");
+        assert!(methods[1].children.is_none());
+        assert!(outline[1].children.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_document_symbol_detail_shows_signature() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("store.py");
+        std::fs::write(&file_path, r#"class Store(Base, Generic[T]):
+    def get(
+        self,
+        key: str,
+        default=None,
+    ) -> Optional[T]:
+        pass
+
+class Plain:
+    pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], temp_dir.path(), &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let outline = to_document_symbols(functions.iter().chain(classes.iter()));
+
+        assert_eq!(outline[0].detail.as_deref(), Some("(Base, Generic[T])"));
+        let get = &outline[0].children.as_ref().unwrap()[0];
+        assert_eq!(get.detail.as_deref(), Some("(self, key: str, default=None) -> Optional[T]"));
+        assert_eq!(outline[1].name, "Plain");
+        assert_eq!(outline[1].detail, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_semantic_tokens_for_definition_names() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("shop.py");
+        let source = "def top(): pass\n\nclass Cart:\n    def add(self): pass\n    def éclair(self): pass\n\nx = 1; def_late = 2\n";
+        std::fs::write(&file_path, source)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], temp_dir.path(), &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let symbols = || functions.iter().chain(classes.iter());
+
+        let tokens = to_semantic_tokens(symbols(), source, None);
+        let encoded: Vec<(u32, u32, u32, u32)> = tokens.iter()
+            .map(|t| (t.delta_line, t.delta_start, t.length, t.token_type))
+            .collect();
+        assert_eq!(encoded, vec![
+            (0, 4, 3, 0), // top, line 0 col 4
+            (2, 6, 4, 1), // Cart, line 2 col 6
+            (1, 8, 3, 2), // add, line 3 col 8
+            (1, 8, 6, 2), // éclair, line 4 col 8, six UTF-16 units
+        ]);
+        assert!(tokens.iter().all(|t| t.token_modifiers_bitset == 1));
+        assert_eq!(semantic_tokens_legend().token_types[2], SemanticTokenType::METHOD);
+
+        // A range keeps only the tokens starting inside it; the first is relative to the document start
+        let range = Range { start: Position { line: 3, character: 0 }, end: Position { line: 4, character: 0 } };
+        let in_range = to_semantic_tokens(symbols(), source, Some(range));
+        assert_eq!(in_range.len(), 1);
+        assert_eq!((in_range[0].delta_line, in_range[0].delta_start, in_range[0].length), (3, 8, 3));
+
+        // Names that moved since indexing are dropped rather than highlighted in the wrong place
+        let edited = format!("\n{}", source);
+        assert!(to_semantic_tokens(symbols(), &edited, None).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_folding_ranges_class_with_methods() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("account.py");
+        std::fs::write(&file_path, r#"class Account:
+    def deposit(self, amount):
+        self.balance += amount
+
+    @property
+    def total(self):
+        return self.balance
+
+def one_liner(): pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], temp_dir.path(), &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        let ranges = to_folding_ranges(functions.iter().chain(classes.iter()));
+        let lines: Vec<(u32, u32)> = ranges.iter().map(|r| (r.start_line, r.end_line)).collect();
+        // The single-line function has nothing to fold
+        assert_eq!(lines, vec![(0, 6), (1, 2), (5, 6)]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbol_at_position_in_nested_method() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("bank.py");
+        std::fs::write(&file_path, r#"class Bank:
+    class Account:
+        def deposit(self, amount):
+            total = self.balance
+            return total + amount
+
+    def audit(self):
+        pass
+
+def helper():
+    pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], temp_dir.path(), &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let symbols = || functions.iter().chain(classes.iter());
+
+        let symbol = symbol_at_position(symbols(), 0, 4).expect("Cursor is inside deposit");
+        assert_eq!(symbol.name, "deposit");
+        let chain: Vec<&str> = parent_chain(symbol, symbols()).iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(chain, vec!["Account", "Bank"]);
+
+        // Between methods the class itself is innermost
+        assert_eq!(symbol_at_position(symbols(), 0, 6).map(|s| s.name.as_str()), Some("Bank"));
+        assert_eq!(symbol_at_position(symbols(), 0, 10).map(|s| s.name.as_str()), Some("helper"));
+        assert!(symbol_at_position(symbols(), 0, 9).is_none());
+        assert!(symbol_at_position(symbols(), 1, 4).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbols_in_line_range_within_one_method() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let file_path = temp_dir.path().join("service.py");
+        let mut source = String::from("class Service:\n");
+        for i in 0..20 {
+            source.push_str(&format!("    def method_{}(self):\n        a = {}\n        return a\n\n", i, i));
+        }
+        source.push_str("def after():\n    pass\n");
+        std::fs::write(&file_path, source)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], temp_dir.path(), &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let affected = |start, end| -> Vec<String> {
+            symbols_in_line_range(functions.iter().chain(classes.iter()), 0, start, end)
+                .iter()
+                .map(|s| s.name.clone())
+                .collect()
+        };
+
+        // method_5 spans lines 22-24; an edit inside it touches only it and its class
+        assert_eq!(affected(23, 23), vec!["Service", "method_5"]);
+        assert_eq!(affected(22, 24), vec!["Service", "method_5"]);
+        // An edit crossing into the next method affects both
+        assert_eq!(affected(24, 26), vec!["Service", "method_5", "method_6"]);
+        // A blank line between methods only belongs to the class
+        assert_eq!(affected(25, 25), vec!["Service"]);
+        // An edit covering the whole file affects every symbol
+        assert_eq!(affected(1, 100).len(), 22);
+        assert!(symbols_in_line_range(functions.iter().chain(classes.iter()), 1, 23, 23).is_empty());
+        Ok(())
+    }
+}
@@ -1,13 +1,40 @@
 use anyhow::{Context, Result};
-use tracing::{debug, error, info, trace, warn};
+use tracing::{debug, error, info, info_span, trace, warn};
 use std::path::{Path, PathBuf};
 use tree_sitter::Parser;
 use rayon::prelude::*;
-use crate::symbols::{Symbol, SymbolStats};
-use std::sync::atomic::Ordering;
-use std::collections::HashSet;
+use crate::symbols::{ParseIssue, PathRegistry, Symbol, SymbolStats};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+
+/// Check once that the bundled tree-sitter-python grammar was generated for an ABI this
+/// tree-sitter library supports, returning the grammar's ABI version. A mismatched pair
+/// would otherwise fail every parser with an unhelpful error.
+pub fn check_grammar_compatibility() -> Result<usize> {
+    static COMPATIBILITY: OnceLock<Result<usize, String>> = OnceLock::new();
+    let compatibility = COMPATIBILITY.get_or_init(|| {
+        let version = tree_sitter_python::language().version();
+        if (tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION..=tree_sitter::LANGUAGE_VERSION).contains(&version) {
+            Ok(version)
+        } else {
+            Err(format!(
+                "tree-sitter-python grammar uses ABI version {}, but this tree-sitter library supports \
+                 versions {} to {}; update the tree-sitter and tree-sitter-python crates together",
+                version,
+                tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+                tree_sitter::LANGUAGE_VERSION,
+            ))
+        }
+    });
+    compatibility.clone().map_err(anyhow::Error::msg)
+}
 
 pub fn create_python_parser() -> Result<Parser> {
+    check_grammar_compatibility()?;
     let mut parser = Parser::new();
     // Use the tree-sitter-python language function directly
     parser.set_language(tree_sitter_python::language())
@@ -52,9 +79,17 @@ pub fn parse_python_files_sequential(
                      files.len(), path_registry.paths.len());
     }
     
+    let collect_issues = stats.parse_issues.is_some();
+    let mut issues = Vec::new();
+    
     for path in files {
+        stats.files_scanned.fetch_add(1, Ordering::Relaxed);
         match parse_file_and_update_stats(&mut parser, path, base_dir, stats) {
-            Ok(_) => {},
+            Ok(syntax_error_line) => {
+                if let (true, Some(line)) = (collect_issues, syntax_error_line) {
+                    issues.push(syntax_error_issue(path, line));
+                }
+            },
             Err(e) => {
                 if e.to_string().contains("Failed to read") {
                     stats.io_errors.fetch_add(1, Ordering::Relaxed);
@@ -64,20 +99,60 @@ pub fn parse_python_files_sequential(
                     stats.other_errors.fetch_add(1, Ordering::Relaxed);
                 }
                 warn!("Error processing {}: {}", path.display(), e);
+                if collect_issues {
+                    issues.push(error_issue(path, &e));
+                }
             }
         }
     }
     
+    stats.record_parse_issues(issues);
     Ok(())
 }
 
+/// Environment variable that makes `parse_python_files_parallel` and
+/// `parse_python_files_parallel_with_options` parse on the calling thread, without starting
+/// a rayon pool, for sandboxes where spawning threads is restricted.
+/// Any value but empty or `0` turns it on.
+pub const SEQUENTIAL_PARSE_VAR: &str = "PYLIGHT_SEQUENTIAL";
+
+/// Whether a `SEQUENTIAL_PARSE_VAR` value asks for sequential parsing
+fn is_sequential_toggle(value: Option<&std::ffi::OsStr>) -> bool {
+    value.is_some_and(|value| !value.is_empty() && value != "0")
+}
+
+/// Parse `files` across the global rayon pool, or on the calling thread when
+/// `SEQUENTIAL_PARSE_VAR` is set
 pub fn parse_python_files_parallel(
     files: &[PathBuf],
     base_dir: &Path,
     stats: &SymbolStats,
 ) -> Result<()> {
+    parse_python_files_parallel_with_options(files, base_dir, stats, &CollectOptions::default())
+}
+
+/// Parse `files` in chunks, across the current rayon pool or one chunk after another on the
+/// calling thread when `sequential`
+fn parse_files_in_chunks(
+    files: &[PathBuf],
+    base_dir: &Path,
+    stats: &SymbolStats,
+    options: &CollectOptions,
+    sequential: bool,
+) -> Result<()> {
+    // Fail once up front rather than in every chunk
+    check_grammar_compatibility()?;
+    
+    let deadline = options.time_budget.map(|budget| Instant::now() + budget);
+    
     // Process files in chunks to reduce lock contention
-    let chunk_size = (files.len() / rayon::current_num_threads()).max(10);
+    // Asking rayon for its thread count would start the global pool
+    let threads = if sequential { 1 } else { rayon::current_num_threads() };
+    let mut chunk_size = (files.len() / threads).max(10);
+    if deadline.is_some() {
+        // The budget is only checked between chunks, so keep them small enough to stop promptly
+        chunk_size = chunk_size.min(TIME_BUDGET_CHUNK_SIZE);
+    }
     info!("Processing with chunk size: {}", chunk_size);
     
     // Check for duplicate paths in the files list
@@ -99,6 +174,7 @@ pub fn parse_python_files_parallel(
     // Create a stable mapping of paths to indices before starting parallel processing
     let path_indices: std::collections::HashMap<PathBuf, usize>;
     {
+        let _span = info_span!("pre_index_paths", files = files.len()).entered();
         let mut global_registry = stats.path_registry.lock().unwrap();
         // Clear existing paths to prevent any stale data
         global_registry.clear();
@@ -113,7 +189,15 @@ pub fn parse_python_files_parallel(
                       files.len(), global_registry.paths.len());
     }
     
-    files.par_chunks(chunk_size).for_each(|chunk| {
+    let collect_issues = stats.parse_issues.is_some();
+    
+    let parse_chunk = |chunk: &[PathBuf]| {
+        if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            stats.truncated.store(true, Ordering::Relaxed);
+            return;
+        }
+        let chunk_start = Instant::now();
+        let parse_span = info_span!("parse_chunk", files = chunk.len()).entered();
         let mut parser = match create_python_parser() {
             Ok(p) => p,
             Err(e) => {
@@ -129,30 +213,65 @@ pub fn parse_python_files_parallel(
         let mut local_syntax_errors = 0;
         let mut local_io_errors = 0;
         let mut local_other_errors = 0;
+        let mut local_issues = Vec::new();
+        let mut local_empty_files = Vec::new();
+        let mut local_entry_points = Vec::new();
+        let mut local_generated_files = 0;
+        let mut local_parse_timeouts = 0;
         
         // Process the chunk locally without global locks
+        let mut local_files_scanned = 0;
         for path in chunk {
+            if options.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                stats.truncated.store(true, Ordering::Relaxed);
+                break;
+            }
+            local_files_scanned += 1;
             // Use the pre-computed path index from our stable mapping
             let path_idx = path_indices.get(path).cloned();
             
-            match process_file_with_path_idx(&mut parser, path, base_dir, path_idx) {
-                Ok((functions, classes)) => {
-                    local_functions.extend(functions);
-                    local_classes.extend(classes);
+            match process_file_with_path_idx(&mut parser, path, base_dir, path_idx, collect_issues, options) {
+                Ok(file) if file.generated => local_generated_files += 1,
+                Ok(file) => {
+                    if file.functions.is_empty() && file.classes.is_empty() {
+                        local_empty_files.push(path.clone());
+                    }
+                    if file.has_main {
+                        local_entry_points.push(path.clone());
+                    }
+                    local_functions.extend(file.functions);
+                    local_classes.extend(file.classes);
+                    if let Some(line) = file.syntax_error_line {
+                        local_issues.push(syntax_error_issue(path, line));
+                    }
                 },
                 Err(e) => {
                     if e.to_string().contains("Failed to read") {
                         local_io_errors += 1;
+                    } else if e.to_string().starts_with(PARSE_TIMEOUT_MESSAGE) {
+                        local_parse_timeouts += 1;
                     } else if e.to_string().contains("Failed to parse") {
                         local_syntax_errors += 1;
                     } else {
                         local_other_errors += 1;
                     }
                     warn!("Error processing {}: {}", path.display(), e);
+                    if collect_issues {
+                        local_issues.push(error_issue(path, &e));
+                    }
                 }
             }
         }
         
+        drop(parse_span);
+        let _merge_span = info_span!("merge_chunk").entered();
+        
+        stats.record_parse_issues(local_issues);
+        stats.record_empty_files(local_empty_files);
+        stats.record_entry_points(local_entry_points);
+        stats.generated_files.fetch_add(local_generated_files, Ordering::Relaxed);
+        stats.parse_timeouts.fetch_add(local_parse_timeouts, Ordering::Relaxed);
+        
         // Now merge the local results with global state
         if !local_functions.is_empty() {
             let mut func_set = stats.functions.lock().unwrap();
@@ -164,7 +283,8 @@ pub fn parse_python_files_parallel(
             class_set.extend(local_classes);
         }
         
-        // Update error counts
+        // Update counts
+        stats.files_scanned.fetch_add(local_files_scanned, Ordering::Relaxed);
         if local_syntax_errors > 0 {
             stats.syntax_errors.fetch_add(local_syntax_errors, Ordering::Relaxed);
         }
@@ -174,24 +294,828 @@ pub fn parse_python_files_parallel(
         if local_other_errors > 0 {
             stats.other_errors.fetch_add(local_other_errors, Ordering::Relaxed);
         }
-    });
+        stats.parse_busy_nanos.fetch_add(chunk_start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    };
+    if sequential {
+        files.chunks(chunk_size).for_each(parse_chunk);
+    } else {
+        files.par_chunks(chunk_size).for_each(parse_chunk);
+    }
     
     Ok(())
 }
 
+/// Parse `files` on a background thread, sending each function and class symbol as soon as
+/// its file is parsed so a consumer can show results progressively. The channel closes once
+/// every file is done; files that cannot be read or parsed are skipped.
+///
+/// Symbols' `file_path_index` refers to a `PathRegistry` with `files` registered in order,
+/// the same indexing `parse_python_files_parallel` uses.
+pub fn parse_python_files_streaming(files: &[PathBuf], base_dir: &Path) -> Receiver<Symbol> {
+    let mut path_registry = PathRegistry::new();
+    let mut seen = HashSet::new();
+    let indexed_files: Vec<(PathBuf, usize)> = files.iter()
+        .map(|path| (path.clone(), path_registry.register_path(path.clone())))
+        .filter(|(_, index)| seen.insert(*index))
+        .collect();
+    let base_dir = base_dir.to_path_buf();
+
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        indexed_files.par_iter().for_each_init(
+            || create_python_parser().ok(),
+            |parser, (path, index)| {
+                let Some(parser) = parser else {
+                    return;
+                };
+                match collect_file_symbols(parser, path, &base_dir, *index, &CollectOptions::default()) {
+                    Ok((functions, classes)) => {
+                        for symbol in functions.into_iter().chain(classes) {
+                            // The consumer may stop listening early; the remaining work is harmless
+                            let _ = sender.send(symbol);
+                        }
+                    }
+                    Err(e) => debug!("Skipping {} while streaming: {}", path.display(), e),
+                }
+            },
+        );
+    });
+    receiver
+}
+
+/// How a file's symbols differ between a saved index and the file's current source.
+/// Symbols are named by their dotted path within the file (e.g. "Client.send").
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FileDrift {
+    pub path: PathBuf,
+    /// Symbols in the source but not the index
+    pub added: Vec<String>,
+    /// Symbols in the index but not the source
+    pub removed: Vec<String>,
+    /// Symbols in both, but on different lines
+    pub moved: Vec<String>,
+    /// Why the file could not be re-parsed (e.g. it was deleted)
+    pub error: Option<String>,
+}
+
+impl FileDrift {
+    pub fn has_changes(&self) -> bool {
+        self.error.is_some() || !self.added.is_empty() || !self.removed.is_empty() || !self.moved.is_empty()
+    }
+}
+
+// Symbols keyed by dotted name and kind, with the lines each is defined on
+type SymbolLines = BTreeMap<(String, &'static str), Vec<usize>>;
+
+fn symbol_lines<'a>(symbols: impl IntoIterator<Item = &'a Symbol>) -> SymbolLines {
+    let mut lines = SymbolLines::new();
+    for symbol in symbols {
+        let mut name: Vec<&str> = symbol.context.parent_context.iter().map(|p| p.name.as_str()).collect();
+        name.push(&symbol.name);
+        lines.entry((name.join("."), symbol.context.symbol_type.kind_name()))
+            .or_default()
+            .push(symbol.context.line_number);
+    }
+    for file_lines in lines.values_mut() {
+        file_lines.sort_unstable();
+    }
+    lines
+}
+
+/// Re-parse every file in `path_registry` and report those whose symbols no longer match
+/// the indexed `functions` and `classes`, sorted by path. `options` should match the ones
+/// the index was built with, or their differences will show up as drift.
+pub fn find_index_drift(
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+    base_dir: &Path,
+    options: &CollectOptions,
+) -> Vec<FileDrift> {
+    let mut indexed: Vec<Vec<&Symbol>> = vec![Vec::new(); path_registry.paths.len()];
+    for symbol in functions.iter().chain(classes.iter()) {
+        if let Some(file_symbols) = indexed.get_mut(symbol.context.file_path_index) {
+            file_symbols.push(symbol);
+        }
+    }
+
+    let mut drift: Vec<FileDrift> = path_registry.paths.par_iter()
+        .zip(indexed.par_iter())
+        .enumerate()
+        .map_init(
+            || create_python_parser().ok(),
+            |parser, (index, (path, indexed_symbols))| {
+                let mut file_drift = FileDrift { path: path.clone(), ..Default::default() };
+                let current = match parser.as_mut().map(|p| collect_file_symbols(p, path, base_dir, index, options)) {
+                    Some(Ok((functions, classes))) => symbol_lines(functions.iter().chain(classes.iter())),
+                    Some(Err(e)) => {
+                        file_drift.error = Some(e.to_string());
+                        return file_drift;
+                    }
+                    None => {
+                        file_drift.error = Some("Failed to create parser".to_string());
+                        return file_drift;
+                    }
+                };
+                let saved = symbol_lines(indexed_symbols.iter().copied());
+                compare_symbol_lines(&saved, &current, &mut file_drift);
+                file_drift
+            },
+        )
+        .filter(FileDrift::has_changes)
+        .collect();
+    drift.sort_by(|a, b| a.path.cmp(&b.path));
+    drift
+}
+
+/// Record the symbols added, removed and moved going from `before` to `after`
+fn compare_symbol_lines(before: &SymbolLines, after: &SymbolLines, drift: &mut FileDrift) {
+    for ((name, kind), lines) in after {
+        match before.get(&(name.clone(), kind)) {
+            None => drift.added.push(format!("{} {}", kind, name)),
+            Some(before_lines) if before_lines != lines => {
+                drift.moved.push(format!("{} {} ({:?} -> {:?})", kind, name, before_lines, lines));
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, kind) in before.keys() {
+        if !after.contains_key(&(name.clone(), kind)) {
+            drift.removed.push(format!("{} {}", kind, name));
+        }
+    }
+}
+
+/// Compare two saved indexes file by file, reporting the files whose symbols differ,
+/// sorted by path. Each index numbers its paths independently, so symbols are matched
+/// by the path their index resolves to rather than by `file_path_index`.
+pub fn diff_indexes(
+    old_symbols: &[Symbol],
+    old_paths: &[PathBuf],
+    new_symbols: &[Symbol],
+    new_paths: &[PathBuf],
+) -> Vec<FileDrift> {
+    fn by_path<'a>(symbols: &'a [Symbol], paths: &'a [PathBuf]) -> BTreeMap<&'a Path, Vec<&'a Symbol>> {
+        let mut files: BTreeMap<&Path, Vec<&Symbol>> = paths.iter().map(|p| (p.as_path(), Vec::new())).collect();
+        for symbol in symbols {
+            if let Some(path) = paths.get(symbol.context.file_path_index) {
+                files.entry(path.as_path()).or_default().push(symbol);
+            }
+        }
+        files
+    }
+    let old_files = by_path(old_symbols, old_paths);
+    let new_files = by_path(new_symbols, new_paths);
+
+    let all_paths: BTreeSet<&Path> = old_files.keys().chain(new_files.keys()).copied().collect();
+    all_paths.into_iter()
+        .map(|path| {
+            let mut file_drift = FileDrift { path: path.to_path_buf(), ..Default::default() };
+            let before = symbol_lines(old_files.get(path).into_iter().flatten().copied());
+            let after = symbol_lines(new_files.get(path).into_iter().flatten().copied());
+            compare_symbol_lines(&before, &after, &mut file_drift);
+            file_drift
+        })
+        .filter(FileDrift::has_changes)
+        .collect()
+}
+
+/// Count function and class definitions across `files` without building `Symbol`s.
+///
+/// Definitions nested in any statement (including inside `if`/`try` blocks) are counted
+/// once per occurrence. Files that cannot be read or parsed are skipped.
+pub fn count_symbols_parallel(files: &[PathBuf]) -> (usize, usize) {
+    files
+        .par_iter()
+        .map_init(
+            || create_python_parser().ok(),
+            |parser, path| match parser {
+                Some(parser) => count_symbols_in_file(parser, path).unwrap_or_else(|e| {
+                    debug!("Skipping {} while counting: {}", path.display(), e);
+                    (0, 0)
+                }),
+                None => (0, 0),
+            },
+        )
+        .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1))
+}
+
+fn count_symbols_in_file(parser: &mut Parser, path: &Path) -> Result<(usize, usize)> {
+    let source = std::fs::read(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let tree = parser.parse(&source, None)
+        .with_context(|| format!("Failed to parse {}", path.display()))?;
+
+    let mut functions = 0;
+    let mut classes = 0;
+    let mut cursor = tree.walk();
+    // Pre-order traversal using the cursor only, so no nodes are collected.
+    // Definitions can only appear in statements, so expression subtrees are never entered.
+    loop {
+        let kind = cursor.node().kind();
+        match kind {
+            "function_definition" => functions += 1,
+            "class_definition" => classes += 1,
+            _ => {}
+        }
+
+        if (can_contain_definitions(kind) && cursor.goto_first_child()) || cursor.goto_next_sibling() {
+            continue;
+        }
+        loop {
+            if !cursor.goto_parent() {
+                return Ok((functions, classes));
+            }
+            if cursor.goto_next_sibling() {
+                break;
+            }
+        }
+    }
+}
+
+fn can_contain_definitions(kind: &str) -> bool {
+    matches!(
+        kind,
+        "module" | "decorated_definition" | "function_definition" | "class_definition"
+    ) || is_compound_statement_part(kind)
+}
+
+/// Compound statements, their clauses and blocks, whose bodies can hold definitions
+fn is_compound_statement_part(kind: &str) -> bool {
+    matches!(
+        kind,
+        "block"
+            | "if_statement" | "elif_clause" | "else_clause"
+            | "try_statement" | "except_clause" | "except_group_clause" | "finally_clause"
+            | "with_statement" | "for_statement" | "while_statement"
+            | "match_statement" | "case_clause"
+    )
+}
+
+/// Optional behaviour for `parse_python_files_parallel_with_options`
+#[derive(Debug, Default, Clone)]
+pub struct CollectOptions {
+    /// Parse on a dedicated rayon pool of this many threads instead of the global pool
+    pub threads: Option<usize>,
+    /// Also index module-level `Name = factory(...)` assignments as best-effort class symbols.
+    ///
+    /// This is a naming heuristic, not type inference: the target must be CamelCase
+    /// (capitalized and not ALL_CAPS) and the call must not look like instantiating a class,
+    /// i.e. the callee must be lowercase or a known class factory such as `NamedTuple`.
+    /// So `Point = namedtuple("Point", "x y")` is indexed, while `Config = Config()` and
+    /// `DEFAULT = make()` are not. A capitalized name bound to a factory that returns
+    /// something other than a class (`Logger = logging.getLogger()`) is indexed anyway.
+    pub dynamic_class_aliases: bool,
+    /// Also index module-level Python 3.12 `type X = ...` statements as `SymbolType::TypeAlias`,
+    /// kept with the classes. Files parse the same either way; this only adds the symbols.
+    pub type_aliases: bool,
+    /// Skip functions and classes whose names start with `_`, along with everything defined
+    /// inside them, except `__init__`. Unlike `__all__`, this needs no module metadata.
+    pub public_only: bool,
+    /// Leave methods and nested functions out, keeping module-level functions and classes
+    /// (nested classes included). This shrinks the index, unlike filtering kinds at search time.
+    pub skip_methods: bool,
+    /// Keep only symbols that look like test code according to these patterns, e.g. to
+    /// navigate a test suite. Applied before `filter`.
+    pub tests_only: Option<TestPatterns>,
+    /// Stop starting new chunks of files once this much time has passed since parsing began,
+    /// keeping whatever was parsed so far. Chunks already running finish, and
+    /// `SymbolStats::is_truncated` reports whether any files were skipped.
+    pub time_budget: Option<Duration>,
+    /// Stop parsing once this flag is set, e.g. because the results are no longer wanted.
+    /// It is checked before each file; the rest are skipped and `SymbolStats::is_truncated`
+    /// reports it.
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Give up on a file whose parse takes longer than this, counting it in
+    /// `SymbolStats::parse_timeouts`, so one pathological file cannot stall the whole run
+    pub parse_timeout: Option<Duration>,
+    /// Keep at most this many symbols once parsing finishes, dropping the least useful first
+    /// (see `prune_symbols`) and counting them in `SymbolStats::pruned_symbols`
+    pub max_symbols: Option<usize>,
+    /// Keep the first `SNIPPET_LINES` lines of each definition on `SymbolContext::snippet`
+    /// for previews. This makes the index considerably larger.
+    pub capture_snippet: bool,
+    /// Extra attempts at reading a file after a transient I/O error, e.g. a stale NFS handle.
+    /// `None` uses `DEFAULT_IO_RETRIES`; missing or unreadable files are never retried.
+    pub io_retries: Option<u32>,
+    /// Keep only the nearest this-many enclosing classes/functions in each symbol's
+    /// `parent_context`, dropping outer ones. `None` keeps them all.
+    pub max_parents: Option<usize>,
+    /// Also collect definitions that tree-sitter's error recovery wrapped in an ERROR node.
+    /// This recovers symbols from files the grammar only partly understands (such as Cython
+    /// `.pyx` files), at the risk of indexing fragments of genuinely broken code.
+    pub lenient: bool,
+    /// Called with every symbol as its file is collected, after the options above are applied.
+    /// It may modify the symbol (e.g. strip a generated prefix from the name) and returns
+    /// whether to keep it. Dropping a class does not drop the methods inside it.
+    pub filter: Option<SymbolFilter>,
+    /// Directories (relative to the base directory, or absolute) that module names start
+    /// from, like `src` in a src layout, so `src/pkg/mod.py` is in package `pkg`
+    pub source_roots: Vec<PathBuf>,
+    /// Only count directories containing an `__init__.py` as packages in module names, so
+    /// plain directories above a package are left out. Costs a file check per directory.
+    pub packages_require_init: bool,
+    /// Note files with a module-level `if __name__ == "__main__":` block in
+    /// `SymbolStats::entry_points`, e.g. to find a project's scripts
+    pub detect_main: bool,
+    /// Skip files whose first `GENERATED_HEADER_LINES` lines have a comment containing one of
+    /// these markers (e.g. `DEFAULT_GENERATED_MARKERS`), counting them in
+    /// `SymbolStats::generated_files` instead. Empty indexes every file.
+    pub generated_markers: Vec<String>,
+}
+
+/// A shareable `CollectOptions::filter`
+#[derive(Clone)]
+pub struct SymbolFilter(Arc<dyn Fn(&mut Symbol) -> bool + Send + Sync>);
+
+impl SymbolFilter {
+    pub fn new(filter: impl Fn(&mut Symbol) -> bool + Send + Sync + 'static) -> Self {
+        SymbolFilter(Arc::new(filter))
+    }
+
+    /// Apply the filter to `symbol`, returning it unless it should be dropped
+    pub fn apply(&self, mut symbol: Symbol) -> Option<Symbol> {
+        (self.0)(&mut symbol).then_some(symbol)
+    }
+}
+
+impl std::fmt::Debug for SymbolFilter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("SymbolFilter(..)")
+    }
+}
+
+/// Whether `name` matches `pattern`, where `*` stands for any run of characters and `?` for
+/// any one character. Without either it is an exact, case-sensitive comparison.
+pub fn name_matches_pattern(name: &str, pattern: &str) -> bool {
+    let name: Vec<char> = name.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut n, mut p) = (0, 0);
+    // Where the last `*` was, and how much of the name it has swallowed so far
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            n += 1;
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((star_p, star_n)) = star {
+            // Let the `*` take one more character and try again
+            star = Some((star_p, star_n + 1));
+            p = star_p + 1;
+            n = star_n + 1;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Read names to leave out of an index, one per line. Blank lines and lines starting with
+/// `#` are skipped; see `name_matches_pattern` for how each line matches.
+pub fn read_ignore_names(path: &Path) -> Result<Vec<String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read ignore-names file {}", path.display()))?;
+    Ok(content.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// A filter dropping symbols whose name matches any of `patterns`
+pub fn ignore_names_filter(patterns: Vec<String>) -> SymbolFilter {
+    SymbolFilter::new(move |symbol| {
+        !patterns.iter().any(|pattern| name_matches_pattern(&symbol.name, pattern))
+    })
+}
+
+/// Name patterns matching test functions and classes under pytest's default discovery rules
+pub const DEFAULT_TEST_NAME_PATTERNS: &[&str] = &["test_*", "Test*"];
+/// Directory names whose contents are all treated as tests
+pub const DEFAULT_TEST_DIRECTORIES: &[&str] = &["tests", "test"];
+
+/// How `CollectOptions::tests_only` recognizes test code. This is a naming heuristic: a
+/// symbol is a test if its name or the name of any enclosing class or function matches one
+/// of `names` (see `name_matches_pattern`), or if its file is below a directory in
+/// `directories` (relative to the base directory). So `test_login` and the `setUp` method
+/// of `TestLogin` are tests, while `login` is not unless it lives under `tests/`.
+#[derive(Debug, Clone)]
+pub struct TestPatterns {
+    pub names: Vec<String>,
+    pub directories: Vec<String>,
+}
+
+impl Default for TestPatterns {
+    fn default() -> Self {
+        TestPatterns {
+            names: DEFAULT_TEST_NAME_PATTERNS.iter().map(|pattern| pattern.to_string()).collect(),
+            directories: DEFAULT_TEST_DIRECTORIES.iter().map(|directory| directory.to_string()).collect(),
+        }
+    }
+}
+
+impl TestPatterns {
+    /// Whether `symbol`, defined in the file at `relative_path`, looks like test code
+    pub fn is_test(&self, symbol: &Symbol, relative_path: &Path) -> bool {
+        let name_matches = |name: &str| self.names.iter().any(|pattern| name_matches_pattern(name, pattern));
+        name_matches(&symbol.name)
+            || symbol.context.parent_context.iter().any(|parent| name_matches(&parent.name))
+            || self.is_test_path(relative_path)
+    }
+
+    /// Whether a file at `relative_path` is inside one of the test directories
+    pub fn is_test_path(&self, relative_path: &Path) -> bool {
+        relative_path.parent().is_some_and(|parent| {
+            parent.components().any(|component| {
+                self.directories.iter().any(|directory| component.as_os_str() == directory.as_str())
+            })
+        })
+    }
+}
+
+/// Retries after a transient read error when `CollectOptions::io_retries` is unset
+pub const DEFAULT_IO_RETRIES: u32 = 2;
+
+/// Most extra read attempts `--io-retries` accepts
+pub const MAX_IO_RETRIES: u32 = 10;
+
+/// Wait before the first retry of a failed read, doubling for each further attempt
+const IO_RETRY_BACKOFF: Duration = Duration::from_millis(20);
+
+/// Times the retry wait doubles before it stops growing (20ms up to 640ms)
+const IO_RETRY_MAX_DOUBLINGS: u32 = 5;
+
+/// `ESTALE`, a stale NFS file handle, which is matched by its OS error number
+#[cfg(target_os = "linux")]
+const ESTALE: Option<i32> = Some(116);
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd"))]
+const ESTALE: Option<i32> = Some(70);
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "netbsd", target_os = "openbsd")))]
+const ESTALE: Option<i32> = None;
+
+/// Whether a read error may succeed if tried again: interrupted or timed-out reads, and
+/// stale network file handles. Anything else, like a missing file, is not retried.
+fn is_transient_io_error(error: &std::io::Error) -> bool {
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::Interrupted | std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+    ) || (ESTALE.is_some() && error.raw_os_error() == ESTALE)
+}
+
+/// How long to wait before retry number `attempt` (counting from 0)
+fn retry_delay(backoff: Duration, attempt: u32) -> Duration {
+    backoff * 2u32.pow(attempt.min(IO_RETRY_MAX_DOUBLINGS))
+}
+
+/// Call `read` until it succeeds, fails permanently, or `retries` extra attempts are used up
+fn read_with_retry<F>(mut read: F, retries: u32, backoff: Duration) -> std::io::Result<String>
+where
+    F: FnMut() -> std::io::Result<String>,
+{
+    let mut attempt = 0;
+    loop {
+        match read() {
+            Err(e) if attempt < retries && is_transient_io_error(&e) => {
+                debug!("Retrying read after transient error: {}", e);
+                std::thread::sleep(retry_delay(backoff, attempt));
+                attempt += 1;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Header markers codegen tools commonly leave, for `CollectOptions::generated_markers`
+pub const DEFAULT_GENERATED_MARKERS: &[&str] = &["@generated", "DO NOT EDIT"];
+
+/// Lines at the top of a file searched for a generated-code marker
+pub const GENERATED_HEADER_LINES: usize = 5;
+
+/// Whether one of the first `GENERATED_HEADER_LINES` lines of `source` is a comment
+/// containing one of `markers`
+pub fn is_generated_source(source: &str, markers: &[String]) -> bool {
+    source.lines()
+        .take(GENERATED_HEADER_LINES)
+        .filter_map(|line| line.trim_start().strip_prefix('#'))
+        .any(|comment| markers.iter().any(|marker| comment.contains(marker.as_str())))
+}
+
+/// Most source lines kept per symbol by `CollectOptions::capture_snippet`
+pub const SNIPPET_LINES: usize = 5;
+
+/// Up to `SNIPPET_LINES` lines of `source` starting at the symbol's first line,
+/// stopping at the end of its definition
+fn symbol_snippet(source: &str, symbol: &Symbol) -> String {
+    let start = symbol.context.line_number.saturating_sub(1);
+    let end = symbol.context.end_line_number.max(symbol.context.line_number);
+    source
+        .lines()
+        .skip(start)
+        .take((end - start).min(SNIPPET_LINES))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Largest chunk of files parsed between time budget checks
+const TIME_BUDGET_CHUNK_SIZE: usize = 50;
+
+/// Whether a definition should be left out of a public-only index
+fn is_private_name(name: &str) -> bool {
+    name.starts_with('_') && name != "__init__"
+}
+
+/// Whether a function/class (or decorated) definition node defines a private name
+fn is_private_definition(node: tree_sitter::Node, source: &str) -> bool {
+    let definition = if node.kind() == "decorated_definition" {
+        match node.child_by_field_name("definition") {
+            Some(definition) => definition,
+            None => return false,
+        }
+    } else {
+        node
+    };
+    definition
+        .child_by_field_name("name")
+        .is_some_and(|name| is_private_name(&crate::symbols::get_node_text(name, source)))
+}
+
+/// Run `parse_python_files_parallel` on a dedicated rayon pool of `num_threads` threads,
+/// or on the global pool when `num_threads` is `None`.
+pub fn parse_python_files_parallel_with_threads(
+    files: &[PathBuf],
+    base_dir: &Path,
+    stats: &SymbolStats,
+    num_threads: Option<usize>,
+) -> Result<()> {
+    let options = CollectOptions {
+        threads: num_threads,
+        ..Default::default()
+    };
+    parse_python_files_parallel_with_options(files, base_dir, stats, &options)
+}
+
+/// Same as `parse_python_files_parallel`, with explicit collection options
+pub fn parse_python_files_parallel_with_options(
+    files: &[PathBuf],
+    base_dir: &Path,
+    stats: &SymbolStats,
+    options: &CollectOptions,
+) -> Result<()> {
+    if is_sequential_toggle(std::env::var_os(SEQUENTIAL_PARSE_VAR).as_deref()) {
+        info!("{} is set, parsing on the calling thread", SEQUENTIAL_PARSE_VAR);
+        parse_files_in_chunks(files, base_dir, stats, options, true)?;
+    } else {
+        match options.threads {
+            Some(n) => {
+                let pool = rayon::ThreadPoolBuilder::new()
+                    .num_threads(n)
+                    .build()
+                    .context("Failed to build parsing thread pool")?;
+                info!("Parsing with a dedicated pool of {} threads", pool.current_num_threads());
+                pool.install(|| parse_files_in_chunks(files, base_dir, stats, options, false))?;
+            }
+            None => parse_files_in_chunks(files, base_dir, stats, options, false)?,
+        }
+    }
+    if let Some(max) = options.max_symbols {
+        let pruned = prune_symbols(stats, max);
+        if pruned > 0 {
+            info!("Dropped {} symbols to stay within {} symbols", pruned, max);
+        }
+    }
+    Ok(())
+}
+
+/// Order in which symbols are kept under a symbol budget, lowest first
+fn retention_rank(symbol: &Symbol) -> (usize, u8) {
+    use crate::symbols::SymbolType;
+    let kind = match symbol.context.symbol_type {
+        SymbolType::Class | SymbolType::TypeAlias => 0,
+        SymbolType::Function => 1,
+        SymbolType::NestedClass => 2,
+        SymbolType::Method => 3,
+        SymbolType::NestedFunction => 4,
+    };
+    (symbol.context.parent_context.len(), kind)
+}
+
+/// Drop symbols until at most `max` remain, returning how many were dropped. Shallower
+/// definitions are kept first, so module-level classes and functions go last, then methods
+/// and nested classes, and functions nested deep inside others go first. At equal depth
+/// classes outlive functions. Ties are broken by file and line, so the result is deterministic.
+pub fn prune_symbols(stats: &SymbolStats, max: usize) -> usize {
+    let mut functions = stats.functions.lock().unwrap();
+    let mut classes = stats.classes.lock().unwrap();
+    let total = functions.len() + classes.len();
+    if total <= max {
+        return 0;
+    }
+
+    let mut symbols: Vec<(bool, Symbol)> = functions.drain().map(|symbol| (false, symbol))
+        .chain(classes.drain().map(|symbol| (true, symbol)))
+        .collect();
+    symbols.sort_by(|(_, a), (_, b)| {
+        retention_rank(a).cmp(&retention_rank(b))
+            .then(a.context.file_path_index.cmp(&b.context.file_path_index))
+            .then(a.context.line_number.cmp(&b.context.line_number))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+    symbols.truncate(max);
+    for (is_class, symbol) in symbols {
+        if is_class {
+            classes.insert(symbol);
+        } else {
+            functions.insert(symbol);
+        }
+    }
+
+    let pruned = total - max;
+    stats.pruned_symbols.fetch_add(pruned, Ordering::Relaxed);
+    pruned
+}
+
+// Callees with capitalized names that create classes rather than instances
+const CLASS_FACTORY_CALLEES: &[&str] = &[
+    "NamedTuple", "TypedDict", "NewType", "Enum", "IntEnum", "StrEnum", "Flag", "IntFlag",
+];
+
+/// Whether `name` looks like a class name (CamelCase, not an ALL_CAPS constant)
+fn looks_like_class_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_uppercase()) && name.chars().any(|c| c.is_lowercase())
+}
+
+/// Collect module-level `Name = factory(...)` assignments as class symbols; see
+/// `CollectOptions::dynamic_class_aliases` for the heuristic
+fn collect_dynamic_class_aliases(
+    root: tree_sitter::Node,
+    source: &str,
+    file_path_index: usize,
+    module: &str,
+    fully_qualified_module: &str,
+    class_symbols: &mut HashSet<Symbol>,
+) {
+    let mut cursor = root.walk();
+    for statement in root.children(&mut cursor) {
+        if statement.kind() != "expression_statement" {
+            continue;
+        }
+        let Some(assignment) = statement.named_child(0).filter(|n| n.kind() == "assignment") else {
+            continue;
+        };
+        let (Some(left), Some(right)) = (
+            assignment.child_by_field_name("left"),
+            assignment.child_by_field_name("right"),
+        ) else {
+            continue;
+        };
+        if left.kind() != "identifier" || right.kind() != "call" {
+            continue;
+        }
+        let name = crate::symbols::get_node_text(left, source);
+        if !looks_like_class_name(&name) {
+            continue;
+        }
+        let Some(callee) = right.child_by_field_name("function") else {
+            continue;
+        };
+        let callee = crate::symbols::get_node_text(callee, source);
+        let callee_name = callee.rsplit('.').next().unwrap_or(&callee);
+        if callee_name.starts_with(|c: char| c.is_uppercase()) && !CLASS_FACTORY_CALLEES.contains(&callee_name) {
+            // `Name = SomeClass(...)` most likely creates an instance
+            continue;
+        }
+
+        debug!("Found dynamic class alias: {} = {}(...)", name, callee);
+        class_symbols.insert(Symbol {
+            name,
+            context: crate::symbols::SymbolContext {
+                file_path_index,
+                line_number: statement.start_position().row + 1,
+                end_line_number: statement.end_position().row + 1,
+                module: module.to_string(),
+                fully_qualified_module: fully_qualified_module.to_string(),
+                symbol_type: crate::symbols::SymbolType::Class,
+                name_start_byte: Some(left.start_byte()),
+                name_end_byte: Some(left.end_byte()),
+                ..Default::default()
+            },
+        });
+    }
+}
+
+/// Collect module-level `type X = ...` statements (`type X[T] = ...` included) as type alias
+/// symbols. A grammar without the `type` statement leaves them as ERROR nodes, so none are found.
+fn collect_type_aliases(
+    root: tree_sitter::Node,
+    source: &str,
+    file_path_index: usize,
+    module: &str,
+    fully_qualified_module: &str,
+    class_symbols: &mut HashSet<Symbol>,
+) {
+    let mut cursor = root.walk();
+    for statement in root.children(&mut cursor) {
+        if statement.kind() != "type_alias_statement" {
+            continue;
+        }
+        // The aliased name, or for a generic alias the name its parameters are attached to
+        let Some(name_node) = statement.named_child(0)
+            .and_then(|target| target.named_child(0))
+            .and_then(|node| match node.kind() {
+                "identifier" => Some(node),
+                "generic_type" => node.named_child(0).filter(|n| n.kind() == "identifier"),
+                _ => None,
+            })
+        else {
+            continue;
+        };
+        let name = crate::symbols::get_node_text(name_node, source);
+
+        debug!("Found type alias: {}", name);
+        class_symbols.insert(Symbol {
+            name,
+            context: crate::symbols::SymbolContext {
+                file_path_index,
+                line_number: statement.start_position().row + 1,
+                end_line_number: statement.end_position().row + 1,
+                module: module.to_string(),
+                fully_qualified_module: fully_qualified_module.to_string(),
+                symbol_type: crate::symbols::SymbolType::TypeAlias,
+                name_start_byte: Some(name_node.start_byte()),
+                name_end_byte: Some(name_node.end_byte()),
+                ..Default::default()
+            },
+        });
+    }
+}
+
+/// Parse a single file whose path is already registered at `file_path_index`, e.g. to
+/// refresh one file in a long-running server after it changes on disk
+pub fn collect_file_symbols(
+    parser: &mut Parser,
+    path: &Path,
+    base_dir: &Path,
+    file_path_index: usize,
+    options: &CollectOptions,
+) -> Result<(HashSet<Symbol>, HashSet<Symbol>)> {
+    let file = process_file_with_path_idx(parser, path, base_dir, Some(file_path_index), false, options)?;
+    Ok((file.functions, file.classes))
+}
+
+/// Starts the error for a file whose parse exceeded `CollectOptions::parse_timeout`
+const PARSE_TIMEOUT_MESSAGE: &str = "Timed out parsing";
+
+/// What `process_file_with_path_idx` found in one file
+#[derive(Default)]
+struct ProcessedFile {
+    functions: HashSet<Symbol>,
+    classes: HashSet<Symbol>,
+    /// First syntax error, when asked to look for one
+    syntax_error_line: Option<usize>,
+    /// Has a `__main__` guard; only checked with `CollectOptions::detect_main`
+    has_main: bool,
+    /// Skipped unparsed for a `CollectOptions::generated_markers` header
+    generated: bool,
+}
+
 // Process a file with a known path index from the global PathRegistry
 fn process_file_with_path_idx(
     parser: &mut Parser,
     path: &Path,
     base_dir: &Path,
     global_path_idx: Option<usize>,
-) -> Result<(HashSet<Symbol>, HashSet<Symbol>)> {
+    find_syntax_error: bool,
+    options: &CollectOptions,
+) -> Result<ProcessedFile> {
     debug!("Processing file: {}", path.display());
-    let source = std::fs::read_to_string(path)
+    let retries = options.io_retries.unwrap_or(DEFAULT_IO_RETRIES);
+    let source = read_with_retry(|| std::fs::read_to_string(path), retries, IO_RETRY_BACKOFF)
         .with_context(|| format!("Failed to read {}", path.display()))?;
+    // A UTF-8 BOM is not Python source; left in place it ends up in the first
+    // statement's node text (e.g. a top-level def on line 1)
+    let source = match source.strip_prefix('\u{feff}') {
+        Some(stripped) => stripped.to_owned(),
+        None => source,
+    };
     
-    let tree = parser.parse(&source, None)
-        .with_context(|| format!("Failed to parse {}", path.display()))?;
+    if !options.generated_markers.is_empty() && is_generated_source(&source, &options.generated_markers) {
+        debug!("Skipping generated file: {}", path.display());
+        return Ok(ProcessedFile { generated: true, ..ProcessedFile::default() });
+    }
+    
+    // A timeout of 0 means none
+    parser.set_timeout_micros(options.parse_timeout.map_or(0, |timeout| timeout.as_micros() as u64));
+    let tree = match parser.parse(&source, None) {
+        Some(tree) => tree,
+        None if options.parse_timeout.is_some() => {
+            // Otherwise the next parse would try to resume this one
+            parser.reset();
+            return Err(anyhow::anyhow!("{} {}", PARSE_TIMEOUT_MESSAGE, path.display()));
+        }
+        None => return Err(anyhow::anyhow!("Failed to parse {}", path.display())),
+    };
     
     // Use the provided global path index if available
     let file_path_index = match global_path_idx {
@@ -210,9 +1134,15 @@ fn process_file_with_path_idx(
     
     // Extract the module name and fully qualified module name
     let module = crate::symbols::get_module_name(path);
-    let fully_qualified_module = crate::symbols::get_fully_qualified_module(path, base_dir);
+    let module_root = crate::symbols::module_root(path, base_dir, &options.source_roots);
+    let fully_qualified_module = if options.packages_require_init {
+        crate::symbols::get_package_module(path, &module_root)
+    } else {
+        crate::symbols::get_fully_qualified_module(path, &module_root)
+    };
     
     // Recursive function to collect symbols including nested ones
+    #[allow(clippy::too_many_arguments)]
     fn collect_symbols_recursive(
         node: tree_sitter::Node,
         source: &str, 
@@ -222,6 +1152,7 @@ fn process_file_with_path_idx(
         parent_context: &[crate::symbols::ParentContext],
         function_symbols: &mut HashSet<crate::symbols::Symbol>,
         class_symbols: &mut HashSet<crate::symbols::Symbol>,
+        options: &CollectOptions,
     ) {
         let mut cursor = node.walk();
         cursor.goto_first_child();
@@ -229,6 +1160,11 @@ fn process_file_with_path_idx(
         loop {
             let current_node = cursor.node();
             match current_node.kind() {
+                "function_definition" | "class_definition" | "decorated_definition"
+                    if options.public_only && is_private_definition(current_node, source) =>
+                {
+                    trace!("Skipping private definition at line {}", current_node.start_position().row + 1);
+                },
                 "function_definition" => {
                     if let Some(name_node) = current_node.child_by_field_name("name") {
                         let name = crate::symbols::get_node_text(name_node, source);
@@ -236,7 +1172,7 @@ fn process_file_with_path_idx(
                         // Determine symbol type based on parent context
                         let symbol_type = if parent_context.is_empty() {
                             crate::symbols::SymbolType::Function
-                        } else if parent_context.last().map_or(false, |p| 
+                        } else if parent_context.last().is_some_and(|p| 
                             matches!(p.symbol_type, crate::symbols::SymbolType::Class)) {
                             crate::symbols::SymbolType::Method
                         } else {
@@ -246,10 +1182,15 @@ fn process_file_with_path_idx(
                         let context = crate::symbols::SymbolContext {
                             file_path_index,
                             line_number: current_node.start_position().row + 1,
+                            end_line_number: current_node.end_position().row + 1,
                             module: module.to_string(),
                             fully_qualified_module: fully_qualified_module.to_string(),
                             symbol_type: symbol_type.clone(),
                             parent_context: parent_context.to_vec(),
+                            name_start_byte: Some(name_node.start_byte()),
+                            name_end_byte: Some(name_node.end_byte()),
+                            signature: crate::symbols::definition_signature(current_node, source),
+                            ..Default::default()
                         };
                         
                         function_symbols.insert(crate::symbols::Symbol { name: name.clone(), context });
@@ -273,6 +1214,7 @@ fn process_file_with_path_idx(
                                 &new_context,
                                 function_symbols,
                                 class_symbols,
+                                options,
                             );
                         }
                     }
@@ -291,10 +1233,15 @@ fn process_file_with_path_idx(
                         let context = crate::symbols::SymbolContext {
                             file_path_index,
                             line_number: current_node.start_position().row + 1,
+                            end_line_number: current_node.end_position().row + 1,
                             module: module.to_string(),
                             fully_qualified_module: fully_qualified_module.to_string(),
                             symbol_type: symbol_type.clone(),
                             parent_context: parent_context.to_vec(),
+                            name_start_byte: Some(name_node.start_byte()),
+                            name_end_byte: Some(name_node.end_byte()),
+                            signature: crate::symbols::definition_signature(current_node, source),
+                            ..Default::default()
                         };
                         
                         class_symbols.insert(crate::symbols::Symbol { name: name.clone(), context });
@@ -317,12 +1264,17 @@ fn process_file_with_path_idx(
                                 &new_context,
                                 function_symbols,
                                 class_symbols,
+                                options,
                             );
                         }
                     }
                 },
                 "decorated_definition" => {
-                    // Find the definition that's being decorated (function or class)
+                    let decorators = decorator_names(current_node, source);
+                    
+                    // Find the definition that's being decorated (function or class). Stacked
+                    // decorators are sibling `decorator` children of this one node, not nested
+                    // decorated_definitions, so the definition is always a direct child.
                     for i in 0..current_node.child_count() {
                         if let Some(child) = current_node.child(i) {
                             match child.kind() {
@@ -338,21 +1290,29 @@ fn process_file_with_path_idx(
                                             // Determine symbol type based on parent context
                                             let symbol_type = if parent_context.is_empty() {
                                                 crate::symbols::SymbolType::Function
-                                            } else if parent_context.last().map_or(false, |p| 
+                                            } else if parent_context.last().is_some_and(|p| 
                                                 matches!(p.symbol_type, crate::symbols::SymbolType::Class)) {
                                                 crate::symbols::SymbolType::Method
                                             } else {
                                                 crate::symbols::SymbolType::NestedFunction
                                             };
                                             
-                                            let context = crate::symbols::SymbolContext {
+                                            let mut context = crate::symbols::SymbolContext {
                                                 file_path_index,
                                                 line_number: child.start_position().row + 1,
+                                                end_line_number: child.end_position().row + 1,
                                                 module: module.to_string(),
                                                 fully_qualified_module: fully_qualified_module.to_string(),
                                                 symbol_type: symbol_type.clone(),
                                                 parent_context: parent_context.to_vec(),
+                                                decorators: decorators.clone(),
+                                                name_start_byte: Some(name_node.start_byte()),
+                                                name_end_byte: Some(name_node.end_byte()),
+                                                signature: crate::symbols::definition_signature(child, source),
+                                                ..Default::default()
                                             };
+                                            context.is_abstract = symbol_type == crate::symbols::SymbolType::Method
+                                                && context.has_decorator("abstractmethod");
                                             
                                             function_symbols.insert(crate::symbols::Symbol { name: name.clone(), context });
                                             debug!("Found decorated function: {}", name);
@@ -375,6 +1335,7 @@ fn process_file_with_path_idx(
                                                     &new_context,
                                                     function_symbols,
                                                     class_symbols,
+                                                    options,
                                                 );
                                             }
                                         }
@@ -392,10 +1353,16 @@ fn process_file_with_path_idx(
                                             let context = crate::symbols::SymbolContext {
                                                 file_path_index,
                                                 line_number: child.start_position().row + 1,
+                                                end_line_number: child.end_position().row + 1,
                                                 module: module.to_string(),
                                                 fully_qualified_module: fully_qualified_module.to_string(),
                                                 symbol_type: symbol_type.clone(),
                                                 parent_context: parent_context.to_vec(),
+                                                decorators: decorators.clone(),
+                                                name_start_byte: Some(name_node.start_byte()),
+                                                name_end_byte: Some(name_node.end_byte()),
+                                                signature: crate::symbols::definition_signature(child, source),
+                                                ..Default::default()
                                             };
                                             
                                             class_symbols.insert(crate::symbols::Symbol { name: name.clone(), context });
@@ -419,6 +1386,7 @@ fn process_file_with_path_idx(
                                                     &new_context,
                                                     function_symbols,
                                                     class_symbols,
+                                                    options,
                                                 );
                                             }
                                         }
@@ -432,6 +1400,42 @@ fn process_file_with_path_idx(
                         }
                     }
                 },
+                // Definitions under `if`, `try`, `with`, `for`, `while` or `match` at module or
+                // class scope, e.g. fallbacks in `try: import x` / `except ImportError: def x()`.
+                // Each block is only entered from here, so nothing is emitted twice.
+                kind if is_compound_statement_part(kind)
+                    && parent_context.last().is_none_or(|p| matches!(
+                        p.symbol_type,
+                        crate::symbols::SymbolType::Class | crate::symbols::SymbolType::NestedClass
+                    )) =>
+                {
+                    collect_symbols_recursive(
+                        current_node,
+                        source,
+                        file_path_index,
+                        module,
+                        fully_qualified_module,
+                        parent_context,
+                        function_symbols,
+                        class_symbols,
+                        options,
+                    );
+                },
+                // Error recovery can wrap valid definitions in an ERROR node, e.g. around
+                // Cython `cdef` blocks, so look inside when asked to be lenient
+                "ERROR" if options.lenient => {
+                    collect_symbols_recursive(
+                        current_node,
+                        source,
+                        file_path_index,
+                        module,
+                        fully_qualified_module,
+                        parent_context,
+                        function_symbols,
+                        class_symbols,
+                        options,
+                    );
+                },
                 _ => {
                     trace!("Skipping node kind: {}", current_node.kind());
                 }
@@ -453,46 +1457,210 @@ fn process_file_with_path_idx(
         &[],
         &mut function_symbols,
         &mut class_symbols,
+        options,
     );
     
-    Ok((function_symbols, class_symbols))
-}
-
-fn parse_file_and_update_stats(
-    parser: &mut Parser,
-    path: &Path,
-    base_dir: &Path,
-    stats: &SymbolStats,
-) -> Result<()> {
-    // Get the path index from the registry
-    let path_idx = {
-        let path_registry = stats.path_registry.lock().unwrap();
-        path_registry.path_to_index.get(path).cloned()
-    };
+    if options.dynamic_class_aliases {
+        collect_dynamic_class_aliases(
+            tree.root_node(),
+            &source,
+            file_path_index,
+            &module,
+            &fully_qualified_module,
+            &mut class_symbols,
+        );
+    }
     
-    // Ensure the path index exists
-    let file_path_index = match path_idx {
-        Some(idx) => idx,
-        None => {
-            return Err(anyhow::anyhow!(
-                "Failed to find path index for {}, symbol collection aborted", 
-                path.display()
-            ));
-        }
-    };
+    if options.type_aliases {
+        collect_type_aliases(
+            tree.root_node(),
+            &source,
+            file_path_index,
+            &module,
+            &fully_qualified_module,
+            &mut class_symbols,
+        );
+    }
     
-    // Use the same process_file_with_path_idx function to ensure consistent behavior
-    let (function_symbols, class_symbols) = process_file_with_path_idx(
-        parser,
-        path,
-        base_dir,
-        Some(file_path_index)
-    )?;
+    if options.capture_snippet {
+        let with_snippet = |mut symbol: Symbol| {
+            symbol.context.snippet = Some(symbol_snippet(&source, &symbol));
+            symbol
+        };
+        function_symbols = function_symbols.into_iter().map(with_snippet).collect();
+        class_symbols = class_symbols.into_iter().map(with_snippet).collect();
+    }
     
-    // Update stats with found symbols
-    if !function_symbols.is_empty() {
-        let mut func_set = stats.functions.lock().unwrap();
-        for func in function_symbols {
+    if let Some(max) = options.max_parents {
+        let with_max_parents = |mut symbol: Symbol| {
+            symbol.context.truncate_parents(max);
+            symbol
+        };
+        function_symbols = function_symbols.into_iter().map(with_max_parents).collect();
+        class_symbols = class_symbols.into_iter().map(with_max_parents).collect();
+    }
+    
+    if options.skip_methods {
+        function_symbols.retain(|symbol| {
+            !matches!(symbol.context.symbol_type, crate::symbols::SymbolType::Method | crate::symbols::SymbolType::NestedFunction)
+        });
+    }
+    
+    if let Some(patterns) = &options.tests_only {
+        let relative_path = path.strip_prefix(base_dir).unwrap_or(path);
+        function_symbols.retain(|symbol| patterns.is_test(symbol, relative_path));
+        class_symbols.retain(|symbol| patterns.is_test(symbol, relative_path));
+    }
+    
+    if let Some(filter) = &options.filter {
+        function_symbols = function_symbols.into_iter().filter_map(|symbol| filter.apply(symbol)).collect();
+        class_symbols = class_symbols.into_iter().filter_map(|symbol| filter.apply(symbol)).collect();
+    }
+    
+    // Only locate the error when asked; has_error() itself is O(1)
+    let syntax_error_line = if find_syntax_error && tree.root_node().has_error() {
+        Some(first_error_line(tree.root_node()))
+    } else {
+        None
+    };
+    
+    let has_main = options.detect_main && has_main_guard(tree.root_node(), &source);
+    
+    Ok(ProcessedFile {
+        functions: function_symbols,
+        classes: class_symbols,
+        syntax_error_line,
+        has_main,
+        generated: false,
+    })
+}
+
+/// Whether `root` has a module-level `if __name__ == "__main__":` (either operand order or
+/// quote style). Guards nested in other blocks don't make a file runnable, so only direct
+/// children are checked.
+pub fn has_main_guard(root: tree_sitter::Node, source: &str) -> bool {
+    let mut cursor = root.walk();
+    let has_guard = root.children(&mut cursor)
+        .filter(|child| child.kind() == "if_statement")
+        .filter_map(|statement| statement.child_by_field_name("condition"))
+        .any(|condition| is_main_comparison(condition, source));
+    has_guard
+}
+
+fn is_main_comparison(node: tree_sitter::Node, source: &str) -> bool {
+    if node.kind() != "comparison_operator" || node.named_child_count() != 2 {
+        return false;
+    }
+    let operator_is_eq = (0..node.child_count())
+        .filter_map(|i| node.child(i))
+        .any(|child| !child.is_named() && child.kind() == "==");
+    let operands: Vec<String> = (0..2)
+        .filter_map(|i| node.named_child(i))
+        .map(|operand| crate::symbols::get_node_text(operand, source))
+        .collect();
+    let is_main_string = |text: &str| text == "\"__main__\"" || text == "'__main__'";
+    operator_is_eq && match operands.as_slice() {
+        [left, right] => (left == "__name__" && is_main_string(right))
+            || (right == "__name__" && is_main_string(left)),
+        _ => false,
+    }
+}
+
+/// 1-based line of the first ERROR or MISSING node under `node`
+fn first_error_line(node: tree_sitter::Node) -> usize {
+    let mut current = node;
+    'descend: loop {
+        let mut cursor = current.walk();
+        for child in current.children(&mut cursor) {
+            if child.is_error() || child.is_missing() {
+                return child.start_position().row + 1;
+            }
+            if child.has_error() {
+                current = child;
+                continue 'descend;
+            }
+        }
+        return current.start_position().row + 1;
+    }
+}
+
+fn syntax_error_issue(path: &Path, line: usize) -> ParseIssue {
+    ParseIssue {
+        path: path.to_path_buf(),
+        line: Some(line),
+        message: "Syntax error; symbols after this point may be missing".to_string(),
+    }
+}
+
+fn error_issue(path: &Path, error: &anyhow::Error) -> ParseIssue {
+    ParseIssue {
+        path: path.to_path_buf(),
+        line: None,
+        message: format!("{:#}", error),
+    }
+}
+
+/// Collect decorator names from a decorated_definition node, dropping the `@` and any call arguments
+fn decorator_names(node: tree_sitter::Node, source: &str) -> Vec<String> {
+    let mut decorators = Vec::new();
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        if child.kind() == "decorator" {
+            let text = crate::symbols::get_node_text(child, source);
+            let name = text.trim_start_matches('@')
+                .split('(')
+                .next()
+                .unwrap_or("")
+                .trim();
+            if !name.is_empty() {
+                decorators.push(name.to_string());
+            }
+        }
+    }
+    decorators
+}
+
+fn parse_file_and_update_stats(
+    parser: &mut Parser,
+    path: &Path,
+    base_dir: &Path,
+    stats: &SymbolStats,
+) -> Result<Option<usize>> {
+    // Get the path index from the registry
+    let path_idx = {
+        let path_registry = stats.path_registry.lock().unwrap();
+        path_registry.index_of(path)
+    };
+    
+    // Ensure the path index exists
+    let file_path_index = match path_idx {
+        Some(idx) => idx,
+        None => {
+            return Err(anyhow::anyhow!(
+                "Failed to find path index for {}, symbol collection aborted", 
+                path.display()
+            ));
+        }
+    };
+    
+    // Use the same process_file_with_path_idx function to ensure consistent behavior
+    let ProcessedFile { functions: function_symbols, classes: class_symbols, syntax_error_line, .. } = process_file_with_path_idx(
+        parser,
+        path,
+        base_dir,
+        Some(file_path_index),
+        stats.parse_issues.is_some(),
+        &CollectOptions::default(),
+    )?;
+    
+    if function_symbols.is_empty() && class_symbols.is_empty() {
+        stats.record_empty_files(vec![path.to_path_buf()]);
+    }
+    
+    // Update stats with found symbols
+    if !function_symbols.is_empty() {
+        let mut func_set = stats.functions.lock().unwrap();
+        for func in function_symbols {
             func_set.insert(func);
         }
     }
@@ -504,7 +1672,7 @@ fn parse_file_and_update_stats(
         }
     }
     
-    Ok(())
+    Ok(syntax_error_line)
 }
 
 #[cfg(test)]
@@ -513,8 +1681,8 @@ mod tests {
     use std::fs::{self, File, create_dir_all};
     use std::io::Write;
     use tempfile::tempdir;
-    use crate::symbols::{PathRegistry, SymbolStats, parse_python_file};
-    use crate::search::search_symbols;
+    use crate::symbols::{PathRegistry, Stats, SymbolStats, SymbolType, parse_python_file};
+    use crate::search::{search_symbols, SearchAlgorithm};
 
     fn create_test_python_file(path: &Path, content: &str) -> Result<()> {
         if let Some(parent) = path.parent() {
@@ -614,6 +1782,55 @@ class TestClass:
         Ok(())
     }
 
+    #[test]
+    fn test_sequential_and_parallel_parsing_agree() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let mut files = Vec::new();
+        for i in 0..25 {
+            let file_path = base_dir.join(format!("pkg{}", i % 3)).join(format!("mod{}.py", i));
+            create_test_python_file(&file_path, &format!("def f{i}():\n    def inner():\n        pass\n\nclass C{i}:\n    def method(self):\n        pass\n"))?;
+            files.push(file_path);
+        }
+        let broken = base_dir.join("broken.py");
+        create_test_python_file(&broken, "def broken(:\n")?;
+        files.push(broken);
+        files.push(base_dir.join("missing.py"));
+
+        let sequential = SymbolStats::new();
+        parse_python_files_sequential(&files, base_dir, &sequential)?;
+        let parallel = SymbolStats::new();
+        parse_files_in_chunks(&files, base_dir, &parallel, &CollectOptions::default(), false)?;
+
+        assert_eq!(*sequential.functions.lock().unwrap(), *parallel.functions.lock().unwrap());
+        assert_eq!(*sequential.classes.lock().unwrap(), *parallel.classes.lock().unwrap());
+        assert_eq!(sequential.path_registry.lock().unwrap().paths, parallel.path_registry.lock().unwrap().paths);
+        assert_eq!(sequential.get_counts(), parallel.get_counts());
+        assert_eq!(sequential.snapshot().files_scanned, parallel.snapshot().files_scanned);
+        assert_eq!(sequential.get_counts().3, 1, "The missing file is an I/O error either way");
+
+        // With the toggle set, chunks run on the calling thread and still honour the options
+        let empty = base_dir.join("empty.py");
+        create_test_python_file(&empty, "# nothing here\n")?;
+        files.push(empty.clone());
+        let options = CollectOptions { skip_methods: true, ..Default::default() };
+        let in_pool = SymbolStats::new();
+        parse_files_in_chunks(&files, base_dir, &in_pool, &options, false)?;
+        let on_caller = SymbolStats::new();
+        parse_files_in_chunks(&files, base_dir, &on_caller, &options, true)?;
+        assert_eq!(*in_pool.functions.lock().unwrap(), *on_caller.functions.lock().unwrap());
+        assert!(on_caller.functions.lock().unwrap().iter().all(|symbol| symbol.name != "method"));
+        assert_eq!(on_caller.empty_files(), vec![empty]);
+        assert_eq!(in_pool.get_counts(), on_caller.get_counts());
+
+        assert!(is_sequential_toggle(Some("1".as_ref())));
+        assert!(is_sequential_toggle(Some("true".as_ref())));
+        assert!(!is_sequential_toggle(Some("0".as_ref())));
+        assert!(!is_sequential_toggle(Some("".as_ref())));
+        assert!(!is_sequential_toggle(None));
+        Ok(())
+    }
+
     #[test]
     fn test_parse_python_files_parallel() -> Result<()> {
         let temp_dir = tempdir()?;
@@ -661,10 +1878,305 @@ class TestClass:
         assert_eq!(syntax_errors, 0);
         assert_eq!(io_errors, 0);
         assert_eq!(other_errors, 0);
+        // Busy time is summed across the threads that parsed
+        assert!(stats.parse_busy_time() > std::time::Duration::ZERO);
         
         Ok(())
     }
 
+    #[test]
+    fn test_count_symbols_parallel() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file1 = base_dir.join("a.py");
+        let file2 = base_dir.join("pkg/b.py");
+        create_test_python_file(&file1, r#"
+def top():
+    def inner():
+        pass
+
+@decorator
+class Thing:
+    def method(self):
+        pass
+"#)?;
+        create_test_python_file(&file2, r#"
+if TYPE_CHECKING:
+    class Guarded:
+        pass
+
+def other(): pass
+"#)?;
+
+        let files = vec![file1, file2, base_dir.join("missing.py")];
+        let (functions, classes) = count_symbols_parallel(&files);
+        assert_eq!(functions, 4, "top, inner, method and other");
+        assert_eq!(classes, 2, "Thing and Guarded");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_file_counts_as_io_error() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let existing = base_dir.join("exists.py");
+        create_test_python_file(&existing, "def present(): pass")?;
+
+        let files = vec![existing, base_dir.join("does_not_exist.py")];
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+
+        let (func_count, _, syntax_errors, io_errors, other_errors) = stats.get_counts();
+        assert_eq!(func_count, 1, "Should still index the existing file");
+        assert_eq!(io_errors, 1, "Missing file should count as an I/O error");
+        assert_eq!(syntax_errors, 0);
+        assert_eq!(other_errors, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_with_retry() {
+        use std::io::{Error, ErrorKind};
+
+        // Fails once with a transient error, then succeeds
+        let mut calls = 0;
+        let result = read_with_retry(|| {
+            calls += 1;
+            if calls == 1 {
+                Err(Error::new(ErrorKind::Interrupted, "interrupted"))
+            } else {
+                Ok("def f(): pass".to_string())
+            }
+        }, 2, Duration::ZERO);
+        assert_eq!(result.unwrap(), "def f(): pass");
+        assert_eq!(calls, 2);
+
+        // Missing files are not retried
+        let mut calls = 0;
+        let result = read_with_retry(|| {
+            calls += 1;
+            Err(Error::new(ErrorKind::NotFound, "missing"))
+        }, 2, Duration::ZERO);
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::NotFound);
+        assert_eq!(calls, 1);
+
+        // Retries are bounded
+        let mut calls = 0;
+        let result = read_with_retry(|| {
+            calls += 1;
+            Err(Error::new(ErrorKind::TimedOut, "timed out"))
+        }, 2, Duration::ZERO);
+        assert!(result.is_err());
+        assert_eq!(calls, 3);
+
+        // Only errors known to be transient are retried
+        assert!(!is_transient_io_error(&Error::other("disk on fire")));
+        assert!(!is_transient_io_error(&Error::new(ErrorKind::PermissionDenied, "forbidden")));
+        if let Some(estale) = ESTALE {
+            assert!(is_transient_io_error(&Error::from_raw_os_error(estale)));
+        }
+
+        // The wait doubles, up to a cap
+        let backoff = Duration::from_millis(20);
+        assert_eq!(retry_delay(backoff, 0), backoff);
+        assert_eq!(retry_delay(backoff, 1), backoff * 2);
+        assert_eq!(retry_delay(backoff, 5), Duration::from_millis(640));
+        assert_eq!(retry_delay(backoff, u32::MAX), Duration::from_millis(640));
+    }
+
+    #[test]
+    fn test_stats_snapshot_counts_scanned_files() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let existing = base_dir.join("exists.py");
+        create_test_python_file(&existing, "def present(): pass\n\nclass Here: pass\n")?;
+        let files = vec![existing, base_dir.join("does_not_exist.py")];
+
+        let expected = Stats {
+            functions: 1,
+            classes: 1,
+            syntax_errors: 0,
+            io_errors: 1,
+            other_errors: 0,
+            files_scanned: 2,
+            empty_files: 0,
+            generated_files: 0,
+            parse_timeouts: 0,
+            pruned_symbols: 0,
+        };
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert_eq!(stats.snapshot(), expected);
+
+        let stats = SymbolStats::new();
+        parse_python_files_sequential(&files, base_dir, &stats)?;
+        assert_eq!(stats.snapshot(), expected);
+
+        let json = serde_json::to_value(stats.snapshot())?;
+        assert_eq!(json["files_scanned"], 2);
+        assert_eq!(json["io_errors"], 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_files_without_symbols_are_recorded() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let imports = base_dir.join("imports.py");
+        let init = base_dir.join("pkg").join("__init__.py");
+        let code = base_dir.join("code.py");
+        create_test_python_file(&imports, "import os\nfrom typing import List\n")?;
+        create_test_python_file(&init, "")?;
+        create_test_python_file(&code, "def present(): pass\n")?;
+        // Unreadable files are errors, not empty files
+        let files = vec![code, imports.clone(), init.clone(), base_dir.join("missing.py")];
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert_eq!(stats.empty_files(), vec![imports.clone(), init.clone()]);
+        assert_eq!(stats.snapshot().empty_files, 2);
+
+        let stats = SymbolStats::new();
+        parse_python_files_sequential(&files, base_dir, &stats)?;
+        assert_eq!(stats.empty_files(), vec![imports, init]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_generated_files_are_skipped() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let generated = base_dir.join("api_pb2.py");
+        let handwritten = base_dir.join("client.py");
+        let late_marker = base_dir.join("late.py");
+        create_test_python_file(&generated, "# -*- coding: utf-8 -*-\n# @generated by protoc\nclass Request: pass\n")?;
+        // Only comments near the top count, not strings or later lines
+        create_test_python_file(&handwritten, "MARKER = \"@generated\"\ndef send(): pass\n")?;
+        create_test_python_file(&late_marker, concat!("\n\n\n\n\n", "# @generated\ndef late(): pass\n"))?;
+        let files = vec![generated, handwritten, late_marker];
+
+        let options = CollectOptions {
+            generated_markers: DEFAULT_GENERATED_MARKERS.iter().map(|m| m.to_string()).collect(),
+            ..Default::default()
+        };
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let mut names: Vec<String> = stats.functions.lock().unwrap().iter()
+            .chain(stats.classes.lock().unwrap().iter())
+            .map(|s| s.name.clone())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["late", "send"]);
+        let counts = stats.snapshot();
+        assert_eq!((counts.generated_files, counts.empty_files, counts.files_scanned), (1, 0, 3));
+
+        // Off by default
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert_eq!(stats.classes.lock().unwrap().len(), 1);
+        assert_eq!(stats.snapshot().generated_files, 0);
+
+        assert!(is_generated_source("# Code generated by mockgen. DO NOT EDIT.\n", &options.generated_markers));
+        assert!(is_generated_source("#!/usr/bin/env python\n# autogen: yes\n", &["autogen".to_string()]));
+        Ok(())
+    }
+
+    #[test]
+    fn test_main_guard_marks_entry_points() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let script = base_dir.join("script.py");
+        let reversed = base_dir.join("reversed.py");
+        let library = base_dir.join("library.py");
+        create_test_python_file(&script, "def main(): pass\n\nif __name__ == \"__main__\":\n    main()\n")?;
+        create_test_python_file(&reversed, "if '__main__' == __name__:\n    print('hi')\n")?;
+        // Not module level, and not a comparison with "__main__"
+        create_test_python_file(&library, concat!(
+            "def run():\n    if __name__ == \"__main__\":\n        pass\n",
+            "if __name__ != \"__main__\":\n    pass\n",
+        ))?;
+        let files = vec![script.clone(), reversed.clone(), library];
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert!(stats.entry_points().is_empty(), "detection is opt-in");
+
+        let options = CollectOptions { detect_main: true, ..Default::default() };
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        assert_eq!(stats.entry_points(), vec![reversed.clone(), script.clone()]);
+
+        let index = base_dir.join("symbols.bin");
+        crate::symbols::save_symbols(&index, &stats)?;
+        let data = crate::symbols::load_symbol_data(&index)?;
+        let mut entry_points = data.entry_points();
+        entry_points.sort();
+        assert_eq!(entry_points, vec![reversed.as_path(), script.as_path()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_python_files_parallel_with_threads() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let files: Vec<PathBuf> = (0..5)
+            .map(|i| {
+                let path = base_dir.join(format!("file{}.py", i));
+                create_test_python_file(&path, &format!("def func_{}(): pass\nclass Class_{}: pass", i, i)).unwrap();
+                path
+            })
+            .collect();
+
+        for num_threads in [Some(1), Some(2), None] {
+            let stats = SymbolStats::new();
+            parse_python_files_parallel_with_threads(&files, base_dir, &stats, num_threads)?;
+            let (func_count, class_count, _, _, _) = stats.get_counts();
+            assert_eq!(func_count, 5, "threads={:?}", num_threads);
+            assert_eq!(class_count, 5, "threads={:?}", num_threads);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_issues_collection() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let good = base_dir.join("good.py");
+        let broken = base_dir.join("broken.py");
+        let missing = base_dir.join("missing.py");
+        create_test_python_file(&good, "def fine():\n    pass\n")?;
+        create_test_python_file(&broken, "def ok():\n    pass\n\ndef broken(:\n    pass\n")?;
+        let files = vec![good, broken.clone(), missing.clone()];
+
+        // Disabled by default
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert!(stats.parse_issues().is_empty());
+
+        for sequential in [false, true] {
+            let stats = SymbolStats::with_parse_issues();
+            if sequential {
+                parse_python_files_sequential(&files, base_dir, &stats)?;
+            } else {
+                parse_python_files_parallel(&files, base_dir, &stats)?;
+            }
+
+            let issues = stats.parse_issues();
+            assert_eq!(issues.len(), 2, "sequential={}: {:?}", sequential, issues);
+            assert_eq!(issues[0].path, broken);
+            assert_eq!(issues[0].line, Some(4));
+            assert_eq!(issues[1].path, missing);
+            assert_eq!(issues[1].line, None);
+            assert!(issues[1].message.contains("Failed to read"));
+        }
+
+        Ok(())
+    }
+
     #[test]
     fn test_parse_invalid_python() -> Result<()> {
         let temp_dir = tempdir()?;
@@ -814,7 +2326,7 @@ def function_at_line_25():
         
         // Now test the search functionality to ensure path indices are preserved
         // Search for function_at_line_5
-        let results = search_symbols("function_at_line_5", &functions, &classes, &path_registry, false);
+        let (results, _) = search_symbols("function_at_line_5", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
         
         // Print search results for debugging
         println!("Search results for 'function_at_line_5':");
@@ -847,7 +2359,7 @@ def function_at_line_25():
                 "File path should be preserved in search results");
         
         // Search for ClassAtLine11
-        let results = search_symbols("ClassAtLine11", &functions, &classes, &path_registry, false);
+        let (results, _) = search_symbols("ClassAtLine11", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
         assert_eq!(results.len(), 1, "Should find exactly one match for ClassAtLine11");
         
         let (symbol, _) = &results[0];
@@ -984,7 +2496,7 @@ class Class2_line9:
         
         // Test search works correctly for both files
         for &(name, expected_line, file_idx) in expected_functions.iter().chain(expected_classes.iter()) {
-            let results = search_symbols(name, &functions, &classes, &path_registry, false);
+            let (results, _) = search_symbols(name, &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
             // Results should be unique - make sure we find exactly one match
             assert_eq!(results.len(), 1, "Should find exactly one match for {}, found {}", name, results.len());
             
@@ -1053,7 +2565,7 @@ def function_two():
             let functions = stats.functions.lock().unwrap();
             let classes = stats.classes.lock().unwrap();
             
-            let results = crate::search::search_symbols("function_one", &functions, &classes, &path_registry, false);
+            let (results, _) = crate::search::search_symbols("function_one", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
             assert_eq!(results.len(), 1, "Should find function_one");
             
             // Verify the path index is correct
@@ -1190,7 +2702,7 @@ def file3_func():
         assert_eq!(retrieved_path, &file2, "File2Class should have correct path");
         
         // Test search functionality to ensure correct path resolution
-        let results = crate::search::search_symbols("file1_func", &functions, &classes, &path_registry, false);
+        let (results, _) = crate::search::search_symbols("file1_func", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
         assert_eq!(results.len(), 1, "Should find exactly one match for file1_func");
         let (symbol, _) = &results[0];
         let path_idx = symbol.context.file_path_index;
@@ -1291,32 +2803,32 @@ class MultipleDecoratedClass:
         // function_with_decorated_args, multiple_decorated_function + any internal functions
         
         // Test for presence of decorated function
-        let decorated_function = functions.iter()
+        let _decorated_function = functions.iter()
             .find(|f| f.name == "decorated_function")
             .expect("Should find decorated_function");
         
         // Test for presence of function with decorated args
-        let function_with_args = functions.iter()
+        let _function_with_args = functions.iter()
             .find(|f| f.name == "function_with_decorated_args")
             .expect("Should find function_with_decorated_args");
             
         // Test for presence of multiply decorated function
-        let multiple_decorated = functions.iter()
+        let _multiple_decorated = functions.iter()
             .find(|f| f.name == "multiple_decorated_function")
             .expect("Should find multiple_decorated_function");
         
         // Test for presence of decorated class
-        let decorated_class = classes.iter()
+        let _decorated_class = classes.iter()
             .find(|c| c.name == "DecoratedClass")
             .expect("Should find DecoratedClass");
             
         // Test for presence of multiply decorated class
-        let multiple_decorated_class = classes.iter()
+        let _multiple_decorated_class = classes.iter()
             .find(|c| c.name == "MultipleDecoratedClass")
             .expect("Should find MultipleDecoratedClass");
             
         // Test for decorated method inside a decorated class
-        let decorated_method = functions.iter()
+        let _decorated_method = functions.iter()
             .find(|f| f.name == "decorated_method" && !f.context.parent_context.is_empty() && 
                  f.context.parent_context.iter().any(|p| p.name == "MultipleDecoratedClass"))
             .expect("Should find decorated_method inside MultipleDecoratedClass");
@@ -1335,6 +2847,123 @@ class MultipleDecoratedClass:
         Ok(())
     }
 
+    #[test]
+    fn test_three_stacked_decorators_indexed_once() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("stacked.py");
+        create_test_python_file(&file_path, r#"import functools
+
+@first
+@second.attr(1)
+@third
+def triple(x):
+    return x
+
+class Service:
+    @staticmethod
+    @functools.lru_cache(maxsize=None)
+    @traced
+    def lookup(key):
+        return key
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], base_dir, &stats)?;
+        let functions = stats.functions.lock().unwrap();
+
+        let triples: Vec<&Symbol> = functions.iter().filter(|f| f.name == "triple").collect();
+        assert_eq!(triples.len(), 1, "Stacked decorators should not duplicate or hide the function");
+        assert_eq!(triples[0].context.line_number, 6, "The line is the def, not the first decorator");
+        assert_eq!(triples[0].context.decorators, vec!["first", "second.attr", "third"]);
+
+        let lookups: Vec<&Symbol> = functions.iter().filter(|f| f.name == "lookup").collect();
+        assert_eq!(lookups.len(), 1);
+        assert_eq!(lookups[0].context.line_number, 13);
+        assert_eq!(lookups[0].context.symbol_type, SymbolType::Method);
+        assert_eq!(lookups[0].context.decorators, vec!["staticmethod", "functools.lru_cache", "traced"]);
+        assert_eq!(functions.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_decorator_names_captured() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("decorators.py");
+        create_test_python_file(&file_path, r#"
+import typing_extensions
+
+@typing_extensions.deprecated("use new_api")
+def old_api():
+    pass
+
+class Service:
+    @staticmethod
+    @functools.lru_cache(maxsize=None)
+    def cached():
+        pass
+
+def plain():
+    pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_sequential(&[file_path], base_dir, &stats)?;
+        let functions = stats.functions.lock().unwrap();
+
+        let old_api = functions.iter().find(|f| f.name == "old_api").expect("Should find old_api");
+        assert_eq!(old_api.context.decorators, vec!["typing_extensions.deprecated".to_string()]);
+        assert!(old_api.context.has_decorator("deprecated"));
+
+        let cached = functions.iter().find(|f| f.name == "cached").expect("Should find cached");
+        assert_eq!(cached.context.decorators, vec!["staticmethod".to_string(), "functools.lru_cache".to_string()]);
+        assert!(!cached.context.has_decorator("deprecated"));
+
+        let plain = functions.iter().find(|f| f.name == "plain").expect("Should find plain");
+        assert!(plain.context.decorators.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abstract_methods_tagged() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("shapes.py");
+        create_test_python_file(&file_path, r#"
+import abc
+from abc import ABC, abstractmethod
+
+class Shape(ABC):
+    @abstractmethod
+    def area(self):
+        ...
+
+    @property
+    @abc.abstractmethod
+    def name(self):
+        ...
+
+    def describe(self):
+        return f"{self.name}: {self.area()}"
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_sequential(&[file_path], base_dir, &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let find = |name: &str| functions.iter().find(|f| f.name == name).unwrap_or_else(|| panic!("Should find {}", name));
+
+        assert!(find("area").context.is_abstract);
+        assert!(find("name").context.is_abstract, "Qualified and stacked decorators should count");
+        assert!(!find("describe").context.is_abstract);
+
+        let classes = stats.classes.lock().unwrap();
+        assert!(classes.iter().all(|c| !c.context.is_abstract));
+
+        Ok(())
+    }
+
     // Test that verifies complex nested decorators and classes
     #[test]
     fn test_complex_decorated_structures() -> Result<()> {
@@ -1446,39 +3075,40 @@ DynamicClass = returns_class_decorator("dynamic")
         }
         
         // Complex class should be found
-        let complex_class = classes.iter()
+        let _complex_class = classes.iter()
             .find(|c| c.name == "ComplexClass")
             .expect("Should find ComplexClass");
             
         // Nested class should be found
-        let nested_class = classes.iter()
+        let _nested_class = classes.iter()
             .find(|c| c.name == "NestedClass" && !c.context.parent_context.is_empty() &&
                  c.context.parent_context.iter().any(|p| p.name == "ComplexClass"))
             .expect("Should find NestedClass inside ComplexClass");
             
         // Complex decorated function should be found
-        let complex_function = functions.iter()
+        let _complex_function = functions.iter()
             .find(|f| f.name == "complex_decorated_function")
             .expect("Should find complex_decorated_function");
             
         // Decorated methods should be found
-        let simple_decorated_method = functions.iter()
+        let _simple_decorated_method = functions.iter()
             .find(|f| f.name == "simple_decorated_method" && !f.context.parent_context.is_empty() &&
                  f.context.parent_context.iter().any(|p| p.name == "ComplexClass"))
             .expect("Should find simple_decorated_method");
             
-        let double_decorated_method = functions.iter()
+        let _double_decorated_method = functions.iter()
             .find(|f| f.name == "double_decorated_method" && !f.context.parent_context.is_empty() &&
                  f.context.parent_context.iter().any(|p| p.name == "ComplexClass"))
             .expect("Should find double_decorated_method");
             
         // Generated class should be found
-        let generated_class = classes.iter()
+        let _generated_class = classes.iter()
             .find(|c| c.name == "GeneratedClass")
             .expect("Should find GeneratedClass");
             
-        // Dynamic class should be found (this is a variable assignment, not a class definition,
-        // so it won't be found as a class unless we specifically handle this case)
+        // DynamicClass is a variable assignment, not a class definition, so it is only
+        // indexed with CollectOptions::dynamic_class_aliases (see test_dynamic_class_aliases)
+        assert!(!classes.iter().any(|c| c.name == "DynamicClass"));
         
         // Verify we have the right counts
         let (func_count, class_count, syntax_errors, io_errors, other_errors) = stats.get_counts();
@@ -1493,4 +3123,818 @@ DynamicClass = returns_class_decorator("dynamic")
         
         Ok(())
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn test_dynamic_class_aliases() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("dynamic.py");
+        create_test_python_file(&file_path, r#"from collections import namedtuple
+from typing import NamedTuple
+
+DynamicClass = returns_class_decorator("dynamic")
+Point = namedtuple("Point", "x y")
+Pair = NamedTuple("Pair", [("a", int), ("b", int)])
+Model = registry.make_model(name="model")
+instance = make_thing()
+DEFAULT_TIMEOUT = compute_timeout()
+Settings = Config()
+
+def factory():
+    Inner = make_class()
+    return Inner
+"#)?;
+        let files = vec![file_path];
+
+        // Off by default
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert!(stats.classes.lock().unwrap().is_empty());
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { dynamic_class_aliases: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let classes = stats.classes.lock().unwrap();
+
+        let mut names: Vec<&str> = classes.iter().map(|c| c.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["DynamicClass", "Model", "Pair", "Point"]);
+
+        let dynamic = classes.iter().find(|c| c.name == "DynamicClass").unwrap();
+        assert_eq!(dynamic.context.line_number, 4);
+        assert_eq!(dynamic.context.symbol_type, SymbolType::Class);
+        assert!(dynamic.context.parent_context.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_type_alias_statements() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("aliases.py");
+        create_test_python_file(&file_path, r#"type Vector = list[float]
+type Pair[T] = tuple[T, T]
+type = "a variable, not a statement"
+
+def scale(v: Vector) -> Vector:
+    return v
+"#)?;
+        let files = vec![file_path];
+
+        // Off by default
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert!(stats.classes.lock().unwrap().is_empty());
+        assert_eq!(stats.functions.lock().unwrap().len(), 1);
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { type_aliases: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let classes = stats.classes.lock().unwrap();
+
+        let mut names: Vec<&str> = classes.iter().map(|c| c.name.as_str()).collect();
+        names.sort();
+        assert_eq!(names, vec!["Pair", "Vector"]);
+
+        let vector = classes.iter().find(|c| c.name == "Vector").unwrap();
+        assert_eq!(vector.context.line_number, 1);
+        assert_eq!(vector.context.symbol_type, SymbolType::TypeAlias);
+        assert_eq!(vector.context.name_start_byte, Some(5));
+        assert_eq!(crate::lsp::symbol_kind(&vector.context.symbol_type), lsp_types::SymbolKind::CLASS);
+        assert_eq!(stats.functions.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_collect_from_single_file_path() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let package = temp_dir.path().join("pkg");
+        std::fs::create_dir_all(&package)?;
+        let file_path = package.join("single.py");
+        create_test_python_file(&file_path, "def only():\n    pass\n\nclass Lone:\n    pass\n")?;
+        create_test_python_file(&package.join("sibling.py"), "def ignored():\n    pass\n")?;
+
+        let files: Vec<PathBuf> = crate::files::list_python_files(&file_path, false).collect();
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, crate::files::scan_base_dir(&file_path), &stats)?;
+
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        assert_eq!(functions.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), vec!["only"]);
+        assert_eq!(classes.iter().map(|c| c.name.as_str()).collect::<Vec<_>>(), vec!["Lone"]);
+        let only = functions.iter().next().unwrap();
+        assert_eq!(only.context.module, "single");
+        assert_eq!(only.context.fully_qualified_module, "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_name_byte_ranges_slice_to_names() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("ranges.py");
+        let source = "import functools\n\n@functools.cache\ndef cached(x):\n    return x\n\nclass Café:\n    def método(self):\n        def inner():\n            pass\n\n@dataclass\nclass Point:\n    x: int\n\nAlias = make_class()\n";
+        create_test_python_file(&file_path, source)?;
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { dynamic_class_aliases: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&[file_path], base_dir, &stats, &options)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        assert_eq!(functions.len() + classes.len(), 6);
+
+        for symbol in functions.iter().chain(classes.iter()) {
+            let start = symbol.context.name_start_byte.expect("parsed symbols have a start byte");
+            let end = symbol.context.name_end_byte.expect("parsed symbols have an end byte");
+            assert_eq!(&source[start..end], symbol.name);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_capture_snippet() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("snippets.py");
+        let source = "import os\n\ndef short(a, b):\n    return a + b\n\nclass Long:\n    x = 1\n    y = 2\n    z = 3\n\n    def method(self):\n        pass\n";
+        create_test_python_file(&file_path, source)?;
+        let files = vec![file_path];
+
+        // Off by default
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert!(stats.functions.lock().unwrap().iter().all(|f| f.context.snippet.is_none()));
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { capture_snippet: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        let short = functions.iter().find(|f| f.name == "short").unwrap();
+        assert_eq!(short.context.snippet.as_deref(), Some("def short(a, b):\n    return a + b"));
+        let long = classes.iter().find(|c| c.name == "Long").unwrap();
+        assert_eq!(long.context.snippet.as_deref(), Some("class Long:\n    x = 1\n    y = 2\n    z = 3\n"));
+        let method = functions.iter().find(|f| f.name == "method").unwrap();
+        assert_eq!(method.context.snippet.as_deref(), Some("    def method(self):\n        pass"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_parents_caps_parent_context() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("nested.py");
+        let source = "class A:\n    class B:\n        class C:\n            def deep(self):\n                pass\n\ndef top():\n    pass\n";
+        create_test_python_file(&file_path, source)?;
+        let files = vec![file_path];
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { max_parents: Some(2), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        // Only the nearest parents are kept, innermost last as usual
+        let deep = functions.iter().find(|f| f.name == "deep").unwrap();
+        let parents: Vec<_> = deep.context.parent_context.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(parents, vec!["B", "C"]);
+        assert!(deep.context.parents_truncated);
+
+        // Symbols within the cap are untouched
+        let c = classes.iter().find(|c| c.name == "C").unwrap();
+        assert_eq!(c.context.parent_context.len(), 2);
+        assert!(!c.context.parents_truncated);
+        let top = functions.iter().find(|f| f.name == "top").unwrap();
+        assert!(top.context.parent_context.is_empty());
+        assert!(!top.context.parents_truncated);
+        Ok(())
+    }
+
+    #[test]
+    fn test_cython_def_and_class_are_indexed() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        create_test_python_file(&base_dir.join("plain.py"), "def plain():\n    pass\n")?;
+        create_test_python_file(&base_dir.join("fast.pyx"), r#"cimport numpy as np
+
+cdef int counter = 0
+
+class Matrix:
+    def transpose(self):
+        pass
+
+def make_matrix(rows, cols):
+    return Matrix()
+
+cdef double scale(double x):
+    return x * 2
+"#)?;
+
+        // Cython files are only discovered on request
+        let files: Vec<PathBuf> = crate::files::list_source_files(base_dir, false, false).collect();
+        assert_eq!(files, vec![base_dir.join("plain.py")]);
+        let mut files: Vec<PathBuf> = crate::files::list_source_files(base_dir, false, true).collect();
+        files.sort();
+        assert_eq!(files, vec![base_dir.join("fast.pyx"), base_dir.join("plain.py")]);
+
+        // The trailing cdef function turns the whole module into an ERROR node
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert!(stats.classes.lock().unwrap().is_empty());
+
+        // Leniently, the def and class definitions around the cdef declarations are found
+        let stats = SymbolStats::new();
+        let options = CollectOptions { lenient: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        let matrix = classes.iter().find(|c| c.name == "Matrix").expect("class in .pyx");
+        assert_eq!(matrix.context.line_number, 5);
+        assert_eq!(matrix.context.module, "fast");
+        let make_matrix = functions.iter().find(|f| f.name == "make_matrix").expect("def in .pyx");
+        assert_eq!(make_matrix.context.line_number, 9);
+        assert!(functions.iter().any(|f| f.name == "transpose"));
+        assert!(functions.iter().all(|f| f.name != "scale"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_filter_drops_and_renames_symbols() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("generated.py");
+        create_test_python_file(&file_path, "class Gen_Client:\n    def send(self):\n        pass\n\n    def close(self):\n        pass\n\ndef Gen_connect():\n    pass\n")?;
+        let files = vec![file_path];
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions {
+            filter: Some(SymbolFilter::new(|symbol| {
+                if let Some(name) = symbol.name.strip_prefix("Gen_") {
+                    symbol.name = name.to_string();
+                }
+                symbol.context.symbol_type != SymbolType::Method
+            })),
+            ..Default::default()
+        };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        let function_names: Vec<_> = functions.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(function_names, vec!["connect"]);
+        let class_names: Vec<_> = classes.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(class_names, vec!["Client"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignore_names_excludes_matching_symbols() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("test_models.py");
+        create_test_python_file(&file_path, "class TestUser:\n    def setUp(self):\n        pass\n\n    def tearDown(self):\n        pass\n\n    def test_save(self):\n        pass\n\n    def check_saved(self):\n        pass\n")?;
+        let ignore_file = base_dir.join("ignore-names");
+        std::fs::write(&ignore_file, "# unittest fixtures\nsetUp\n\ntearDown\ntest_*\n")?;
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions {
+            filter: Some(ignore_names_filter(read_ignore_names(&ignore_file)?)),
+            ..Default::default()
+        };
+        parse_python_files_parallel_with_options(&[file_path], base_dir, &stats, &options)?;
+        let function_names: Vec<_> = stats.functions.lock().unwrap().iter().map(|f| f.name.clone()).collect();
+        assert_eq!(function_names, vec!["check_saved"]);
+        // `test_*` is case-sensitive, so the TestUser class stays
+        assert_eq!(stats.classes.lock().unwrap().len(), 1);
+
+        assert!(name_matches_pattern("setUp", "setUp"));
+        assert!(!name_matches_pattern("setUpClass", "setUp"));
+        assert!(!name_matches_pattern("setup", "setUp"));
+        assert!(name_matches_pattern("get_user", "get_*"));
+        assert!(name_matches_pattern("get_", "get_*"));
+        assert!(name_matches_pattern("a_b_c", "*_c"));
+        assert!(name_matches_pattern("a_b_c", "a*b*c"));
+        assert!(!name_matches_pattern("a_b_d", "a*b*c"));
+        assert!(name_matches_pattern("v1", "v?"));
+        assert!(!name_matches_pattern("v", "v?"));
+        assert!(read_ignore_names(&base_dir.join("missing")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_source_roots_give_src_layout_module_names() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("src").join("package").join("client.py");
+        create_test_python_file(&file_path, "class Client:\n    pass\n")?;
+        let files = vec![file_path];
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        let client = stats.classes.lock().unwrap().iter().next().unwrap().clone();
+        assert_eq!(client.context.module_path(), "src.package.client");
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { source_roots: vec![PathBuf::from("src")], ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let client = stats.classes.lock().unwrap().iter().next().unwrap().clone();
+        assert_eq!(client.context.fully_qualified_module, "package");
+        assert_eq!(client.context.module_path(), "package.client");
+        Ok(())
+    }
+
+    #[test]
+    fn test_bom_and_shebang_keep_line_numbers() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let bom_path = base_dir.join("bom.py");
+        create_test_python_file(&bom_path, "\u{feff}def first():\n    pass\n\n\nclass Second:\n    pass\n")?;
+        let shebang_path = base_dir.join("script.py");
+        create_test_python_file(&shebang_path, "\u{feff}#!/usr/bin/env python3\n# -*- coding: utf-8 -*-\n\ndef main():\n    pass\n")?;
+        let files = vec![bom_path, shebang_path];
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        let first = functions.iter().find(|f| f.name == "first").expect("first should be found");
+        assert_eq!(first.context.line_number, 1);
+        let main = functions.iter().find(|f| f.name == "main").expect("main should be found");
+        assert_eq!(main.context.line_number, 4);
+        let second = classes.iter().find(|c| c.name == "Second").expect("Second should be found");
+        assert_eq!(second.context.line_number, 5);
+        Ok(())
+    }
+
+    #[test]
+    fn test_public_only_skips_private_names() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("api.py");
+        create_test_python_file(&file_path, r#"def public_function():
+    def _nested_helper():
+        pass
+
+def _helper():
+    def inner():
+        pass
+
+class Client:
+    def __init__(self):
+        pass
+
+    def __repr__(self):
+        return "Client()"
+
+    @property
+    def _cached(self):
+        return None
+
+    def send(self):
+        pass
+
+class _Internal:
+    def run(self):
+        pass
+"#)?;
+        let files = vec![file_path];
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { public_only: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+        let mut function_names: Vec<&str> = functions.iter().map(|f| f.name.as_str()).collect();
+        function_names.sort();
+        let class_names: Vec<&str> = classes.iter().map(|c| c.name.as_str()).collect();
+
+        // _helper is excluded along with its body; __init__ is kept
+        assert_eq!(function_names, vec!["__init__", "public_function", "send"]);
+        assert_eq!(class_names, vec!["Client"]);
+
+        // Everything is indexed by default
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert!(stats.functions.lock().unwrap().iter().any(|f| f.name == "_helper"));
+        assert!(stats.classes.lock().unwrap().iter().any(|c| c.name == "_Internal"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tests_only_keeps_test_symbols() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let auth_path = base_dir.join("auth.py");
+        create_test_python_file(&auth_path, r#"def login():
+    pass
+
+def test_login():
+    pass
+
+class TestLogout:
+    def setUp(self):
+        pass
+
+class Session:
+    pass
+"#)?;
+        create_dir_all(base_dir.join("tests"))?;
+        let helper_path = base_dir.join("tests").join("helpers.py");
+        create_test_python_file(&helper_path, "def make_user():\n    pass\n")?;
+        let files = vec![auth_path, helper_path];
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { tests_only: Some(TestPatterns::default()), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let mut function_names: Vec<String> = stats.functions.lock().unwrap().iter().map(|f| f.name.clone()).collect();
+        function_names.sort();
+        let class_names: Vec<String> = stats.classes.lock().unwrap().iter().map(|c| c.name.clone()).collect();
+        assert_eq!(function_names, vec!["make_user", "setUp", "test_login"]);
+        assert_eq!(class_names, vec!["TestLogout"]);
+
+        // Custom patterns replace the defaults
+        let stats = SymbolStats::new();
+        let patterns = TestPatterns { names: vec!["*_spec".to_string()], directories: Vec::new() };
+        create_test_python_file(&files[0], "def login_spec():\n    pass\n\ndef test_login():\n    pass\n")?;
+        let options = CollectOptions { tests_only: Some(patterns), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let function_names: Vec<String> = stats.functions.lock().unwrap().iter().map(|f| f.name.clone()).collect();
+        assert_eq!(function_names, vec!["login_spec"]);
+
+        let patterns = TestPatterns::default();
+        assert!(patterns.is_test_path(Path::new("tests/unit/test_auth.py")));
+        assert!(!patterns.is_test_path(Path::new("tests.py")));
+        assert!(!patterns.is_test_path(Path::new("src/contests/auth.py")));
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_methods_keeps_module_level_definitions() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("api.py");
+        create_test_python_file(&file_path, r#"def connect():
+    def retry():
+        pass
+
+class Client:
+    class Config:
+        pass
+
+    def send(self):
+        pass
+"#)?;
+        let files = vec![file_path];
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { skip_methods: true, ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+
+        let function_names: Vec<String> = stats.functions.lock().unwrap().iter().map(|f| f.name.clone()).collect();
+        let mut class_names: Vec<String> = stats.classes.lock().unwrap().iter().map(|c| c.name.clone()).collect();
+        class_names.sort();
+        assert_eq!(function_names, vec!["connect"]);
+        assert_eq!(class_names, vec!["Client", "Config"]);
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        assert_eq!(stats.functions.lock().unwrap().len(), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_timeout_is_counted() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        // Big enough that parsing takes far longer than the timeout
+        let large = base_dir.join("large.py");
+        let source: String = (0..20_000).map(|i| format!("def function_{}(a, b):\n    return a + b\n\n", i)).collect();
+        create_test_python_file(&large, &source)?;
+        let small = base_dir.join("small.py");
+        create_test_python_file(&small, "def quick():\n    pass\n")?;
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions {
+            threads: Some(1),
+            parse_timeout: Some(Duration::from_micros(1)),
+            ..Default::default()
+        };
+        parse_python_files_parallel_with_options(&[large.clone(), small], base_dir, &stats, &options)?;
+        let snapshot = stats.snapshot();
+        assert_eq!(snapshot.parse_timeouts, 1);
+        assert_eq!(snapshot.syntax_errors, 0);
+        assert_eq!(snapshot.files_scanned, 2);
+        // The small file still parses on the same parser once it is reset
+        let function_names: Vec<String> = stats.functions.lock().unwrap().iter().map(|f| f.name.clone()).collect();
+        assert_eq!(function_names, vec!["quick"]);
+
+        // Without a timeout the large file parses fine
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[large], base_dir, &stats)?;
+        assert_eq!(stats.snapshot().parse_timeouts, 0);
+        assert_eq!(stats.functions.lock().unwrap().len(), 20_000);
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_symbols_drops_nested_definitions_first() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("shop.py");
+        create_test_python_file(&file_path, r#"def checkout():
+    def validate():
+        def check_item():
+            pass
+
+class Cart:
+    def add(self):
+        pass
+
+def refund():
+    pass
+"#)?;
+        let files = vec![file_path];
+
+        let stats = SymbolStats::new();
+        let options = CollectOptions { max_symbols: Some(3), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let mut function_names: Vec<String> = stats.functions.lock().unwrap().iter().map(|f| f.name.clone()).collect();
+        function_names.sort();
+        let class_names: Vec<String> = stats.classes.lock().unwrap().iter().map(|c| c.name.clone()).collect();
+        assert_eq!(function_names, vec!["checkout", "refund"]);
+        assert_eq!(class_names, vec!["Cart"]);
+        assert_eq!(stats.snapshot().pruned_symbols, 3);
+
+        // Methods outlive functions nested inside functions
+        let stats = SymbolStats::new();
+        let options = CollectOptions { max_symbols: Some(4), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        let function_names: HashSet<String> = stats.functions.lock().unwrap().iter().map(|f| f.name.clone()).collect();
+        assert!(function_names.contains("add"));
+        assert!(!function_names.contains("validate") && !function_names.contains("check_item"));
+
+        // Under budget nothing is dropped
+        let stats = SymbolStats::new();
+        let options = CollectOptions { max_symbols: Some(100), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        assert_eq!(stats.functions.lock().unwrap().len() + stats.classes.lock().unwrap().len(), 6);
+        assert_eq!(stats.snapshot().pruned_symbols, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_budget_truncates_parsing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let mut files = Vec::new();
+        for i in 0..120 {
+            let file_path = base_dir.join(format!("module_{}.py", i));
+            create_test_python_file(&file_path, &format!("def function_{}():\n    pass\n", i))?;
+            files.push(file_path);
+        }
+
+        // An exhausted budget stops before every chunk is parsed
+        let stats = SymbolStats::new();
+        let options = CollectOptions { time_budget: Some(Duration::ZERO), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        assert!(stats.is_truncated());
+        assert!(stats.functions.lock().unwrap().len() < files.len());
+
+        // A generous budget parses everything
+        let stats = SymbolStats::new();
+        let options = CollectOptions { time_budget: Some(Duration::from_secs(600)), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        assert!(!stats.is_truncated());
+        assert_eq!(stats.functions.lock().unwrap().len(), files.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel_stops_parsing() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let mut files = Vec::new();
+        for i in 0..20 {
+            let file_path = base_dir.join(format!("module_{}.py", i));
+            create_test_python_file(&file_path, &format!("def function_{}():\n    pass\n", i))?;
+            files.push(file_path);
+        }
+
+        let cancel = Arc::new(AtomicBool::new(true));
+        let stats = SymbolStats::new();
+        let options = CollectOptions { cancel: Some(cancel.clone()), ..Default::default() };
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        assert!(stats.is_truncated());
+        assert!(stats.functions.lock().unwrap().is_empty());
+        assert_eq!(stats.snapshot().files_scanned, 0);
+
+        cancel.store(false, Ordering::Relaxed);
+        let stats = SymbolStats::new();
+        parse_python_files_parallel_with_options(&files, base_dir, &stats, &options)?;
+        assert!(!stats.is_truncated());
+        assert_eq!(stats.functions.lock().unwrap().len(), files.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_streamed_symbols_match_batch() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let mut files = Vec::new();
+        for i in 0..5 {
+            let file_path = base_dir.join("pkg").join(format!("module_{}.py", i));
+            create_test_python_file(&file_path, &format!(r#"
+def function_{i}():
+    def nested():
+        pass
+
+class Class_{i}:
+    def method(self):
+        pass
+"#))?;
+            files.push(file_path);
+        }
+        files.push(base_dir.join("missing.py"));
+
+        let streamed: HashSet<Symbol> = parse_python_files_streaming(&files, base_dir).into_iter().collect();
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        let mut batch = stats.functions.lock().unwrap().clone();
+        batch.extend(stats.classes.lock().unwrap().iter().cloned());
+
+        assert_eq!(streamed.len(), 20);
+        assert_eq!(streamed, batch);
+        Ok(())
+    }
+
+    #[test]
+    fn test_definitions_in_compound_statements() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let file_path = base_dir.join("compat.py");
+        create_test_python_file(&file_path, r#"
+try:
+    from fast import loads
+except ImportError:
+    def loads(data):
+        pass
+finally:
+    def cleanup():
+        pass
+
+if TYPE_CHECKING:
+    class Protocol:
+        pass
+elif sys.version_info >= (3, 8):
+    def version_helper():
+        pass
+else:
+    pass
+
+with open("config") as f:
+    def configured():
+        pass
+
+for name in NAMES:
+    def in_loop():
+        pass
+
+match mode:
+    case "fast":
+        def fast_path():
+            pass
+
+class Service:
+    if DEBUG:
+        def debug_dump(self):
+            pass
+
+def outer():
+    if flag:
+        def local_only():
+            pass
+"#)?;
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&[file_path], base_dir, &stats)?;
+        let functions = stats.functions.lock().unwrap();
+        let classes = stats.classes.lock().unwrap();
+
+        let mut function_names: Vec<&str> = functions.iter().map(|f| f.name.as_str()).collect();
+        function_names.sort();
+        assert_eq!(function_names, vec![
+            "cleanup", "configured", "debug_dump", "fast_path", "in_loop", "loads", "outer", "version_helper",
+        ]);
+        assert!(classes.iter().any(|c| c.name == "Protocol"));
+
+        let loads = functions.iter().find(|f| f.name == "loads").unwrap();
+        assert_eq!(loads.context.symbol_type, crate::symbols::SymbolType::Function);
+        assert_eq!(loads.context.line_number, 5);
+        let debug_dump = functions.iter().find(|f| f.name == "debug_dump").unwrap();
+        assert_eq!(debug_dump.context.symbol_type, crate::symbols::SymbolType::Method);
+
+        // Nothing is emitted twice: counting sees the same definitions, plus the function-local one
+        let (num_functions, num_classes) = count_symbols_parallel(std::slice::from_ref(&base_dir.join("compat.py")));
+        assert_eq!((num_functions, num_classes), (function_names.len() + 1, classes.len()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_index_drift() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let unchanged = base_dir.join("unchanged.py");
+        let edited = base_dir.join("edited.py");
+        let deleted = base_dir.join("deleted.py");
+        create_test_python_file(&unchanged, "def stable():\n    pass\n")?;
+        create_test_python_file(&edited, r#"class Client:
+    def send(self):
+        pass
+
+    def close(self):
+        pass
+"#)?;
+        create_test_python_file(&deleted, "def gone():\n    pass\n")?;
+        let files = vec![unchanged.clone(), edited.clone(), deleted.clone()];
+
+        let stats = SymbolStats::new();
+        parse_python_files_parallel(&files, base_dir, &stats)?;
+        let functions = stats.functions.lock().unwrap().clone();
+        let classes = stats.classes.lock().unwrap().clone();
+        let path_registry = stats.path_registry.lock().unwrap().clone();
+
+        let options = CollectOptions::default();
+        assert!(find_index_drift(&functions, &classes, &path_registry, base_dir, &options).is_empty());
+
+        create_test_python_file(&edited, r#"class Client:
+    def connect(self):
+        pass
+
+    def send(self):
+        pass
+"#)?;
+        std::fs::remove_file(&deleted)?;
+
+        let drift = find_index_drift(&functions, &classes, &path_registry, base_dir, &options);
+        assert_eq!(drift.len(), 2);
+        assert_eq!(drift[0].path, deleted);
+        assert!(drift[0].error.is_some());
+        assert_eq!(drift[1].path, edited);
+        assert_eq!(drift[1].added, vec!["method Client.connect"]);
+        assert_eq!(drift[1].removed, vec!["method Client.close"]);
+        assert_eq!(drift[1].moved, vec!["method Client.send ([2] -> [5])"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_diff_indexes() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let base_dir = temp_dir.path();
+        let models = base_dir.join("models.py");
+        let utils = base_dir.join("utils.py");
+        create_test_python_file(&models, "class User:\n    pass\n")?;
+        create_test_python_file(&utils, "def helper():\n    pass\n")?;
+
+        let index = |files: Vec<PathBuf>| -> Result<(Vec<Symbol>, Vec<PathBuf>)> {
+            let stats = SymbolStats::new();
+            parse_python_files_parallel(&files, base_dir, &stats)?;
+            let mut symbols: Vec<Symbol> = stats.functions.lock().unwrap().iter().cloned().collect();
+            symbols.extend(stats.classes.lock().unwrap().iter().cloned());
+            let paths = stats.path_registry.lock().unwrap().paths.clone();
+            Ok((symbols, paths))
+        };
+        let (old_symbols, old_paths) = index(vec![models.clone(), utils.clone()])?;
+        assert!(diff_indexes(&old_symbols, &old_paths, &old_symbols, &old_paths).is_empty());
+
+        // Register the files in the other order so their path indices differ between indexes
+        create_test_python_file(&utils, "def helper():\n    pass\n\ndef format_name():\n    pass\n")?;
+        let (new_symbols, new_paths) = index(vec![utils.clone(), models.clone()])?;
+        assert_ne!(old_paths, new_paths);
+
+        let diff = diff_indexes(&old_symbols, &old_paths, &new_symbols, &new_paths);
+        assert_eq!(diff.len(), 1);
+        assert_eq!(diff[0].path, utils);
+        assert_eq!(diff[0].added, vec!["function format_name"]);
+        assert!(diff[0].removed.is_empty() && diff[0].moved.is_empty());
+
+        let reverse = diff_indexes(&new_symbols, &new_paths, &old_symbols, &old_paths);
+        assert_eq!(reverse[0].removed, vec!["function format_name"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_python_parser_reports_abi_version() -> Result<()> {
+        let version = check_grammar_compatibility()?;
+        assert!(version >= tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION);
+        assert!(version <= tree_sitter::LANGUAGE_VERSION);
+
+        let parser = create_python_parser()?;
+        assert_eq!(parser.language().map(|language| language.version()), Some(version));
+        Ok(())
+    }
+}
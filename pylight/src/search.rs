@@ -1,11 +1,21 @@
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
 use crate::symbols::{Symbol, PathRegistry, SymbolType};
-use crate::search_skim::search_symbols_skim;
+use crate::search_skim::{search_symbols_skim, SkimPreset};
+use crate::search_regex::search_symbols_regex;
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use crossterm::style::{Color, Stylize};
+use std::io::{IsTerminal, Write};
 use std::time::Instant;
+use icu_normalizer::DecomposingNormalizer;
+use icu_properties::GeneralCategory;
 use nucleo_matcher::{
     pattern::{CaseMatching, Normalization, Pattern}, 
     Config as NucleoConfig, 
-    Matcher as NucleoMatcher
+    Matcher as NucleoMatcher,
+    Utf32Str,
 };
 
 /// Defines the available search algorithms
@@ -15,6 +25,19 @@ pub enum SearchAlgorithm {
     Skim,
     /// Uses fuzzy-matcher with ignore_case option
     Nucleo,
+    /// Treats the query as a regular expression matched against symbol names
+    Regex,
+}
+
+impl SearchAlgorithm {
+    /// The algorithm after this one, wrapping around, for cycling through them interactively
+    pub fn next(self) -> Self {
+        match self {
+            SearchAlgorithm::Skim => SearchAlgorithm::Nucleo,
+            SearchAlgorithm::Nucleo => SearchAlgorithm::Regex,
+            SearchAlgorithm::Regex => SearchAlgorithm::Skim,
+        }
+    }
 }
 
 impl std::str::FromStr for SearchAlgorithm {
@@ -24,7 +47,8 @@ impl std::str::FromStr for SearchAlgorithm {
         match s.to_lowercase().as_str() {
             "skim" => Ok(SearchAlgorithm::Skim),
             "nucleo" => Ok(SearchAlgorithm::Nucleo),
-            _ => Err(format!("Unknown search algorithm: {}. Valid options are 'skim' or 'nucleo'", s)),
+            "regex" => Ok(SearchAlgorithm::Regex),
+            _ => Err(format!("Unknown search algorithm: {}. Valid options are 'skim', 'nucleo' or 'regex'", s)),
         }
     }
 }
@@ -34,10 +58,377 @@ impl std::fmt::Display for SearchAlgorithm {
         match self {
             SearchAlgorithm::Skim => write!(f, "skim"),
             SearchAlgorithm::Nucleo => write!(f, "nucleo"),
+            SearchAlgorithm::Regex => write!(f, "regex"),
+        }
+    }
+}
+
+/// Optional tweaks to how symbols are matched
+#[derive(Debug, Default, Clone)]
+pub struct SearchOptions {
+    /// Append the lowercase symbol kind to the text used for fuzzy matching, so a
+    /// query like "config class" matches a class named Config. Displayed names are unchanged.
+    pub match_kind_in_name: bool,
+    /// Also match the query against the initials of the name's words (split on `_` and
+    /// camel-case transitions), so "gu" finds GetUser and "dfs" finds depth_first_search.
+    /// The better of the fuzzy and initials scores is used.
+    pub match_word_initials: bool,
+    /// Words of each symbol name split once up front, used by `match_word_initials` instead
+    /// of splitting every name again for each query. Names missing from it are split as usual.
+    pub identifier_words: Option<Arc<IdentifierWords>>,
+    /// Match against the fully-qualified name (`package.module.Parent.name`) instead of the
+    /// bare name. Matched characters in the final `.` segment score extra, so a query for
+    /// "target" prefers `a.b.target` over a symbol whose module path happens to spell it.
+    pub match_qualified_name: bool,
+    /// Strip diacritics from the query and the matched text, so "cafe" finds `café`.
+    /// Displayed names are unchanged.
+    pub fold_diacritics: bool,
+    /// Only consider symbols of these kinds (e.g. just methods); `None` allows every kind.
+    /// Filtering happens before scoring, so excluded symbols cost nothing.
+    pub kinds: Option<HashSet<SymbolType>>,
+    /// Scoring weights for the Skim algorithm
+    pub skim_preset: SkimPreset,
+    /// Return only the best this many results; `None` returns every match
+    pub max_results: Option<usize>,
+}
+
+impl SearchOptions {
+    /// Whether symbols of `kind` can appear in results
+    pub fn includes_kind(&self, kind: &SymbolType) -> bool {
+        self.kinds.as_ref().is_none_or(|kinds| kinds.contains(kind))
+    }
+}
+
+/// Remove diacritics from `text`, both precomposed (é) and as combining marks (e + ◌́):
+/// decompose it to NFD, then drop every nonspacing mark
+pub fn fold_diacritics(text: &str) -> String {
+    let general_category = icu_properties::maps::general_category();
+    DecomposingNormalizer::new_nfd()
+        .normalize(text)
+        .chars()
+        .filter(|&c| general_category.get(c) != GeneralCategory::NonspacingMark)
+        .collect()
+}
+
+/// Score given when the query spells out all of a name's word initials
+const INITIALS_FULL_MATCH_SCORE: i64 = 500;
+/// Score given when the query is a prefix of a name's word initials
+const INITIALS_PREFIX_MATCH_SCORE: i64 = 300;
+
+/// Split an identifier into words on `_` and camel-case transitions.
+/// Runs of capitals are kept together as acronyms ("HTTPServer" -> "HTTP", "Server").
+pub fn split_identifier_words(name: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for part in name.split('_').filter(|p| !p.is_empty()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+        for i in 1..chars.len() {
+            let (idx, c) = chars[i];
+            let prev = chars[i - 1].1;
+            let next_is_lower = chars.get(i + 1).is_some_and(|&(_, n)| n.is_lowercase());
+            let boundary = (c.is_uppercase() && !prev.is_uppercase())
+                || (c.is_uppercase() && prev.is_uppercase() && next_is_lower)
+                || (c.is_ascii_digit() != prev.is_ascii_digit() && c.is_alphanumeric() && prev.is_alphanumeric());
+            if boundary {
+                words.push(&part[start..idx]);
+                start = idx;
+            }
+        }
+        words.push(&part[start..]);
+    }
+    words
+}
+
+/// The lowercased words (see `split_identifier_words`) of every symbol name in an index.
+/// Words are interned, since the same few (`get`, `user`, `test`, ...) recur across names.
+#[derive(Debug, Default)]
+pub struct IdentifierWords {
+    words: HashMap<String, Vec<Arc<str>>>,
+}
+
+impl IdentifierWords {
+    /// Split the names of `symbols`, each distinct name once
+    pub fn build<'a>(symbols: impl IntoIterator<Item = &'a Symbol>) -> Self {
+        let mut interned: HashSet<Arc<str>> = HashSet::new();
+        let mut words = HashMap::new();
+        for symbol in symbols {
+            if words.contains_key(&symbol.name) {
+                continue;
+            }
+            let name_words = split_identifier_words(&symbol.name)
+                .into_iter()
+                .map(|word| {
+                    let word = word.to_lowercase();
+                    match interned.get(word.as_str()) {
+                        Some(existing) => existing.clone(),
+                        None => {
+                            let word: Arc<str> = word.into();
+                            interned.insert(word.clone());
+                            word
+                        }
+                    }
+                })
+                .collect();
+            words.insert(symbol.name.clone(), name_words);
+        }
+        IdentifierWords { words }
+    }
+
+    /// The words of `name`, if it was one of the indexed names
+    pub fn get(&self, name: &str) -> Option<&[Arc<str>]> {
+        self.words.get(name).map(Vec::as_slice)
+    }
+}
+
+/// Score how well `query` matches the initials of `name`'s words, if at all
+pub fn word_initials_score(name: &str, query: &str) -> Option<i64> {
+    initials_score(split_identifier_words(name).into_iter(), query)
+}
+
+fn initials_score<'a>(words: impl Iterator<Item = &'a str>, query: &str) -> Option<i64> {
+    let initials: String = words
+        .filter_map(|w| w.chars().next())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let query: String = query.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect();
+
+    // A single letter is too ambiguous to treat as an acronym
+    if query.chars().count() < 2 {
+        return None;
+    }
+    if initials == query {
+        Some(INITIALS_FULL_MATCH_SCORE)
+    } else if initials.starts_with(&query) {
+        Some(INITIALS_PREFIX_MATCH_SCORE)
+    } else {
+        None
+    }
+}
+
+/// Combine a fuzzy score with the word-initials score when enabled, keeping the better one
+pub(crate) fn apply_word_initials(name: &str, query: &str, fuzzy_score: i64, options: &SearchOptions) -> i64 {
+    if !options.match_word_initials {
+        return fuzzy_score;
+    }
+    let initials = match options.identifier_words.as_ref().and_then(|words| words.get(name)) {
+        Some(words) => initials_score(words.iter().map(|word| &**word), query),
+        None => word_initials_score(name, query),
+    };
+    initials.map_or(fuzzy_score, |initials| initials.max(fuzzy_score))
+}
+
+/// Whether `query` is `Class.method` naming this symbol and its enclosing class exactly
+/// (ignoring case), so `User.save` picks out one of many `save` methods. Nested classes
+/// may be spelled out too, as in `Outer.Inner.method`. Such queries rank like exact matches.
+pub fn is_class_qualified_match(symbol: &Symbol, query: &str) -> bool {
+    let Some((class_path, name)) = query.rsplit_once('.') else {
+        return false;
+    };
+    let parents = &symbol.context.parent_context;
+    let is_in_class = parents.last().is_some_and(|parent| {
+        matches!(parent.symbol_type, SymbolType::Class | SymbolType::NestedClass)
+    });
+    let classes: Vec<&str> = class_path.split('.').collect();
+    is_in_class
+        && classes.len() <= parents.len()
+        && symbol.name.to_lowercase() == name.to_lowercase()
+        && parents[parents.len() - classes.len()..].iter()
+            .zip(&classes)
+            .all(|(parent, class)| parent.name.to_lowercase() == class.to_lowercase())
+}
+
+/// Extra score for each matched character in the final segment of a qualified name
+const FINAL_SEGMENT_MATCH_BONUS: i64 = 8;
+
+/// Build the text a symbol is fuzzy matched against
+pub(crate) fn match_haystack<'a>(symbol: &'a Symbol, options: &SearchOptions) -> Cow<'a, str> {
+    let name: Cow<'a, str> = if options.match_qualified_name {
+        let mut qualified = symbol.context.module_path();
+        for parent in &symbol.context.parent_context {
+            qualified.push('.');
+            qualified.push_str(&parent.name);
+        }
+        qualified.push('.');
+        qualified.push_str(&symbol.name);
+        Cow::Owned(qualified)
+    } else {
+        Cow::Borrowed(symbol.name.as_str())
+    };
+    let haystack = if options.match_kind_in_name {
+        Cow::Owned(format!("{} {}", name, symbol.context.symbol_type.kind_name()))
+    } else {
+        name
+    };
+    if options.fold_diacritics {
+        Cow::Owned(fold_diacritics(&haystack))
+    } else {
+        haystack
+    }
+}
+
+/// Split a query into whitespace-separated terms that must all match, like fzf.
+/// A query without whitespace is a single term.
+pub fn query_terms(query: &str) -> Vec<&str> {
+    query.split_whitespace().collect()
+}
+
+/// Bonus for matched characters (char indices into `haystack`, sorted and unique) that fall
+/// in the final `.`-delimited segment, added atop the matcher's score for qualified haystacks
+pub(crate) fn final_segment_bonus(haystack: &str, matched_indices: &[usize]) -> i64 {
+    let segment_start = haystack.rfind('.').map_or(0, |dot| haystack[..dot].chars().count() + 1);
+    let in_final_segment = matched_indices.iter().filter(|&&i| i >= segment_start).count();
+    in_final_segment as i64 * FINAL_SEGMENT_MATCH_BONUS
+}
+
+/// Order results best first, breaking score ties by name, then file and line, so that
+/// equal-scoring results come out the same way every run rather than in `HashSet` order
+pub(crate) fn sort_results(results: &mut [(Symbol, i64)]) {
+    results.sort_by(compare_results);
+}
+
+fn compare_results((a, a_score): &(Symbol, i64), (b, b_score): &(Symbol, i64)) -> std::cmp::Ordering {
+    b_score.cmp(a_score)
+        .then_with(|| a.name.cmp(&b.name))
+        .then(a.context.file_path_index.cmp(&b.context.file_path_index))
+        .then(a.context.line_number.cmp(&b.context.line_number))
+}
+
+/// How search results are ordered for display, once the best ones are found
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResultOrder {
+    /// Best match first
+    #[default]
+    Score,
+    /// Alphabetically by name, ignoring case
+    Name,
+    /// By file path, then line, so results from one file are together
+    File,
+}
+
+impl std::str::FromStr for ResultOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "score" => Ok(ResultOrder::Score),
+            "name" => Ok(ResultOrder::Name),
+            "file" => Ok(ResultOrder::File),
+            _ => Err(format!("Unknown sort order: {}. Valid options are 'score', 'name' or 'file'", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for ResultOrder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResultOrder::Score => write!(f, "score"),
+            ResultOrder::Name => write!(f, "name"),
+            ResultOrder::File => write!(f, "file"),
         }
     }
 }
 
+/// Reorder `results` for display. Every order falls back to name, file path, line and
+/// then score, so equal keys always come out the same way.
+pub fn order_results(results: &mut [(Symbol, i64)], order: ResultOrder, path_registry: &PathRegistry) {
+    let path = |symbol: &Symbol| path_registry.get_path(symbol.context.file_path_index);
+    let by_name = |a: &Symbol, b: &Symbol| {
+        a.name.to_lowercase().cmp(&b.name.to_lowercase()).then_with(|| a.name.cmp(&b.name))
+    };
+    let by_location = |a: &Symbol, b: &Symbol| {
+        path(a).cmp(path(b)).then(a.context.line_number.cmp(&b.context.line_number))
+    };
+    match order {
+        ResultOrder::Score => results.sort_by(|(a, a_score), (b, b_score)| {
+            b_score.cmp(a_score).then_with(|| by_name(a, b)).then_with(|| by_location(a, b))
+        }),
+        ResultOrder::Name => results.sort_by(|(a, a_score), (b, b_score)| {
+            by_name(a, b).then_with(|| by_location(a, b)).then(b_score.cmp(a_score))
+        }),
+        ResultOrder::File => results.sort_by(|(a, a_score), (b, b_score)| {
+            by_location(a, b).then_with(|| by_name(a, b)).then(b_score.cmp(a_score))
+        }),
+    }
+}
+
+/// `sort_results`, keeping only the best `limit` results. The rest are partitioned off
+/// and dropped first, so only the kept ones pay for a full sort.
+pub(crate) fn sort_top_results(results: &mut Vec<(Symbol, i64)>, limit: Option<usize>) {
+    if let Some(limit) = limit.filter(|&limit| limit < results.len()) {
+        if limit == 0 {
+            results.clear();
+            return;
+        }
+        results.select_nth_unstable_by(limit - 1, compare_results);
+        results.truncate(limit);
+    }
+    sort_results(results);
+}
+
+/// Maximum number of files whose symbols are returned for a path query
+const MAX_PATH_QUERY_FILES: usize = 10;
+
+/// Whether a query looks like a path fragment (e.g. "auth/login") rather than a symbol name
+pub fn is_path_query(query: &str) -> bool {
+    query.contains('/')
+}
+
+/// Fuzzy match `query` against indexed file paths and return every symbol from the
+/// best-matching files, ranked by how well their path matched (then by file and line)
+pub fn search_symbols_by_path(
+    query: &str,
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+    debug: bool,
+) -> (Vec<(Symbol, i64)>, SearchMetrics) {
+    let mut metrics = SearchMetrics::default();
+    let start_total = Instant::now();
+
+    let matcher_start = Instant::now();
+    let matcher = SkimMatcherV2::default();
+    metrics.matcher_init_time_ms = matcher_start.elapsed().as_millis();
+
+    let search_start = Instant::now();
+    let mut file_scores: Vec<(usize, i64)> = path_registry.paths.iter()
+        .enumerate()
+        .filter_map(|(index, path)| {
+            matcher.fuzzy_match(&path.to_string_lossy(), query).map(|score| (index, score))
+        })
+        .collect();
+    file_scores.sort_by_key(|&(index, score)| (std::cmp::Reverse(score), index));
+    file_scores.truncate(MAX_PATH_QUERY_FILES);
+
+    if debug {
+        for &(index, score) in &file_scores {
+            println!("PATH: {} | Score: {}", path_registry.get_path(index).display(), score);
+        }
+    }
+
+    let file_scores: std::collections::HashMap<usize, i64> = file_scores.into_iter().collect();
+    let mut results: Vec<(Symbol, i64)> = functions.iter()
+        .chain(classes.iter())
+        .filter_map(|symbol| {
+            file_scores.get(&symbol.context.file_path_index).map(|&score| (symbol.clone(), score))
+        })
+        .collect();
+    metrics.search_time_ms = search_start.elapsed().as_millis();
+
+    let sort_start = Instant::now();
+    results.sort_by_key(|(symbol, score)| {
+        (std::cmp::Reverse(*score), symbol.context.file_path_index, symbol.context.line_number)
+    });
+    metrics.sort_time_ms = sort_start.elapsed().as_millis();
+
+    metrics.results_count = results.len();
+    metrics.total_time_ms = start_total.elapsed().as_millis();
+
+    (results, metrics)
+}
+
 /// Performance metrics for search operations
 #[derive(Debug, Default, Clone)]
 pub struct SearchMetrics {
@@ -49,7 +440,7 @@ pub struct SearchMetrics {
     pub sort_time_ms: u128,
     /// Total time spent
     pub total_time_ms: u128,
-    /// Number of results found
+    /// Number of results found, including any cut by `SearchOptions::max_results`
     pub results_count: usize,
 }
 
@@ -60,6 +451,7 @@ pub fn search_symbols_nucleo(
     classes: &HashSet<Symbol>,
     path_registry: &PathRegistry,
     debug: bool,
+    options: &SearchOptions,
 ) -> (Vec<(Symbol, i64)>, SearchMetrics) {
     let mut metrics = SearchMetrics::default();
     let start_total = Instant::now();
@@ -79,26 +471,27 @@ pub fn search_symbols_nucleo(
     let search_start = Instant::now();
     
     // Process function symbols
-    process_collection(functions, &mut seen_symbols, &pattern, &mut matcher, &mut results, path_registry, debug, query);
+    process_collection(functions, &mut seen_symbols, &pattern, &mut matcher, &mut results, path_registry, debug, query, options);
     
     // Process class symbols
-    process_collection(classes, &mut seen_symbols, &pattern, &mut matcher, &mut results, path_registry, debug, query);
+    process_collection(classes, &mut seen_symbols, &pattern, &mut matcher, &mut results, path_registry, debug, query, options);
 
     // Record search time
     metrics.search_time_ms = search_start.elapsed().as_millis();
     
     // Sort by score (highest first)
+    metrics.results_count = results.len();
     let sort_start = Instant::now();
-    results.sort_by(|a, b| b.1.cmp(&a.1));
+    sort_top_results(&mut results, options.max_results);
     metrics.sort_time_ms = sort_start.elapsed().as_millis();
     
-    metrics.results_count = results.len();
     metrics.total_time_ms = start_total.elapsed().as_millis();
     
     (results, metrics)
 }
 
 // Helper function to process a collection of symbols
+#[allow(clippy::too_many_arguments)]
 fn process_collection(
     symbols: &HashSet<Symbol>,
     seen_symbols: &mut HashSet<String>,
@@ -108,8 +501,12 @@ fn process_collection(
     path_registry: &PathRegistry,
     debug: bool,
     original_query: &str,
+    options: &SearchOptions,
 ) {
     for symbol in symbols {
+        if !options.includes_kind(&symbol.context.symbol_type) {
+            continue;
+        }
         let symbol_key = format!("{}:{}:{}", 
             symbol.name, 
             symbol.context.line_number, 
@@ -118,22 +515,37 @@ fn process_collection(
         // Only check if we've seen this exact symbol (name+line+file) before
         if !seen_symbols.contains(&symbol_key) {
             // Match each symbol one at a time
-            let name_slice = [symbol.name.as_str()];
-            let matches = pattern.match_list(&name_slice, matcher);
+            let haystack = match_haystack(symbol, options);
+            let fuzzy_score = if options.match_qualified_name {
+                let mut buf = Vec::new();
+                let mut indices = Vec::new();
+                pattern.indices(Utf32Str::new(&haystack, &mut buf), matcher, &mut indices).map_or(0, |score| {
+                    indices.sort_unstable();
+                    indices.dedup();
+                    let indices: Vec<usize> = indices.iter().map(|&i| i as usize).collect();
+                    score as i64 + final_segment_bonus(&haystack, &indices)
+                })
+            } else {
+                let name_slice = [haystack.as_ref()];
+                let matches = pattern.match_list(&name_slice, matcher);
+                matches.first().map_or(0, |m| m.1 as i64)
+            };
+            let score = apply_word_initials(&symbol.name, original_query, fuzzy_score, options);
+            let class_qualified = is_class_qualified_match(symbol, original_query);
             
             // If we got a match with a positive score
-            if !matches.is_empty() && matches[0].1 > 0 {
+            if score > 0 || class_qualified {
                 // Mark as seen ONLY IF it matches the pattern
                 seen_symbols.insert(symbol_key);
                 
                 // Boost the score for exact matches
-                let mut score_i64 = matches[0].1 as i64;
+                let mut score_i64 = score;
                 
                 // Check for exact match - case insensitive
                 let symbol_name_lower = symbol.name.to_lowercase();
                 let query_lower = original_query.to_lowercase();
                 
-                if symbol_name_lower == query_lower {
+                if symbol_name_lower == query_lower || class_qualified {
                     // Use a very high score to ensure exact matches appear first
                     score_i64 = 10000;
                     
@@ -179,41 +591,321 @@ pub fn search_symbols(
     debug: bool,
     algorithm: SearchAlgorithm,
 ) -> (Vec<(Symbol, i64)>, SearchMetrics) {
+    search_symbols_with_options(query, functions, classes, path_registry, debug, algorithm, &SearchOptions::default())
+}
+
+/// Same as `search_symbols`, with explicit matching options
+pub fn search_symbols_with_options(
+    query: &str,
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+    debug: bool,
+    algorithm: SearchAlgorithm,
+    options: &SearchOptions,
+) -> (Vec<(Symbol, i64)>, SearchMetrics) {
+    let query = if options.fold_diacritics {
+        Cow::Owned(fold_diacritics(query))
+    } else {
+        Cow::Borrowed(query)
+    };
+    let query = query.as_ref();
+    
+    // Path fragments list the symbols of matching files; regex queries are always taken literally
+    if algorithm != SearchAlgorithm::Regex && is_path_query(query) {
+        let (mut results, mut metrics) = search_symbols_by_path(query, functions, classes, path_registry, debug);
+        results.retain(|(symbol, _)| options.includes_kind(&symbol.context.symbol_type));
+        metrics.results_count = results.len();
+        results.truncate(options.max_results.unwrap_or(usize::MAX));
+        return (results, metrics);
+    }
+    
     // Just delegate to the appropriate implementation
     match algorithm {
         SearchAlgorithm::Skim => {
             // For Skim, we delegate to the skim implementation
-            let (results, metrics) = search_symbols_skim(query, functions, classes, path_registry, debug);
+            let (results, metrics) = search_symbols_skim(query, functions, classes, path_registry, debug, options);
             (results, metrics)
         },
         SearchAlgorithm::Nucleo => {
             // For Nucleo, we delegate to the nucleo implementation
-            search_symbols_nucleo(query, functions, classes, path_registry, debug)
+            search_symbols_nucleo(query, functions, classes, path_registry, debug, options)
+        },
+        SearchAlgorithm::Regex => {
+            // An invalid pattern (often a half-typed one) simply matches nothing here;
+            // call search_symbols_regex directly to get the error
+            search_symbols_regex(query, functions, classes, path_registry, debug, options)
+                .unwrap_or_else(|e| {
+                    tracing::debug!("Invalid regex query '{}': {}", query, e);
+                    (Vec::new(), SearchMetrics::default())
+                })
         },
     }
 }
 
-/// Print a symbol with its details
-pub fn print_symbol(symbol: &Symbol, path_registry: &PathRegistry) {
+/// Maximum number of "did you mean" suggestions for a query with no results
+pub const MAX_SUGGESTIONS: usize = 3;
+
+/// Symbol names closest to `query` by edit distance, for queries that found nothing.
+/// Comparison ignores case; names further than about a third of the query length are
+/// not offered.
+pub fn suggest_symbol_names(
+    query: &str,
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    limit: usize,
+) -> Vec<String> {
+    let query = query.to_lowercase();
+    let max_distance = (query.chars().count() / 3).max(1);
+
+    let mut candidates: Vec<(usize, &str)> = functions
+        .iter()
+        .chain(classes.iter())
+        .map(|symbol| symbol.name.as_str())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .filter_map(|name| {
+            let distance = strsim::levenshtein(&query, &name.to_lowercase());
+            (distance <= max_distance).then_some((distance, name))
+        })
+        .collect();
+    candidates.sort_unstable();
+    candidates
+        .into_iter()
+        .take(limit)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+/// Terminal color for each kind of symbol in printed listings
+pub fn symbol_color(symbol_type: &SymbolType) -> Color {
+    match symbol_type {
+        SymbolType::Class | SymbolType::NestedClass => Color::Yellow,
+        SymbolType::Method => Color::Green,
+        SymbolType::Function | SymbolType::NestedFunction => Color::Cyan,
+        SymbolType::TypeAlias => Color::Magenta,
+    }
+}
+
+/// Whether printed listings should be colorized: never with `--no-color` or `NO_COLOR`,
+/// or when stdout is redirected to a file or pipe
+pub fn should_colorize(no_color: bool) -> bool {
+    !no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal()
+}
+
+/// A one-line key to the colors used for each symbol kind
+pub fn symbol_color_legend() -> String {
+    format!("Legend: {}  {}  {}  {}",
+        "class".with(symbol_color(&SymbolType::Class)),
+        "function".with(symbol_color(&SymbolType::Function)),
+        "method".with(symbol_color(&SymbolType::Method)),
+        "type alias".with(symbol_color(&SymbolType::TypeAlias)))
+}
+
+/// A stored path for display: relative to `root` when it is under it, otherwise as stored
+pub fn display_path<'a>(path: &'a Path, root: Option<&Path>) -> &'a Path {
+    root.and_then(|root| path.strip_prefix(root).ok())
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .unwrap_or(path)
+}
+
+/// `file:line` of the best result, for jumping straight to it from an editor or script
+pub fn top_result_location(results: &[(Symbol, i64)], path_registry: &PathRegistry, root: Option<&Path>) -> Option<String> {
+    let (symbol, _) = results.first()?;
+    let path = path_registry.get_path(symbol.context.file_path_index);
+    Some(format!("{}:{}", display_path(path, root).display(), symbol.context.line_number))
+}
+
+/// Format a symbol with its details, coloring the kind and name by symbol kind if `color` is set.
+/// With a `root`, file paths under it are shown relative to it.
+pub fn format_symbol(symbol: &Symbol, path_registry: &PathRegistry, color: bool, root: Option<&Path>) -> String {
     // First check if the file_path_index seems valid
     let file_path_display = if symbol.context.file_path_index < path_registry.paths.len() {
-        path_registry.get_path(symbol.context.file_path_index).display().to_string()
+        display_path(path_registry.get_path(symbol.context.file_path_index), root).display().to_string()
     } else {
         format!("INVALID_PATH_INDEX({})", symbol.context.file_path_index)
     };
 
-    println!("{}: {} | File: {}:{} | Module: {} | Type: {:?} | Parents: {}",
-        if matches!(symbol.context.symbol_type, SymbolType::Class | SymbolType::NestedClass) { "CLASS" } else { "FUNCTION" },
-        symbol.name,
+    let kind = match symbol.context.symbol_type {
+        SymbolType::Class | SymbolType::NestedClass => "CLASS",
+        SymbolType::TypeAlias => "TYPE",
+        _ => "FUNCTION",
+    };
+    let (kind, name) = if color {
+        let symbol_color = symbol_color(&symbol.context.symbol_type);
+        (kind.with(symbol_color).to_string(), symbol.name.as_str().with(symbol_color).bold().to_string())
+    } else {
+        (kind.to_string(), symbol.name.clone())
+    };
+
+    format!("{}: {} | File: {}:{} | Module: {} | Type: {:?}{} | Parents: {}",
+        kind,
+        name,
         file_path_display,
         symbol.context.line_number,
         symbol.context.fully_qualified_module,
         symbol.context.symbol_type,
+        if symbol.context.is_abstract { " (abstract)" } else { "" },
         symbol.context.parent_context.iter()
             .map(|p| format!("{}:{}", p.name, p.line_number))
             .collect::<Vec<_>>()
             .join(", ")
-    );
+    )
+}
+
+/// File format for exported search results
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResultsFormat {
+    Json,
+    Csv,
+}
+
+impl ResultsFormat {
+    /// Pick the format from the file extension: `.csv` is CSV, anything else JSON
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("csv")) {
+            ResultsFormat::Csv
+        } else {
+            ResultsFormat::Json
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct ExportedResult<'a> {
+    name: &'a str,
+    file: String,
+    line: usize,
+    score: i64,
+}
+
+// Quote a CSV field when it contains a separator, quote or newline
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Write search results (name, file, line and score) in ranked order, for use in scripts
+pub fn export_results<W: Write>(
+    writer: &mut W,
+    results: &[(Symbol, i64)],
+    path_registry: &PathRegistry,
+    format: ResultsFormat,
+) -> anyhow::Result<()> {
+    let exported: Vec<ExportedResult> = results.iter()
+        .map(|(symbol, score)| ExportedResult {
+            name: &symbol.name,
+            file: path_registry.get_path(symbol.context.file_path_index).display().to_string(),
+            line: symbol.context.line_number,
+            score: *score,
+        })
+        .collect();
+
+    match format {
+        ResultsFormat::Json => {
+            serde_json::to_writer_pretty(&mut *writer, &exported)?;
+            writeln!(writer)?;
+        }
+        ResultsFormat::Csv => {
+            writeln!(writer, "name,file,line,score")?;
+            for result in &exported {
+                writeln!(writer, "{},{},{},{}", csv_field(result.name), csv_field(&result.file), result.line, result.score)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Search results clustered for display: a class with its matching methods, or a single
+/// result that is not a method
+#[derive(Debug)]
+pub struct ResultGroup<'a> {
+    /// Dotted parent chain of the methods (e.g. `Outer.Inner`); `None` for ungrouped results
+    pub class: Option<String>,
+    pub results: Vec<&'a (Symbol, i64)>,
+}
+
+/// Group method results under their class, keeping the rank order of each group's best
+/// result. Methods of same-named classes in different files or at different lines stay apart.
+pub fn group_results_by_class(results: &[(Symbol, i64)]) -> Vec<ResultGroup<'_>> {
+    let mut groups: Vec<ResultGroup> = Vec::new();
+    let mut class_groups: HashMap<(usize, usize, String), usize> = HashMap::new();
+    for result in results {
+        let context = &result.0.context;
+        let parent = context.parent_context.last()
+            .filter(|_| context.symbol_type == SymbolType::Method);
+        let Some(parent) = parent else {
+            groups.push(ResultGroup { class: None, results: vec![result] });
+            continue;
+        };
+        let class = context.parent_context.iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>()
+            .join(".");
+        let key = (context.file_path_index, parent.line_number, class.clone());
+        match class_groups.get(&key) {
+            Some(&index) => groups[index].results.push(result),
+            None => {
+                class_groups.insert(key, groups.len());
+                groups.push(ResultGroup { class: Some(class), results: vec![result] });
+            }
+        }
+    }
+    groups
+}
+
+/// Format grouped results: each class on its own line followed by its indented methods,
+/// and ungrouped results as `format_symbol` with their score
+pub fn format_grouped_results(
+    groups: &[ResultGroup],
+    path_registry: &PathRegistry,
+    color: bool,
+    root: Option<&Path>,
+) -> String {
+    let mut lines = Vec::new();
+    for group in groups {
+        match &group.class {
+            Some(class) => {
+                let first = &group.results[0].0;
+                let path = display_path(path_registry.get_path(first.context.file_path_index), root);
+                let class = if color {
+                    class.as_str().with(symbol_color(&SymbolType::Class)).bold().to_string()
+                } else {
+                    class.clone()
+                };
+                lines.push(format!("CLASS: {} | File: {}", class, path.display()));
+                for (symbol, score) in &group.results {
+                    let name = if color {
+                        symbol.name.as_str().with(symbol_color(&symbol.context.symbol_type)).to_string()
+                    } else {
+                        symbol.name.clone()
+                    };
+                    lines.push(format!("    {} | Line: {} | Score: {}", name, symbol.context.line_number, score));
+                }
+            }
+            None => {
+                for (symbol, score) in &group.results {
+                    lines.push(format_symbol(symbol, path_registry, color, root));
+                    lines.push(format!("  Score: {}", score));
+                }
+            }
+        }
+        lines.push(String::new());
+    }
+    lines.join("\n")
+}
+
+/// Print a symbol with its details
+pub fn print_symbol(symbol: &Symbol, path_registry: &PathRegistry) {
+    print_symbol_colored(symbol, path_registry, false, None);
+}
+
+/// Print a symbol with its details, optionally colorized by kind and with paths relative to `root`
+pub fn print_symbol_colored(symbol: &Symbol, path_registry: &PathRegistry, color: bool, root: Option<&Path>) {
+    println!("{}", format_symbol(symbol, path_registry, color, root));
 
     // If path index is very high, it might be an error, so add debug info
     if symbol.context.file_path_index > 1000 {  // Arbitrary threshold for suspicious indices
@@ -221,11 +913,122 @@ pub fn print_symbol(symbol: &Symbol, path_registry: &PathRegistry) {
     }
 }
 
+/// A user-supplied output line for each result, such as `{file}:{line}: {name}` for an
+/// editor's quickfix list. `{{` and `}}` stand for literal braces.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResultTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Placeholder {
+    Name,
+    Kind,
+    File,
+    Line,
+    Module,
+    Score,
+}
+
+impl ResultTemplate {
+    /// Every placeholder a template may use
+    pub const PLACEHOLDERS: &'static [&'static str] = &["name", "kind", "file", "line", "module", "score"];
+
+    /// Parse a template, rejecting unknown placeholders and unmatched braces up front so a
+    /// typo fails before the search rather than printing garbage for every result
+    pub fn parse(template: &str) -> anyhow::Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == '}' {
+                            closed = true;
+                            break;
+                        }
+                        name.push(c);
+                    }
+                    if !closed {
+                        anyhow::bail!("Unclosed '{{' in template {:?}", template);
+                    }
+                    let placeholder = match name.as_str() {
+                        "name" => Placeholder::Name,
+                        "kind" => Placeholder::Kind,
+                        "file" => Placeholder::File,
+                        "line" => Placeholder::Line,
+                        "module" => Placeholder::Module,
+                        "score" => Placeholder::Score,
+                        _ => anyhow::bail!(
+                            "Unknown placeholder {{{}}} in template {:?}; expected one of {}",
+                            name,
+                            template,
+                            Self::PLACEHOLDERS.iter().map(|p| format!("{{{}}}", p)).collect::<Vec<_>>().join(", "),
+                        ),
+                    };
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(TemplatePart::Placeholder(placeholder));
+                }
+                '}' => anyhow::bail!("Unmatched '}}' in template {:?}; use '}}}}' for a literal brace", template),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+        Ok(ResultTemplate { parts })
+    }
+
+    /// Expand the template for one symbol. `{score}` is empty without a score (e.g. when
+    /// listing rather than searching); with a `root`, file paths under it are relative.
+    pub fn render(&self, symbol: &Symbol, path_registry: &PathRegistry, score: Option<i64>, root: Option<&Path>) -> String {
+        let mut line = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(text) => line.push_str(text),
+                TemplatePart::Placeholder(Placeholder::Name) => line.push_str(&symbol.name),
+                TemplatePart::Placeholder(Placeholder::Kind) => line.push_str(symbol.context.symbol_type.kind_name()),
+                TemplatePart::Placeholder(Placeholder::File) => {
+                    let path = path_registry.get_path(symbol.context.file_path_index);
+                    line.push_str(&display_path(path, root).display().to_string());
+                }
+                TemplatePart::Placeholder(Placeholder::Line) => line.push_str(&symbol.context.line_number.to_string()),
+                TemplatePart::Placeholder(Placeholder::Module) => line.push_str(&symbol.context.module_path()),
+                TemplatePart::Placeholder(Placeholder::Score) => {
+                    if let Some(score) = score {
+                        line.push_str(&score.to_string());
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
-    use crate::symbols::{Symbol, SymbolContext, SymbolType, PathRegistry};
+    use crate::symbols::{ParentContext, Symbol, SymbolContext, SymbolType, PathRegistry};
     use std::collections::HashSet;
 
     fn create_test_data() -> (HashSet<Symbol>, HashSet<Symbol>, PathRegistry) {
@@ -245,10 +1048,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index1,
                 line_number: 10,
+                end_line_number: 10,
                 module: "file1".to_string(),
                 fully_qualified_module: "module1.file1".to_string(),
                 symbol_type: SymbolType::Function,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -257,10 +1061,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index2,
                 line_number: 20,
+                end_line_number: 20,
                 module: "file2".to_string(),
                 fully_qualified_module: "module2.file2".to_string(),
                 symbol_type: SymbolType::Function,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -270,10 +1075,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index1,
                 line_number: 5,
+                end_line_number: 5,
                 module: "file1".to_string(),
                 fully_qualified_module: "module1.file1".to_string(),
                 symbol_type: SymbolType::Class,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -282,10 +1088,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index2,
                 line_number: 15,
+                end_line_number: 15,
                 module: "file2".to_string(),
                 fully_qualified_module: "module2.file2".to_string(),
                 symbol_type: SymbolType::Class,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -319,4 +1126,630 @@ mod tests {
             println!("{:?} metrics for case-insensitive match: {:?}", algorithm, metrics);
         }
     }
+
+    #[test]
+    fn test_multi_term_queries_match_every_term() {
+        let (functions, classes, path_registry) = create_test_data();
+        assert_eq!(query_terms("  user   get "), vec!["user", "get"]);
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo] {
+            let (results, _) = search_symbols("class another", &functions, &classes, &path_registry, false, algorithm);
+            let names: Vec<_> = results.iter().map(|(s, _)| s.name.as_str()).collect();
+            assert_eq!(names, vec!["AnotherClass"], "Every term should have to match with {:?}", algorithm);
+
+            let (results, _) = search_symbols("test nonexistent", &functions, &classes, &path_registry, false, algorithm);
+            assert!(results.is_empty(), "An unmatched term should exclude everything with {:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_equal_scores_have_stable_order() {
+        let (functions, _, path_registry) = create_test_data();
+        let template = functions.iter().next().unwrap().clone();
+        let make = |name: &str, file_path_index: usize, line_number: usize| {
+            let mut symbol = template.clone();
+            symbol.name = name.to_string();
+            symbol.context.file_path_index = file_path_index;
+            symbol.context.line_number = line_number;
+            symbol
+        };
+        let expected = vec![("handler", 0, 3), ("handler", 0, 40), ("handler", 1, 7)];
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo, SearchAlgorithm::Regex] {
+            // Fresh sets iterate in a different order each time
+            for _ in 0..10 {
+                let functions: HashSet<Symbol> = [make("handler", 1, 7), make("handler", 0, 40), make("handler", 0, 3)]
+                    .into_iter()
+                    .collect();
+                let (results, _) = search_symbols("handler", &functions, &HashSet::new(), &path_registry, false, algorithm);
+                let found: Vec<_> = results.iter()
+                    .map(|(s, _)| (s.name.as_str(), s.context.file_path_index, s.context.line_number))
+                    .collect();
+                assert_eq!(found, expected, "Ties should be ordered by file and line with {:?}", algorithm);
+            }
+        }
+
+        // Among equal scores names come first, and better scores still lead
+        let mut results = vec![
+            (make("beta", 0, 1), 50),
+            (make("alpha", 1, 1), 50),
+            (make("gamma", 0, 9), 80),
+        ];
+        sort_results(&mut results);
+        let names: Vec<_> = results.iter().map(|(s, _)| s.name.as_str()).collect();
+        assert_eq!(names, vec!["gamma", "alpha", "beta"]);
+    }
+
+    #[test]
+    fn test_order_results() {
+        let mut path_registry = PathRegistry::new();
+        let b_file = path_registry.register_path(PathBuf::from("/project/b.py"));
+        let a_file = path_registry.register_path(PathBuf::from("/project/a.py"));
+        let (functions, _, _) = create_test_data();
+        let template = functions.iter().next().unwrap().clone();
+        let make = |name: &str, file_path_index: usize, line_number: usize| {
+            let mut symbol = template.clone();
+            symbol.name = name.to_string();
+            symbol.context.file_path_index = file_path_index;
+            symbol.context.line_number = line_number;
+            symbol
+        };
+        let results = vec![
+            (make("load", b_file, 20), 90),
+            (make("Save", a_file, 5), 50),
+            (make("delete", a_file, 30), 50),
+            (make("load", a_file, 10), 70),
+        ];
+        let order = |order: ResultOrder| -> Vec<(String, String, usize)> {
+            let mut results = results.clone();
+            order_results(&mut results, order, &path_registry);
+            results.iter()
+                .map(|(s, _)| (s.name.clone(), path_registry.get_path(s.context.file_path_index).display().to_string(), s.context.line_number))
+                .collect()
+        };
+        let entry = |name: &str, file: &str, line: usize| (name.to_string(), file.to_string(), line);
+
+        assert_eq!(order(ResultOrder::Score), vec![
+            entry("load", "/project/b.py", 20),
+            entry("load", "/project/a.py", 10),
+            entry("delete", "/project/a.py", 30),
+            entry("Save", "/project/a.py", 5),
+        ]);
+        // Names compare without case; equal names go by file path, not registration order
+        assert_eq!(order(ResultOrder::Name), vec![
+            entry("delete", "/project/a.py", 30),
+            entry("load", "/project/a.py", 10),
+            entry("load", "/project/b.py", 20),
+            entry("Save", "/project/a.py", 5),
+        ]);
+        assert_eq!(order(ResultOrder::File), vec![
+            entry("Save", "/project/a.py", 5),
+            entry("load", "/project/a.py", 10),
+            entry("delete", "/project/a.py", 30),
+            entry("load", "/project/b.py", 20),
+        ]);
+
+        assert_eq!("File".parse(), Ok(ResultOrder::File));
+        assert!("size".parse::<ResultOrder>().is_err());
+    }
+
+    #[test]
+    fn test_max_results_keeps_the_best() {
+        let (functions, classes, path_registry) = create_test_data();
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo, SearchAlgorithm::Regex] {
+            let query = if algorithm == SearchAlgorithm::Regex { "(?i)class|function" } else { "t" };
+            let (all, _) = search_symbols_with_options(query, &functions, &classes, &path_registry, false, algorithm, &SearchOptions::default());
+            assert!(all.len() > 2, "Query should match several symbols with {:?}", algorithm);
+
+            let options = SearchOptions { max_results: Some(2), ..Default::default() };
+            let (top, metrics) = search_symbols_with_options(query, &functions, &classes, &path_registry, false, algorithm, &options);
+            assert_eq!(top, all[..2].to_vec(), "Top results should match the head of the full list with {:?}", algorithm);
+            assert_eq!(metrics.results_count, all.len(), "Every match is still counted with {:?}", algorithm);
+        }
+
+        let options = SearchOptions { max_results: Some(1), ..Default::default() };
+        let (top, _) = search_symbols_with_options("module2/", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+        assert_eq!(top.len(), 1, "Path queries are limited too");
+
+        let mut results = vec![(functions.iter().next().unwrap().clone(), 1)];
+        sort_top_results(&mut results, Some(0));
+        assert!(results.is_empty());
+    }
+
+    #[test]
+    fn test_class_qualified_query_selects_method() {
+        let (functions, _, path_registry) = create_test_data();
+        let template = functions.iter().next().unwrap().clone();
+        let method = |class: &str, line_number: usize| {
+            let mut symbol = template.clone();
+            symbol.name = "save".to_string();
+            symbol.context.symbol_type = SymbolType::Method;
+            symbol.context.line_number = line_number;
+            symbol.context.parent_context = vec![ParentContext {
+                name: class.to_string(),
+                symbol_type: SymbolType::Class,
+                line_number: line_number - 1,
+            }];
+            symbol
+        };
+        let functions: HashSet<Symbol> = [method("Order", 5), method("User", 20)].into_iter().collect();
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo] {
+            for query in ["User.save", "user.SAVE"] {
+                let (results, _) = search_symbols(query, &functions, &HashSet::new(), &path_registry, false, algorithm);
+                let classes: Vec<&str> = results.iter()
+                    .map(|(s, _)| s.context.parent_context[0].name.as_str())
+                    .collect();
+                assert_eq!(classes.first(), Some(&"User"), "{} with {:?}", query, algorithm);
+                assert!(!classes.contains(&"Order"), "{} with {:?}", query, algorithm);
+            }
+        }
+
+        let user_save = method("User", 20);
+        assert!(!is_class_qualified_match(&user_save, "Order.save"));
+        assert!(!is_class_qualified_match(&user_save, "save"));
+        assert!(!is_class_qualified_match(&user_save, "Outer.User.save"));
+        let mut nested = user_save.clone();
+        nested.context.parent_context.insert(0, ParentContext {
+            name: "Outer".to_string(),
+            symbol_type: SymbolType::Class,
+            line_number: 1,
+        });
+        assert!(is_class_qualified_match(&nested, "Outer.User.save"));
+        assert!(is_class_qualified_match(&nested, "User.save"));
+    }
+
+    #[test]
+    fn test_path_query_returns_symbols_from_matching_file() {
+        let (functions, classes, path_registry) = create_test_data();
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo] {
+            let (results, _) = search_symbols("module1/file1", &functions, &classes, &path_registry, false, algorithm);
+            let names: Vec<&str> = results.iter().map(|(s, _)| s.name.as_str()).collect();
+            // All symbols of the file, in line order since they share the path score
+            assert_eq!(names, vec!["TestClass", "test_function"], "{:?}", algorithm);
+        }
+
+        // Both files match "module/", but the closer path ranks first
+        let (results, _) = search_symbols("module2/file", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
+        assert_eq!(results.first().map(|(s, _)| s.context.file_path_index), Some(1));
+        assert!(results.windows(2).all(|w| w[0].1 >= w[1].1));
+
+        let (results, _) = search_symbols("nowhere/xyz", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
+        assert!(results.is_empty());
+
+        assert!(is_path_query("auth/login"));
+        assert!(!is_path_query("login"));
+    }
+
+    #[test]
+    fn test_regex_algorithm_dispatch() {
+        let (functions, classes, path_registry) = create_test_data();
+
+        let (results, _) = search_symbols("^test_", &functions, &classes, &path_registry, false, SearchAlgorithm::Regex);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.name, "test_function");
+
+        // Invalid patterns match nothing rather than panicking
+        let (results, _) = search_symbols("test_(", &functions, &classes, &path_registry, false, SearchAlgorithm::Regex);
+        assert!(results.is_empty());
+
+        assert_eq!("regex".parse::<SearchAlgorithm>(), Ok(SearchAlgorithm::Regex));
+    }
+
+    #[test]
+    fn test_format_symbol_color() {
+        let (functions, _, path_registry) = create_test_data();
+        let symbol = functions.iter().find(|s| s.name == "test_function").unwrap();
+
+        let plain = format_symbol(symbol, &path_registry, false, None);
+        assert!(plain.starts_with("FUNCTION: test_function | File: /test/module1/file1.py:10"));
+        assert!(!plain.contains('\u{1b}'), "Plain output must not contain escape codes");
+        assert!(!plain.contains("(abstract)"));
+
+        let mut abstract_symbol = symbol.clone();
+        abstract_symbol.context.is_abstract = true;
+        assert!(format_symbol(&abstract_symbol, &path_registry, false, None).contains("Type: Function (abstract)"));
+
+        let colored = format_symbol(symbol, &path_registry, true, None);
+        assert!(colored.contains('\u{1b}'));
+        assert!(colored.contains("test_function"));
+
+        let relative = format_symbol(symbol, &path_registry, false, Some(Path::new("/test")));
+        assert!(relative.contains("| File: module1/file1.py:10 |"));
+        // Paths outside the root are shown in full
+        let outside = format_symbol(symbol, &path_registry, false, Some(Path::new("/elsewhere")));
+        assert!(outside.contains("| File: /test/module1/file1.py:10 |"));
+        assert_eq!(display_path(Path::new("./pkg/a.py"), Some(Path::new("."))), Path::new("pkg/a.py"));
+
+        // Classes, functions and methods are distinguishable
+        assert_ne!(symbol_color(&SymbolType::Class), symbol_color(&SymbolType::Function));
+        assert_ne!(symbol_color(&SymbolType::Function), symbol_color(&SymbolType::Method));
+        assert_ne!(symbol_color(&SymbolType::Class), symbol_color(&SymbolType::Method));
+        assert!(should_colorize(false) == (std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()));
+        assert!(!should_colorize(true));
+    }
+
+    #[test]
+    fn test_search_algorithm_next_cycles() {
+        let mut algorithm = SearchAlgorithm::Skim;
+        let mut seen = Vec::new();
+        for _ in 0..3 {
+            seen.push(algorithm.to_string());
+            algorithm = algorithm.next();
+        }
+        assert_eq!(seen, vec!["skim", "nucleo", "regex"]);
+        assert_eq!(algorithm, SearchAlgorithm::Skim);
+    }
+
+    #[test]
+    fn test_export_results() -> anyhow::Result<()> {
+        let (functions, _, mut path_registry) = create_test_data();
+        let mut symbol = functions.iter().find(|s| s.name == "test_function").unwrap().clone();
+        symbol.context.file_path_index = path_registry.register_path(PathBuf::from("/test/odd, \"name\".py"));
+        let results = vec![(symbol, 120)];
+
+        let mut json = Vec::new();
+        export_results(&mut json, &results, &path_registry, ResultsFormat::Json)?;
+        let json: serde_json::Value = serde_json::from_slice(&json)?;
+        assert_eq!(json, serde_json::json!([
+            { "name": "test_function", "file": "/test/odd, \"name\".py", "line": 10, "score": 120 }
+        ]));
+
+        let mut csv = Vec::new();
+        export_results(&mut csv, &results, &path_registry, ResultsFormat::Csv)?;
+        assert_eq!(String::from_utf8(csv)?, "name,file,line,score\ntest_function,\"/test/odd, \"\"name\"\".py\",10,120\n");
+
+        assert_eq!(ResultsFormat::from_path(Path::new("out.CSV")), ResultsFormat::Csv);
+        assert_eq!(ResultsFormat::from_path(Path::new("out.json")), ResultsFormat::Json);
+        Ok(())
+    }
+
+    #[test]
+    fn test_result_template_expansion() -> anyhow::Result<()> {
+        let (functions, _, path_registry) = create_test_data();
+        let symbol = functions.iter().find(|s| s.name == "test_function").unwrap();
+
+        let quickfix = ResultTemplate::parse("{file}:{line}: {name}")?;
+        assert_eq!(quickfix.render(symbol, &path_registry, Some(42), None), "/test/module1/file1.py:10: test_function");
+        assert_eq!(quickfix.render(symbol, &path_registry, None, Some(Path::new("/test"))), "module1/file1.py:10: test_function");
+
+        let everything = ResultTemplate::parse("{{{kind}}} {module}.{name} [{score}]")?;
+        assert_eq!(everything.render(symbol, &path_registry, Some(42), None), "{function} module1.file1.file1.test_function [42]");
+        assert_eq!(everything.render(symbol, &path_registry, None, None), "{function} module1.file1.file1.test_function []");
+
+        let error = ResultTemplate::parse("{file}:{lineno}").unwrap_err().to_string();
+        assert!(error.contains("{lineno}") && error.contains("{line}"), "{}", error);
+        assert!(ResultTemplate::parse("{name").is_err());
+        assert!(ResultTemplate::parse("name}").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_suggest_symbol_names() {
+        let (functions, classes, _) = create_test_data();
+
+        assert_eq!(suggest_symbol_names("tset_function", &functions, &classes, MAX_SUGGESTIONS), vec!["test_function"]);
+        assert_eq!(suggest_symbol_names("anotherclas", &functions, &classes, MAX_SUGGESTIONS), vec!["AnotherClass"]);
+        assert!(suggest_symbol_names("completely_unrelated", &functions, &classes, MAX_SUGGESTIONS).is_empty());
+        assert!(suggest_symbol_names("tset_function", &functions, &classes, 0).is_empty());
+    }
+
+    #[test]
+    fn test_split_identifier_words() {
+        assert_eq!(split_identifier_words("depth_first_search"), vec!["depth", "first", "search"]);
+        assert_eq!(split_identifier_words("GetUser"), vec!["Get", "User"]);
+        assert_eq!(split_identifier_words("getUserName"), vec!["get", "User", "Name"]);
+        assert_eq!(split_identifier_words("HTTPServer"), vec!["HTTP", "Server"]);
+        assert_eq!(split_identifier_words("__init__"), vec!["init"]);
+        assert_eq!(split_identifier_words("parse_v2Config"), vec!["parse", "v", "2", "Config"]);
+    }
+
+    #[test]
+    fn test_word_initials_score() {
+        assert_eq!(word_initials_score("depth_first_search", "dfs"), Some(INITIALS_FULL_MATCH_SCORE));
+        assert_eq!(word_initials_score("GetUser", "gu"), Some(INITIALS_FULL_MATCH_SCORE));
+        assert_eq!(word_initials_score("get_user_name", "gu"), Some(INITIALS_PREFIX_MATCH_SCORE));
+        assert_eq!(word_initials_score("digest_utils", "gu"), None);
+        assert_eq!(word_initials_score("GetUser", "g"), None);
+    }
+
+    #[test]
+    fn test_match_word_initials_ranking() {
+        let mut path_registry = PathRegistry::new();
+        let file_path_index = path_registry.register_path(PathBuf::from("/test/module/file.py"));
+        let make = |name: &str, line_number: usize, symbol_type: SymbolType| Symbol {
+            name: name.to_string(),
+            context: SymbolContext {
+                file_path_index,
+                line_number,
+                end_line_number: line_number,
+                module: "file".to_string(),
+                fully_qualified_module: "module".to_string(),
+                symbol_type,
+                ..Default::default()
+            },
+        };
+        let functions: HashSet<Symbol> = [
+            make("digest_utils", 1, SymbolType::Function),
+            make("depth_first_search", 2, SymbolType::Function),
+            make("dataframe_fields", 3, SymbolType::Function),
+        ].into_iter().collect();
+        let classes: HashSet<Symbol> = [
+            make("GetUser", 4, SymbolType::Class),
+            make("GlobalUsage", 5, SymbolType::Class),
+        ].into_iter().collect();
+        let options = SearchOptions { match_word_initials: true, ..Default::default() };
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo] {
+            let (results, _) = search_symbols_with_options("dfs", &functions, &classes, &path_registry, false, algorithm, &options);
+            assert_eq!(results[0].0.name, "depth_first_search", "snake_case initials with {:?}", algorithm);
+
+            let (results, _) = search_symbols_with_options("gu", &functions, &classes, &path_registry, false, algorithm, &options);
+            let top: HashSet<&str> = results.iter().take(2).map(|(s, _)| s.name.as_str()).collect();
+            assert_eq!(top, ["GetUser", "GlobalUsage"].into_iter().collect(), "CamelCase initials with {:?}", algorithm);
+        }
+
+        // Words split up front rank the same as splitting per query
+        let words = IdentifierWords::build(functions.iter().chain(classes.iter()));
+        let options = SearchOptions { identifier_words: Some(Arc::new(words)), ..options };
+        let (results, _) = search_symbols_with_options("dfs", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+        assert_eq!(results[0].0.name, "depth_first_search");
+    }
+
+    #[test]
+    fn test_identifier_words_split_mixed_case_names() {
+        let symbol = |name: &str| Symbol {
+            name: name.to_string(),
+            context: SymbolContext {
+                file_path_index: 0,
+                line_number: 1,
+                end_line_number: 1,
+                module: "file".to_string(),
+                fully_qualified_module: String::new(),
+                symbol_type: SymbolType::Function,
+                ..Default::default()
+            },
+        };
+        let symbols = [symbol("parseHTTPResponse_v2"), symbol("get_user"), symbol("GetUserName")];
+        let words = IdentifierWords::build(&symbols);
+
+        let as_strs = |name: &str| -> Vec<String> {
+            words.get(name).unwrap().iter().map(|word| word.to_string()).collect()
+        };
+        assert_eq!(as_strs("parseHTTPResponse_v2"), vec!["parse", "http", "response", "v", "2"]);
+        assert_eq!(as_strs("get_user"), vec!["get", "user"]);
+        assert_eq!(as_strs("GetUserName"), vec!["get", "user", "name"]);
+        assert!(words.get("missing").is_none());
+        // Shared words are stored once
+        assert!(Arc::ptr_eq(&words.get("get_user").unwrap()[1], &words.get("GetUserName").unwrap()[1]));
+    }
+
+    #[test]
+    fn test_match_kind_in_name() {
+        let (mut functions, classes, path_registry) = create_test_data();
+        let options = SearchOptions { match_kind_in_name: true, ..Default::default() };
+
+        // A name that doesn't contain its kind word, so that word only matches with the option
+        let mut get_user = functions.iter().find(|f| f.name == "test_function").unwrap().clone();
+        get_user.name = "get_user".to_string();
+        functions.insert(get_user);
+
+        let (results, _) = search_symbols("get_user function", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
+        assert!(results.is_empty(), "Kind should not be matched by default");
+        let (results, _) = search_symbols_with_options("get_user function", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0.name, "get_user");
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo] {
+            // With the option the kind word helps select the class
+            let (results, _) = search_symbols_with_options("testclass class", &functions, &classes, &path_registry, false, algorithm, &options);
+            assert!(!results.is_empty(), "Should match kind in haystack with {:?}", algorithm);
+            assert_eq!(results[0].0.name, "TestClass", "Displayed name should stay clean with {:?}", algorithm);
+
+            // Plain-name queries still work, including exact matches
+            let (results, _) = search_symbols_with_options("test_function", &functions, &classes, &path_registry, false, algorithm, &options);
+            assert!(!results.is_empty(), "Plain queries should still match with {:?}", algorithm);
+            assert_eq!(results[0].0.name, "test_function");
+        }
+    }
+
+    #[test]
+    fn test_match_qualified_name_prefers_final_segment() {
+        let mut path_registry = PathRegistry::new();
+        let file_path_index = path_registry.register_path(PathBuf::from("/test/file.py"));
+        let make = |name: &str, line_number: usize, module: &str, fully_qualified_module: &str| Symbol {
+            name: name.to_string(),
+            context: SymbolContext {
+                file_path_index,
+                line_number,
+                end_line_number: line_number,
+                module: module.to_string(),
+                fully_qualified_module: fully_qualified_module.to_string(),
+                symbol_type: SymbolType::Function,
+                ..Default::default()
+            },
+        };
+        // Both matchers prefer "target" at the very start of the haystack, so without the
+        // final-segment bonus target.utils.get_config would outrank pkg.api.target_cache
+        let functions: HashSet<Symbol> = [
+            make("target_cache", 1, "api", "pkg"),
+            make("get_config", 2, "utils", "target"),
+        ].into_iter().collect();
+        let classes = HashSet::new();
+        let options = SearchOptions { match_qualified_name: true, ..Default::default() };
+
+        assert_eq!(match_haystack(&make("target_cache", 1, "api", "pkg"), &options), "pkg.api.target_cache");
+        assert_eq!(final_segment_bonus("pkg.api.target_cache", &[8, 9, 10, 11, 12, 13]), 6 * FINAL_SEGMENT_MATCH_BONUS);
+        assert_eq!(final_segment_bonus("target.utils.get_config", &[0, 1, 2, 3, 4, 5]), 0);
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo] {
+            let (results, _) = search_symbols_with_options("target", &functions, &classes, &path_registry, false, algorithm, &options);
+            assert_eq!(results.len(), 2, "Module path should still match with {:?}", algorithm);
+            assert_eq!(results[0].0.name, "target_cache", "Final segment should win with {:?}", algorithm);
+        }
+    }
+
+    #[test]
+    fn test_fold_diacritics() {
+        assert_eq!(fold_diacritics("café_crème"), "cafe_creme");
+        // Decomposed form: "e" followed by a combining acute accent
+        assert_eq!(fold_diacritics("cafe\u{0301}"), "cafe");
+        assert_eq!(fold_diacritics("plain_name"), "plain_name");
+        // Stacked marks, and scripts other than Latin
+        assert_eq!(fold_diacritics("Nguyễn"), "Nguyen");
+        assert_eq!(fold_diacritics("λέξη"), "λεξη");
+
+        let mut path_registry = PathRegistry::new();
+        let file_path_index = path_registry.register_path(PathBuf::from("/test/menu.py"));
+        let symbol = Symbol {
+            name: "café_menu".to_string(),
+            context: SymbolContext {
+                file_path_index,
+                line_number: 1,
+                end_line_number: 1,
+                module: "menu".to_string(),
+                fully_qualified_module: "".to_string(),
+                symbol_type: SymbolType::Function,
+                ..Default::default()
+            },
+        };
+        let functions: HashSet<Symbol> = [symbol].into_iter().collect();
+        let classes = HashSet::new();
+        let options = SearchOptions { fold_diacritics: true, ..Default::default() };
+
+        let (results, _) = search_symbols("cafe_m", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim);
+        assert!(results.is_empty(), "Skim should not fold diacritics by default");
+
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo, SearchAlgorithm::Regex] {
+            let (results, _) = search_symbols_with_options("cafe_m", &functions, &classes, &path_registry, false, algorithm, &options);
+            assert_eq!(results.len(), 1, "Should fold diacritics with {:?}", algorithm);
+            assert_eq!(results[0].0.name, "café_menu", "Displayed name should keep its accents");
+        }
+    }
+
+    #[test]
+    fn test_group_results_by_class() {
+        let (functions, _, path_registry) = create_test_data();
+        let base = functions.iter().find(|s| s.name == "test_function").unwrap();
+        let make = |name: &str, line_number: usize, symbol_type: SymbolType, parents: &[(&str, usize)]| {
+            let mut symbol = base.clone();
+            symbol.name = name.to_string();
+            symbol.context.line_number = line_number;
+            symbol.context.symbol_type = symbol_type;
+            symbol.context.parent_context = parents.iter()
+                .map(|&(name, line_number)| ParentContext { name: name.to_string(), symbol_type: SymbolType::Class, line_number })
+                .collect();
+            symbol
+        };
+        let results = vec![
+            (make("__init__", 2, SymbolType::Method, &[("Alpha", 1)]), 90),
+            (make("init_app", 30, SymbolType::Function, &[]), 80),
+            (make("__init__", 12, SymbolType::Method, &[("Outer", 10), ("Inner", 11)]), 70),
+            (make("init_db", 5, SymbolType::Method, &[("Alpha", 1)]), 60),
+            // A same-named class elsewhere in the file is a different group
+            (make("__init__", 41, SymbolType::Method, &[("Alpha", 40)]), 50),
+        ];
+
+        let groups = group_results_by_class(&results);
+        let summary: Vec<(Option<&str>, Vec<usize>)> = groups.iter()
+            .map(|g| (g.class.as_deref(), g.results.iter().map(|(s, _)| s.context.line_number).collect()))
+            .collect();
+        assert_eq!(summary, vec![
+            (Some("Alpha"), vec![2, 5]),
+            (None, vec![30]),
+            (Some("Outer.Inner"), vec![12]),
+            (Some("Alpha"), vec![41]),
+        ]);
+
+        let output = format_grouped_results(&groups[..2], &path_registry, false, Some(Path::new("/test")));
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "CLASS: Alpha | File: module1/file1.py");
+        assert_eq!(lines[1], "    __init__ | Line: 2 | Score: 90");
+        assert_eq!(lines[2], "    init_db | Line: 5 | Score: 60");
+        assert_eq!(lines[3], "");
+        assert!(lines[4].starts_with("FUNCTION: init_app | File: module1/file1.py:30"));
+        assert_eq!(lines[5], "  Score: 80");
+    }
+
+    #[test]
+    fn test_restrict_search_to_kinds() {
+        let (mut functions, classes, path_registry) = create_test_data();
+        let mut method = functions.iter().find(|s| s.name == "test_function").unwrap().clone();
+        method.name = "test_method".to_string();
+        method.context.line_number = 6;
+        method.context.symbol_type = SymbolType::Method;
+        method.context.parent_context = vec![ParentContext {
+            name: "TestClass".to_string(),
+            symbol_type: SymbolType::Class,
+            line_number: 5,
+        }];
+        functions.insert(method);
+
+        let options = SearchOptions {
+            kinds: Some([SymbolType::Method].into_iter().collect()),
+            ..Default::default()
+        };
+        for algorithm in [SearchAlgorithm::Skim, SearchAlgorithm::Nucleo, SearchAlgorithm::Regex] {
+            let query = if algorithm == SearchAlgorithm::Regex { "(?i)test" } else { "test" };
+            let (results, _) = search_symbols_with_options(query, &functions, &classes, &path_registry, false, algorithm, &options);
+            let names: Vec<&str> = results.iter().map(|(s, _)| s.name.as_str()).collect();
+            assert_eq!(names, vec!["test_method"], "Only methods should match with {:?}", algorithm);
+        }
+
+        // Path queries list whole files, but still only the requested kinds
+        let (results, metrics) = search_symbols_with_options("module1/", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+        assert_eq!(results.len(), 1);
+        assert_eq!(metrics.results_count, 1);
+
+        assert_eq!("nested-function".parse(), Ok(SymbolType::NestedFunction));
+        assert_eq!("Method".parse(), Ok(SymbolType::Method));
+        assert!("module".parse::<SymbolType>().is_err());
+    }
+
+    #[test]
+    fn test_skim_presets_rank_word_boundaries() {
+        let (mut functions, classes, path_registry) = create_test_data();
+        let template = functions.iter().find(|f| f.name == "test_function").unwrap().clone();
+        for (line_number, name) in [(30, "phash"), (31, "parse_html")] {
+            let mut symbol = template.clone();
+            symbol.name = name.to_string();
+            symbol.context.line_number = line_number;
+            functions.insert(symbol);
+        }
+        let top = |preset: SkimPreset| {
+            let options = SearchOptions { skim_preset: preset, ..Default::default() };
+            let (results, _) = search_symbols_with_options("ph", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+            results[0].0.name.clone()
+        };
+
+        // The default weights favour the contiguous "ph", the heavier word-start bonus
+        // favours the initials of parse_html
+        assert_eq!(top(SkimPreset::Default), "phash");
+        assert_eq!(top(SkimPreset::WordBoundaryHeavy), "parse_html");
+
+        assert_eq!("word-boundary-heavy".parse(), Ok(SkimPreset::WordBoundaryHeavy));
+        assert_eq!(SkimPreset::Default.to_string(), "default");
+        assert!("fastest".parse::<SkimPreset>().is_err());
+    }
+
+    #[test]
+    fn test_top_result_location() {
+        let (functions, classes, path_registry) = create_test_data();
+        let options = SearchOptions::default();
+        let (results, _) = search_symbols_with_options("another", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+        assert_eq!(top_result_location(&results, &path_registry, None).as_deref(), Some("/test/module2/file2.py:20"));
+        assert_eq!(
+            top_result_location(&results, &path_registry, Some(Path::new("/test"))).as_deref(),
+            Some("module2/file2.py:20")
+        );
+
+        // Search options still apply, so restricting to classes jumps to AnotherClass instead
+        let options = SearchOptions { kinds: Some([SymbolType::Class].into_iter().collect()), ..Default::default() };
+        let (results, _) = search_symbols_with_options("another", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+        assert_eq!(top_result_location(&results, &path_registry, None).as_deref(), Some("/test/module2/file2.py:15"));
+
+        let (results, _) = search_symbols_with_options("zzz", &functions, &classes, &path_registry, false, SearchAlgorithm::Skim, &options);
+        assert_eq!(top_result_location(&results, &path_registry, None), None);
+    }
 }
\ No newline at end of file
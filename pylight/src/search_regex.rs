@@ -0,0 +1,174 @@
+use std::collections::HashSet;
+use std::time::Instant;
+use regex::Regex;
+use crate::symbols::{Symbol, PathRegistry};
+use crate::search::{match_haystack, sort_top_results, SearchMetrics, SearchOptions};
+
+/// Score given when the regex matches the whole name
+const REGEX_FULL_MATCH_SCORE: i64 = 1000;
+
+/// Compile a search query as a regex. Matching is case-sensitive unless the pattern says
+/// otherwise (e.g. with `(?i)`), since regex users want precise control.
+pub fn compile_query(query: &str) -> Result<Regex, regex::Error> {
+    Regex::new(query)
+}
+
+/// Score a match: matches covering more of the name, and starting earlier in it, rank higher
+fn regex_match_score(haystack: &str, start: usize, end: usize) -> i64 {
+    if start == 0 && end == haystack.len() {
+        return REGEX_FULL_MATCH_SCORE;
+    }
+    let coverage = ((end - start) * 500 / haystack.len().max(1)) as i64;
+    let position = 100 - start.min(100) as i64;
+    coverage + position
+}
+
+pub fn search_symbols_regex(
+    query: &str,
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+    debug: bool,
+    options: &SearchOptions,
+) -> Result<(Vec<(Symbol, i64)>, SearchMetrics), regex::Error> {
+    let mut metrics = SearchMetrics::default();
+    let start_total = Instant::now();
+
+    let matcher_start = Instant::now();
+    let regex = compile_query(query)?;
+    metrics.matcher_init_time_ms = matcher_start.elapsed().as_millis();
+
+    let mut results = Vec::new();
+
+    // Track symbols we've already added to avoid duplicates
+    let mut seen_symbols = HashSet::new();
+
+    let search_start = Instant::now();
+
+    for symbol in functions.iter().chain(classes.iter()) {
+        if !options.includes_kind(&symbol.context.symbol_type) {
+            continue;
+        }
+        let haystack = match_haystack(symbol, options);
+        let Some(m) = regex.find(&haystack) else {
+            continue;
+        };
+        let score = regex_match_score(&haystack, m.start(), m.end());
+
+        // Create a unique key for this symbol (name + line number + file path)
+        let symbol_key = format!("{}:{}:{}",
+            symbol.name,
+            symbol.context.line_number,
+            symbol.context.file_path_index);
+
+        if seen_symbols.insert(symbol_key) {
+            if debug {
+                println!("{}: {} | Score: {} | File: {}:{} | Module: {} | Type: {:?}",
+                    symbol.context.symbol_type.kind_name().to_uppercase(),
+                    symbol.name,
+                    score,
+                    path_registry.get_path(symbol.context.file_path_index).display(),
+                    symbol.context.line_number,
+                    symbol.context.fully_qualified_module,
+                    symbol.context.symbol_type,
+                );
+            }
+            results.push((symbol.clone(), score));
+        }
+    }
+
+    metrics.search_time_ms = search_start.elapsed().as_millis();
+
+    // Sort by score (highest first)
+    metrics.results_count = results.len();
+    let sort_start = Instant::now();
+    sort_top_results(&mut results, options.max_results);
+    metrics.sort_time_ms = sort_start.elapsed().as_millis();
+
+    metrics.total_time_ms = start_total.elapsed().as_millis();
+
+    Ok((results, metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+    use crate::symbols::{Symbol, SymbolContext, SymbolType, PathRegistry};
+    use std::collections::HashSet;
+
+    fn create_test_data() -> (HashSet<Symbol>, HashSet<Symbol>, PathRegistry) {
+        let mut path_registry = PathRegistry::new();
+        let file_path_index = path_registry.register_path(PathBuf::from("/test/module1/file1.py"));
+
+        let make = |name: &str, line_number: usize, symbol_type: SymbolType| Symbol {
+            name: name.to_string(),
+            context: SymbolContext {
+                file_path_index,
+                line_number,
+                end_line_number: line_number,
+                module: "file1".to_string(),
+                fully_qualified_module: "module1".to_string(),
+                symbol_type,
+                ..Default::default()
+            },
+        };
+
+        let functions: HashSet<Symbol> = [
+            make("get_user", 1, SymbolType::Function),
+            make("get", 2, SymbolType::Function),
+            make("forget_user", 3, SymbolType::Function),
+            make("foo_helper", 4, SymbolType::Function),
+            make("set_bar", 5, SymbolType::Function),
+        ].into_iter().collect();
+        let classes: HashSet<Symbol> = [
+            make("Foo", 6, SymbolType::Class),
+            make("Baz", 7, SymbolType::Class),
+        ].into_iter().collect();
+
+        (functions, classes, path_registry)
+    }
+
+    fn names(results: &[(Symbol, i64)]) -> Vec<&str> {
+        results.iter().map(|(s, _)| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_regex_anchored_pattern() {
+        let (functions, classes, path_registry) = create_test_data();
+
+        let (results, _) = search_symbols_regex("^get", &functions, &classes, &path_registry, false, &SearchOptions::default()).unwrap();
+
+        // forget_user contains "get" but not at the start
+        assert_eq!(names(&results), vec!["get", "get_user"]);
+        assert_eq!(results[0].1, REGEX_FULL_MATCH_SCORE);
+        assert!(results[1].1 < REGEX_FULL_MATCH_SCORE);
+    }
+
+    #[test]
+    fn test_regex_alternation_pattern() {
+        let (functions, classes, path_registry) = create_test_data();
+
+        let (results, _) = search_symbols_regex("foo|bar", &functions, &classes, &path_registry, false, &SearchOptions::default()).unwrap();
+
+        // Case-sensitive by default, so the class Foo is not matched
+        let mut found = names(&results);
+        found.sort();
+        assert_eq!(found, vec!["foo_helper", "set_bar"]);
+        // With equal coverage, a match at the start of the name outranks one at the end
+        assert!(regex_match_score("foo_bar", 0, 3) > regex_match_score("bar_foo", 4, 7));
+
+        let (results, _) = search_symbols_regex("(?i)^(foo|baz)$", &functions, &classes, &path_registry, false, &SearchOptions::default()).unwrap();
+        let mut found = names(&results);
+        found.sort();
+        assert_eq!(found, vec!["Baz", "Foo"]);
+    }
+
+    #[test]
+    fn test_regex_invalid_pattern_is_an_error() {
+        let (functions, classes, path_registry) = create_test_data();
+
+        let result = search_symbols_regex("get_(user", &functions, &classes, &path_registry, false, &SearchOptions::default());
+        assert!(result.is_err());
+    }
+}
@@ -1,8 +1,80 @@
 use std::collections::HashSet;
 use std::time::Instant;
-use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use fuzzy_matcher::{skim::{SkimMatcherV2, SkimScoreConfig}, FuzzyMatcher};
 use crate::symbols::{Symbol, PathRegistry};
-use crate::search::SearchMetrics;
+use crate::search::{apply_word_initials, final_segment_bonus, is_class_qualified_match, match_haystack, query_terms, sort_top_results, SearchMetrics, SearchOptions};
+
+/// Named sets of Skim scoring weights
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SkimPreset {
+    /// The matcher's own weights
+    #[default]
+    Default,
+    /// Raise the bonus for matching at the start of a word (after `_`, `.` or a camel-case
+    /// hump) to a full character's score, so "gu" prefers get_user over a name that
+    /// happens to contain "gu" somewhere in the middle
+    WordBoundaryHeavy,
+}
+
+impl SkimPreset {
+    pub fn score_config(self) -> SkimScoreConfig {
+        let default = SkimScoreConfig::default();
+        match self {
+            SkimPreset::Default => default,
+            SkimPreset::WordBoundaryHeavy => SkimScoreConfig {
+                bonus_first_char_multiplier: 3,
+                bonus_head: default.score_match,
+                bonus_break: default.score_match + default.gap_extension,
+                bonus_camel: default.score_match + 2 * default.gap_extension,
+                ..default
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for SkimPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "default" => Ok(SkimPreset::Default),
+            "word-boundary-heavy" => Ok(SkimPreset::WordBoundaryHeavy),
+            _ => Err(format!("Unknown skim preset: {}. Valid options are 'default' or 'word-boundary-heavy'", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for SkimPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SkimPreset::Default => write!(f, "default"),
+            SkimPreset::WordBoundaryHeavy => write!(f, "word-boundary-heavy"),
+        }
+    }
+}
+
+/// The Skim matcher configured by `options`. Its buffer cache stays on: fuzzy-matcher
+/// 0.3.7 panics on a double borrow when `use_cache(false)` is set.
+pub fn skim_matcher(options: &SearchOptions) -> SkimMatcherV2 {
+    SkimMatcherV2::default().score_config(options.skim_preset.score_config())
+}
+
+fn term_score(matcher: &SkimMatcherV2, haystack: &str, term: &str, options: &SearchOptions) -> Option<i64> {
+    if options.match_qualified_name {
+        matcher.fuzzy_indices(haystack, term)
+            .map(|(score, indices)| score + final_segment_bonus(haystack, &indices))
+    } else {
+        matcher.fuzzy_match(haystack, term)
+    }
+}
+
+/// Every term of the query must match the haystack; the score is the sum of the terms' scores
+fn fuzzy_score(matcher: &SkimMatcherV2, symbol: &Symbol, query: &str, options: &SearchOptions) -> i64 {
+    let haystack = match_haystack(symbol, options);
+    query_terms(query).iter()
+        .try_fold(0, |total, term| term_score(matcher, &haystack, term, options).map(|score| total + score))
+        .unwrap_or(0)
+}
 
 pub fn search_symbols_skim(
     query: &str,
@@ -10,12 +82,13 @@ pub fn search_symbols_skim(
     classes: &HashSet<Symbol>,
     path_registry: &PathRegistry,
     debug: bool,
+    options: &SearchOptions,
 ) -> (Vec<(Symbol, i64)>, SearchMetrics) {
     let mut metrics = SearchMetrics::default();
     let start_total = Instant::now();
     
     let matcher_start = Instant::now();
-    let matcher = SkimMatcherV2::default();
+    let matcher = skim_matcher(options);
     metrics.matcher_init_time_ms = matcher_start.elapsed().as_millis();
     
     let mut results = Vec::new();
@@ -32,13 +105,17 @@ pub fn search_symbols_skim(
 
     // Search in functions
     for symbol in functions {
+        if !options.includes_kind(&symbol.context.symbol_type) {
+            continue;
+        }
         let name_lower = symbol.name.to_lowercase();
-        let score = if name_lower == query_lower {
+        let score = if name_lower == query_lower || is_class_qualified_match(symbol, query) {
             // Exact match gets highest score
             1000
         } else {
             // Fuzzy match gets lower score
-            matcher.fuzzy_match(&symbol.name, query).unwrap_or(0)
+            let fuzzy_score = fuzzy_score(&matcher, symbol, query, options);
+            apply_word_initials(&symbol.name, query, fuzzy_score, options)
         };
         
         if score > 0 {
@@ -71,13 +148,17 @@ pub fn search_symbols_skim(
 
     // Search in classes
     for symbol in classes {
+        if !options.includes_kind(&symbol.context.symbol_type) {
+            continue;
+        }
         let name_lower = symbol.name.to_lowercase();
-        let score = if name_lower == query_lower {
+        let score = if name_lower == query_lower || is_class_qualified_match(symbol, query) {
             // Exact match gets highest score
             1000
         } else {
             // Fuzzy match gets lower score
-            matcher.fuzzy_match(&symbol.name, query).unwrap_or(0)
+            let fuzzy_score = fuzzy_score(&matcher, symbol, query, options);
+            apply_word_initials(&symbol.name, query, fuzzy_score, options)
         };
         
         if score > 0 {
@@ -112,11 +193,11 @@ pub fn search_symbols_skim(
     metrics.search_time_ms = search_start.elapsed().as_millis();
 
     // Sort by score (highest first)
+    metrics.results_count = results.len();
     let sort_start = Instant::now();
-    results.sort_by(|a, b| b.1.cmp(&a.1));
+    sort_top_results(&mut results, options.max_results);
     metrics.sort_time_ms = sort_start.elapsed().as_millis();
     
-    metrics.results_count = results.len();
     metrics.total_time_ms = start_total.elapsed().as_millis();
     
     (results, metrics)
@@ -146,10 +227,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index1,
                 line_number: 10,
+                end_line_number: 10,
                 module: "file1".to_string(),
                 fully_qualified_module: "module1.file1".to_string(),
                 symbol_type: SymbolType::Function,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -158,10 +240,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index2,
                 line_number: 20,
+                end_line_number: 20,
                 module: "file2".to_string(),
                 fully_qualified_module: "module2.file2".to_string(),
                 symbol_type: SymbolType::Function,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -171,10 +254,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index1,
                 line_number: 5,
+                end_line_number: 5,
                 module: "file1".to_string(),
                 fully_qualified_module: "module1.file1".to_string(),
                 symbol_type: SymbolType::Class,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -183,10 +267,11 @@ mod tests {
             context: SymbolContext {
                 file_path_index: file_path_index2,
                 line_number: 15,
+                end_line_number: 15,
                 module: "file2".to_string(),
                 fully_qualified_module: "module2.file2".to_string(),
                 symbol_type: SymbolType::Class,
-                parent_context: vec![],
+                ..Default::default()
             },
         });
         
@@ -198,7 +283,7 @@ mod tests {
         let (functions, classes, path_registry) = create_test_data();
         
         // Test exact match
-        let (results, metrics) = search_symbols_skim("test_function", &functions, &classes, &path_registry, false);
+        let (results, metrics) = search_symbols_skim("test_function", &functions, &classes, &path_registry, false, &SearchOptions::default());
         assert_eq!(results.len(), 1, "Should find exactly one match");
         assert_eq!(results[0].0.name, "test_function", "Should match the correct symbol");
         assert_eq!(results[0].1, 1000, "Exact match should have score 1000");
@@ -210,7 +295,7 @@ mod tests {
         let (functions, classes, path_registry) = create_test_data();
         
         // Test fuzzy match
-        let (results, metrics) = search_symbols_skim("testfunc", &functions, &classes, &path_registry, false);
+        let (results, metrics) = search_symbols_skim("testfunc", &functions, &classes, &path_registry, false, &SearchOptions::default());
         assert!(!results.is_empty(), "Should find fuzzy matches");
         let has_test_function = results.iter().any(|(s, _)| s.name == "test_function");
         assert!(has_test_function, "Should find 'test_function' with fuzzy search");
@@ -222,17 +307,44 @@ mod tests {
         let (functions, classes, path_registry) = create_test_data();
         
         // Test no match
-        let (results, metrics) = search_symbols_skim("nonexistent", &functions, &classes, &path_registry, false);
+        let (results, metrics) = search_symbols_skim("nonexistent", &functions, &classes, &path_registry, false, &SearchOptions::default());
         assert!(results.is_empty(), "Should not find any matches");
         println!("Metrics for no match: {:?}", metrics);
     }
 
+    #[test]
+    fn test_multi_term_query_requires_every_term() {
+        let (functions, classes, path_registry) = create_test_data();
+
+        // Terms can match anywhere in the name, in any order
+        let (results, _) = search_symbols_skim("function another", &functions, &classes, &path_registry, false, &SearchOptions::default());
+        let names: Vec<_> = results.iter().map(|(s, _)| s.name.as_str()).collect();
+        assert_eq!(names, vec!["another_function"]);
+
+        // Both functions match "func", but only one also matches "test"
+        let (results, _) = search_symbols_skim("test func", &functions, &classes, &path_registry, false, &SearchOptions::default());
+        let names: Vec<_> = results.iter().map(|(s, _)| s.name.as_str()).collect();
+        assert_eq!(names, vec!["test_function"]);
+
+        // A term that matches nothing rules out every symbol
+        let (results, _) = search_symbols_skim("test nonexistent", &functions, &classes, &path_registry, false, &SearchOptions::default());
+        assert!(results.is_empty());
+
+        // Each extra matching term adds to the score
+        let (single, _) = search_symbols_skim("another", &functions, &classes, &path_registry, false, &SearchOptions::default());
+        let (multi, _) = search_symbols_skim("another class", &functions, &classes, &path_registry, false, &SearchOptions::default());
+        assert_eq!(multi.len(), 1);
+        assert_eq!(multi[0].0.name, "AnotherClass");
+        let single_score = single.iter().find(|(s, _)| s.name == "AnotherClass").unwrap().1;
+        assert!(multi[0].1 > single_score);
+    }
+
     #[test]
     fn test_case_insensitive_search() {
         let (functions, classes, path_registry) = create_test_data();
         
         // Test case insensitive search
-        let (results, metrics) = search_symbols_skim("TEST_FUNCTION", &functions, &classes, &path_registry, false);
+        let (results, metrics) = search_symbols_skim("TEST_FUNCTION", &functions, &classes, &path_registry, false, &SearchOptions::default());
         assert!(!results.is_empty(), "Should find case-insensitive matches");
         let has_test_function = results.iter().any(|(s, _)| s.name == "test_function");
         assert!(has_test_function, "Should find 'test_function' with case-insensitive search");
@@ -1,16 +1,19 @@
 use std::path::{Path, PathBuf};
 use std::collections::{HashMap, HashSet};
 use std::mem;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use serde::{Serialize, Deserialize};
 use anyhow::{Context as AnyhowContext, Result};
 use tree_sitter::{Parser, Node};
+use flate2::read::GzDecoder;
 use flate2::write::GzEncoder;
 use flate2::Compression;
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use bincode::Options;
 use tracing::info;
+use icu_normalizer::ComposingNormalizer;
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub enum SymbolType {
@@ -19,6 +22,49 @@ pub enum SymbolType {
     Method,
     NestedFunction,
     NestedClass,
+    /// A Python 3.12 `type X = ...` alias
+    TypeAlias,
+}
+
+impl SymbolType {
+    /// Short lowercase kind name ("function", "method", "class" or "type")
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            SymbolType::Function | SymbolType::NestedFunction => "function",
+            SymbolType::Method => "method",
+            SymbolType::Class | SymbolType::NestedClass => "class",
+            SymbolType::TypeAlias => "type",
+        }
+    }
+
+    /// Exuberant ctags kind letter ('f', 'm', 'c' or 't')
+    pub fn ctags_kind(&self) -> char {
+        match self {
+            SymbolType::Function | SymbolType::NestedFunction => 'f',
+            SymbolType::Method => 'm',
+            SymbolType::Class | SymbolType::NestedClass => 'c',
+            SymbolType::TypeAlias => 't',
+        }
+    }
+}
+
+impl std::str::FromStr for SymbolType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "function" => Ok(SymbolType::Function),
+            "class" => Ok(SymbolType::Class),
+            "method" => Ok(SymbolType::Method),
+            "nested_function" => Ok(SymbolType::NestedFunction),
+            "nested_class" => Ok(SymbolType::NestedClass),
+            "type_alias" => Ok(SymbolType::TypeAlias),
+            _ => Err(format!(
+                "Unknown symbol kind: {}. Valid options are 'function', 'class', 'method', 'nested_function', 'nested_class' or 'type_alias'",
+                s
+            )),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
@@ -28,6 +74,31 @@ pub struct ParentContext {
     pub line_number: usize,
 }
 
+/// Compose a path's Unicode to NFC. Paths that are not UTF-8 are returned unchanged.
+pub fn nfc_path(path: PathBuf) -> PathBuf {
+    let Some(text) = path.to_str() else {
+        return path;
+    };
+    let normalizer = ComposingNormalizer::new_nfc();
+    if normalizer.is_normalized(text) {
+        path
+    } else {
+        PathBuf::from(normalizer.normalize(text))
+    }
+}
+
+/// The form a path is stored in by `PathRegistry`. macOS lists file names decomposed (NFD)
+/// while typed or pasted paths are usually composed (NFC), so one file can arrive in both
+/// forms and be indexed twice. Its filesystems treat the forms as the same file, so paths
+/// are composed there. Elsewhere the two forms name different files and are kept apart.
+fn registry_path(path: PathBuf) -> PathBuf {
+    if cfg!(target_os = "macos") {
+        nfc_path(path)
+    } else {
+        path
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct PathRegistry {
     // Store all paths in a vector
@@ -46,6 +117,7 @@ impl PathRegistry {
 
     // Get or create an index for this path
     pub fn register_path(&mut self, path: PathBuf) -> usize {
+        let path = registry_path(path);
         // Look up or insert path
         if let Some(&idx) = self.path_to_index.get(&path) {
             return idx;
@@ -59,6 +131,26 @@ impl PathRegistry {
         new_idx
     }
 
+    /// Rebuild the registry of a saved index, where a path's position is its index. Fails if
+    /// two saved paths register as one (e.g. differently normalized forms of the same name),
+    /// since every later index would then point at the wrong file.
+    pub fn from_saved_paths(paths: Vec<PathBuf>) -> Result<Self> {
+        let mut registry = Self::new();
+        for (i, path) in paths.into_iter().enumerate() {
+            let display = path.display().to_string();
+            let index = registry.register_path(path);
+            if index != i {
+                anyhow::bail!("Path registry indexing error: expected index {} for path {}, got {}", i, display, index);
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Index of an already registered path, matching it the way `register_path` does
+    pub fn index_of(&self, path: &Path) -> Option<usize> {
+        self.path_to_index.get(&registry_path(path.to_path_buf())).copied()
+    }
+
     // Get path from index
     pub fn get_path(&self, index: usize) -> &PathBuf {
         &self.paths[index]
@@ -97,14 +189,84 @@ impl PathRegistry {
     }
 }
 
+/// Saved indexes are bincode, which has no field defaults: changing these fields needs a new
+/// `SymbolData` version, with the old layout kept as a legacy context for loading
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct SymbolContext {
     pub file_path_index: usize,  // Index into PathRegistry
     pub line_number: usize,
+    /// Last line of the definition, inclusive (1-based like `line_number`)
+    pub end_line_number: usize,
     pub module: String,
     pub fully_qualified_module: String,
     pub symbol_type: SymbolType,
     pub parent_context: Vec<ParentContext>,
+    /// Decorator names applied to the definition, without `@` or call arguments (e.g. "functools.wraps")
+    pub decorators: Vec<String>,
+    /// Whether this is a method decorated with `@abstractmethod` (e.g. on an ABC)
+    pub is_abstract: bool,
+    /// The first few source lines of the definition, when collected with `capture_snippet`
+    pub snippet: Option<String>,
+    /// Byte offsets of the name within the parsed source (after any UTF-8 BOM), for exact
+    /// ranges without reparsing. `None` for symbols that were not built by the parser.
+    pub name_start_byte: Option<usize>,
+    pub name_end_byte: Option<usize>,
+    /// Whether outer parents were dropped from `parent_context` by `CollectOptions::max_parents`
+    pub parents_truncated: bool,
+    /// Parameters and return annotation of a function (`(self, key: str) -> int`), or the
+    /// base classes of a class (`(Base, Generic[T])`), as written with whitespace collapsed.
+    /// `None` for symbols loaded from V1 and V2 indexes.
+    pub signature: Option<String>,
+}
+
+/// A top-level function at line 0 of the first registered file, with nothing else recorded.
+/// Build contexts with struct update syntax, so fields a collector does not record (and
+/// fields added later) keep these values.
+impl Default for SymbolContext {
+    fn default() -> Self {
+        SymbolContext {
+            file_path_index: 0,
+            line_number: 0,
+            end_line_number: 0,
+            module: String::new(),
+            fully_qualified_module: String::new(),
+            symbol_type: SymbolType::Function,
+            parent_context: Vec::new(),
+            decorators: Vec::new(),
+            is_abstract: false,
+            snippet: None,
+            name_start_byte: None,
+            name_end_byte: None,
+            parents_truncated: false,
+            signature: None,
+        }
+    }
+}
+
+impl SymbolContext {
+    /// Whether any decorator matches `name`, ignoring module qualification
+    /// (so "deprecated" matches both `@deprecated` and `@typing_extensions.deprecated`)
+    pub fn has_decorator(&self, name: &str) -> bool {
+        self.decorators.iter()
+            .any(|d| d.rsplit('.').next() == Some(name))
+    }
+
+    /// Keep only the nearest `max` parents, marking the context as truncated if any were dropped
+    pub fn truncate_parents(&mut self, max: usize) {
+        if self.parent_context.len() > max {
+            self.parent_context.drain(..self.parent_context.len() - max);
+            self.parents_truncated = true;
+        }
+    }
+
+    /// Dotted module name including the file's own module (e.g. "pkg.sub.file")
+    pub fn module_path(&self) -> String {
+        if self.fully_qualified_module.is_empty() {
+            self.module.clone()
+        } else {
+            format!("{}.{}", self.fully_qualified_module, self.module)
+        }
+    }
 }
 
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
@@ -113,6 +275,15 @@ pub struct Symbol {
     pub context: SymbolContext,
 }
 
+/// A file that could not be (fully) indexed, for reporting to users or editors
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParseIssue {
+    pub path: PathBuf,
+    /// 1-based line of the first syntax error, when known
+    pub line: Option<usize>,
+    pub message: String,
+}
+
 #[derive(Debug, Default)]
 pub struct SymbolStats {
     pub functions: Arc<Mutex<HashSet<Symbol>>>,
@@ -121,6 +292,45 @@ pub struct SymbolStats {
     pub io_errors: AtomicUsize,
     pub other_errors: AtomicUsize,
     pub path_registry: Arc<Mutex<PathRegistry>>,
+    // Only collected when enabled, to avoid overhead on huge error-prone trees
+    pub parse_issues: Option<Arc<Mutex<Vec<ParseIssue>>>>,
+    /// Set when a time budget ran out before every file was parsed
+    pub truncated: AtomicBool,
+    /// Files attempted so far, whether or not they parsed
+    pub files_scanned: AtomicUsize,
+    /// Files that were read and parsed but produced no symbols (e.g. only imports, or
+    /// content the grammar could not make sense of)
+    pub empty_files: Arc<Mutex<Vec<PathBuf>>>,
+    /// Files with a module-level `if __name__ == "__main__":` block, when
+    /// `CollectOptions::detect_main` is set
+    pub entry_points: Arc<Mutex<Vec<PathBuf>>>,
+    /// Files skipped for a generated-code header (see `CollectOptions::generated_markers`)
+    pub generated_files: AtomicUsize,
+    /// Files whose parse was abandoned after `CollectOptions::parse_timeout`
+    pub parse_timeouts: AtomicUsize,
+    /// Symbols dropped to stay within `CollectOptions::max_symbols`
+    pub pruned_symbols: AtomicUsize,
+    /// Nanoseconds the parallel parser's threads spent on their chunks, summed across threads
+    pub parse_busy_nanos: AtomicU64,
+}
+
+/// Point-in-time parse counts from `SymbolStats::snapshot`, e.g. for JSON output
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Stats {
+    pub functions: usize,
+    pub classes: usize,
+    pub syntax_errors: usize,
+    pub io_errors: usize,
+    pub other_errors: usize,
+    pub files_scanned: usize,
+    #[serde(default)]
+    pub empty_files: usize,
+    #[serde(default)]
+    pub generated_files: usize,
+    #[serde(default)]
+    pub parse_timeouts: usize,
+    #[serde(default)]
+    pub pruned_symbols: usize,
 }
 
 impl SymbolStats {
@@ -132,9 +342,82 @@ impl SymbolStats {
             io_errors: AtomicUsize::new(0),
             other_errors: AtomicUsize::new(0),
             path_registry: Arc::new(Mutex::new(PathRegistry::new())),
+            parse_issues: None,
+            truncated: AtomicBool::new(false),
+            files_scanned: AtomicUsize::new(0),
+            empty_files: Arc::new(Mutex::new(Vec::new())),
+            entry_points: Arc::new(Mutex::new(Vec::new())),
+            generated_files: AtomicUsize::new(0),
+            parse_timeouts: AtomicUsize::new(0),
+            pruned_symbols: AtomicUsize::new(0),
+            parse_busy_nanos: AtomicU64::new(0),
+        }
+    }
+
+    /// Create stats that also record a `ParseIssue` for each file that fails to read,
+    /// fails to parse, or contains syntax errors
+    pub fn with_parse_issues() -> Self {
+        Self {
+            parse_issues: Some(Arc::new(Mutex::new(Vec::new()))),
+            ..Self::new()
+        }
+    }
+
+    /// Collected parse issues sorted by path, or empty if collection was not enabled
+    pub fn parse_issues(&self) -> Vec<ParseIssue> {
+        let mut issues = self.parse_issues.as_ref()
+            .map(|issues| issues.lock().unwrap().clone())
+            .unwrap_or_default();
+        issues.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+        issues
+    }
+
+    pub(crate) fn record_parse_issues(&self, issues: Vec<ParseIssue>) {
+        if let Some(collector) = &self.parse_issues {
+            if !issues.is_empty() {
+                collector.lock().unwrap().extend(issues);
+            }
         }
     }
 
+    /// Files that yielded no symbols, sorted by path
+    pub fn empty_files(&self) -> Vec<PathBuf> {
+        let mut files = self.empty_files.lock().unwrap().clone();
+        files.sort();
+        files
+    }
+
+    pub(crate) fn record_empty_files(&self, files: Vec<PathBuf>) {
+        if !files.is_empty() {
+            self.empty_files.lock().unwrap().extend(files);
+        }
+    }
+
+    /// Files with a `__main__` guard, sorted by path
+    pub fn entry_points(&self) -> Vec<PathBuf> {
+        let mut files = self.entry_points.lock().unwrap().clone();
+        files.sort();
+        files
+    }
+
+    pub(crate) fn record_entry_points(&self, files: Vec<PathBuf>) {
+        if !files.is_empty() {
+            self.entry_points.lock().unwrap().extend(files);
+        }
+    }
+
+    /// Time the parallel parser's threads spent reading, parsing and merging files, summed
+    /// across threads. Threads rarely block, so this approximates the CPU time of a parse;
+    /// dividing it by the wall-clock time gives the speedup parallelism achieved.
+    pub fn parse_busy_time(&self) -> std::time::Duration {
+        std::time::Duration::from_nanos(self.parse_busy_nanos.load(Ordering::Relaxed))
+    }
+
+    /// Whether parsing stopped early because its time budget ran out
+    pub fn is_truncated(&self) -> bool {
+        self.truncated.load(Ordering::Relaxed)
+    }
+
     pub fn get_counts(&self) -> (usize, usize, usize, usize, usize) {
         (
             self.functions.lock().unwrap().len(),
@@ -144,39 +427,318 @@ impl SymbolStats {
             self.other_errors.load(Ordering::Relaxed),
         )
     }
+
+    /// Current counts as a named, serializable struct
+    pub fn snapshot(&self) -> Stats {
+        let (functions, classes, syntax_errors, io_errors, other_errors) = self.get_counts();
+        Stats {
+            functions,
+            classes,
+            syntax_errors,
+            io_errors,
+            other_errors,
+            files_scanned: self.files_scanned.load(Ordering::Relaxed),
+            empty_files: self.empty_files.lock().unwrap().len(),
+            generated_files: self.generated_files.load(Ordering::Relaxed),
+            parse_timeouts: self.parse_timeouts.load(Ordering::Relaxed),
+            pruned_symbols: self.pruned_symbols.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// `SymbolContext` as saved in V1 indexes, before definition extents, decorators, snippets
+/// and name offsets were recorded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacySymbolContextV1 {
+    pub file_path_index: usize,
+    pub line_number: usize,
+    pub module: String,
+    pub fully_qualified_module: String,
+    pub symbol_type: SymbolType,
+    pub parent_context: Vec<ParentContext>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacySymbolV1 {
+    pub name: String,
+    pub context: LegacySymbolContextV1,
+}
+
+impl From<LegacySymbolV1> for Symbol {
+    fn from(symbol: LegacySymbolV1) -> Self {
+        let context = symbol.context;
+        Symbol {
+            name: symbol.name,
+            context: SymbolContext {
+                file_path_index: context.file_path_index,
+                line_number: context.line_number,
+                // V1 did not record where definitions end
+                end_line_number: context.line_number,
+                module: context.module,
+                fully_qualified_module: context.fully_qualified_module,
+                symbol_type: context.symbol_type,
+                parent_context: context.parent_context,
+                // Recorded only by later versions
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// `SymbolContext` as saved in V2 indexes, before parent truncation and signatures were recorded
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacySymbolContextV2 {
+    pub file_path_index: usize,
+    pub line_number: usize,
+    pub end_line_number: usize,
+    pub module: String,
+    pub fully_qualified_module: String,
+    pub symbol_type: SymbolType,
+    pub parent_context: Vec<ParentContext>,
+    pub decorators: Vec<String>,
+    pub is_abstract: bool,
+    pub snippet: Option<String>,
+    pub name_start_byte: Option<usize>,
+    pub name_end_byte: Option<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LegacySymbolV2 {
+    pub name: String,
+    pub context: LegacySymbolContextV2,
+}
+
+impl From<LegacySymbolV2> for Symbol {
+    fn from(symbol: LegacySymbolV2) -> Self {
+        let context = symbol.context;
+        Symbol {
+            name: symbol.name,
+            context: SymbolContext {
+                file_path_index: context.file_path_index,
+                line_number: context.line_number,
+                end_line_number: context.end_line_number,
+                module: context.module,
+                fully_qualified_module: context.fully_qualified_module,
+                symbol_type: context.symbol_type,
+                parent_context: context.parent_context,
+                decorators: context.decorators,
+                is_abstract: context.is_abstract,
+                snippet: context.snippet,
+                name_start_byte: context.name_start_byte,
+                name_end_byte: context.name_end_byte,
+                // Recorded only by later versions
+                ..Default::default()
+            },
+        }
+    }
 }
 
 // Versioned data structures
 #[derive(Serialize, Deserialize)]
 pub struct SymbolDataV1 {
+    pub version: u32,
+    pub functions: Vec<LegacySymbolV1>,
+    pub classes: Vec<LegacySymbolV1>,
+    pub paths: Vec<PathBuf>,
+}
+
+/// V2 adds the directory the index was built from, so its paths can be rebased on load
+#[derive(Serialize, Deserialize)]
+pub struct SymbolDataV2 {
+    pub version: u32,
+    pub functions: Vec<LegacySymbolV2>,
+    pub classes: Vec<LegacySymbolV2>,
+    pub paths: Vec<PathBuf>,
+    /// The scan directory module names were computed against, when known
+    pub base_dir: Option<PathBuf>,
+}
+
+/// V3 adds which files have a `__main__` guard
+#[derive(Serialize, Deserialize)]
+pub struct SymbolDataV3 {
     pub version: u32,
     pub functions: Vec<Symbol>,
     pub classes: Vec<Symbol>,
     pub paths: Vec<PathBuf>,
+    pub base_dir: Option<PathBuf>,
+    /// Parallel to `paths`; all false unless the index was collected with `detect_main`
+    pub has_main: Vec<bool>,
+}
+
+/// What an index holds, stored ahead of the symbols in V4 indexes so `read_index_metadata`
+/// can report it without reading the rest of the file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexMetadata {
+    pub version: u32,
+    /// When the index was saved, in seconds since the Unix epoch; `None` before V4
+    pub created_at: Option<u64>,
+    pub base_dir: Option<PathBuf>,
+    pub files: usize,
+    /// How many symbols of each kind the index holds, most common first
+    pub kind_counts: Vec<(SymbolType, usize)>,
+}
+
+impl IndexMetadata {
+    fn describe<'a>(
+        version: u32,
+        created_at: Option<u64>,
+        base_dir: Option<PathBuf>,
+        paths: &[PathBuf],
+        symbol_types: impl IntoIterator<Item = &'a SymbolType>,
+    ) -> Self {
+        let mut counts: HashMap<SymbolType, usize> = HashMap::new();
+        for symbol_type in symbol_types {
+            *counts.entry(symbol_type.clone()).or_default() += 1;
+        }
+        let mut kind_counts: Vec<(SymbolType, usize)> = counts.into_iter().collect();
+        kind_counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.kind_name().cmp(b.0.kind_name())));
+        IndexMetadata { version, created_at, base_dir, files: paths.len(), kind_counts }
+    }
+
+    /// Total symbols across every kind
+    pub fn symbols(&self) -> usize {
+        self.kind_counts.iter().map(|(_, count)| count).sum()
+    }
+}
+
+/// V4 leads with an `IndexMetadata` header, adding a save time and per-kind symbol counts
+#[derive(Serialize, Deserialize)]
+pub struct SymbolDataV4 {
+    pub metadata: IndexMetadata,
+    pub functions: Vec<Symbol>,
+    pub classes: Vec<Symbol>,
+    pub paths: Vec<PathBuf>,
+    /// Parallel to `paths`; all false unless the index was collected with `detect_main`
+    pub has_main: Vec<bool>,
 }
 
 #[derive(Serialize, Deserialize)]
 pub enum SymbolData {
     V1(SymbolDataV1),
+    V2(SymbolDataV2),
+    V3(SymbolDataV3),
+    V4(SymbolDataV4),
 }
 
 impl SymbolData {
     pub fn new(functions: Vec<Symbol>, classes: Vec<Symbol>, paths: Vec<PathBuf>) -> Self {
-        SymbolData::V1(SymbolDataV1 {
-            version: 1,
+        Self::with_base_dir(functions, classes, paths, None)
+    }
+
+    /// Like `new`, recording the scan directory so tools can relocate the index with `rebase_paths`
+    pub fn with_base_dir(functions: Vec<Symbol>, classes: Vec<Symbol>, paths: Vec<PathBuf>, base_dir: Option<PathBuf>) -> Self {
+        let has_main = vec![false; paths.len()];
+        Self::with_entry_points(functions, classes, paths, base_dir, has_main)
+    }
+
+    /// Like `with_base_dir`, also recording which of `paths` have a `__main__` guard
+    pub fn with_entry_points(
+        functions: Vec<Symbol>,
+        classes: Vec<Symbol>,
+        paths: Vec<PathBuf>,
+        base_dir: Option<PathBuf>,
+        has_main: Vec<bool>,
+    ) -> Self {
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()
+            .map(|elapsed| elapsed.as_secs());
+        let symbol_types = functions.iter().chain(&classes).map(|symbol| &symbol.context.symbol_type);
+        let metadata = IndexMetadata::describe(4, created_at, base_dir, &paths, symbol_types);
+        SymbolData::V4(SymbolDataV4 {
+            metadata,
             functions,
             classes,
             paths,
+            has_main,
         })
     }
 
+    /// The index's metadata, worked out from its symbols for versions before V4
+    pub fn metadata(&self) -> IndexMetadata {
+        match self {
+            SymbolData::V1(data) => {
+                let symbol_types = data.functions.iter().chain(&data.classes).map(|symbol| &symbol.context.symbol_type);
+                IndexMetadata::describe(data.version, None, None, &data.paths, symbol_types)
+            }
+            SymbolData::V2(data) => {
+                let symbol_types = data.functions.iter().chain(&data.classes).map(|symbol| &symbol.context.symbol_type);
+                IndexMetadata::describe(data.version, None, data.base_dir.clone(), &data.paths, symbol_types)
+            }
+            SymbolData::V3(data) => {
+                let symbol_types = data.functions.iter().chain(&data.classes).map(|symbol| &symbol.context.symbol_type);
+                IndexMetadata::describe(data.version, None, data.base_dir.clone(), &data.paths, symbol_types)
+            }
+            SymbolData::V4(data) => data.metadata.clone(),
+        }
+    }
+
+    /// The directory the index was built from; `None` for V1 indexes
+    pub fn base_dir(&self) -> Option<&Path> {
+        match self {
+            SymbolData::V1(_) => None,
+            SymbolData::V2(data) => data.base_dir.as_deref(),
+            SymbolData::V3(data) => data.base_dir.as_deref(),
+            SymbolData::V4(data) => data.metadata.base_dir.as_deref(),
+        }
+    }
+
+    /// Indexed files with a `__main__` guard; always empty for V1 and V2 indexes
+    pub fn entry_points(&self) -> Vec<&Path> {
+        match self {
+            SymbolData::V1(_) | SymbolData::V2(_) => Vec::new(),
+            SymbolData::V3(SymbolDataV3 { paths, has_main, .. })
+            | SymbolData::V4(SymbolDataV4 { paths, has_main, .. }) => paths.iter()
+                .zip(has_main)
+                .filter(|(_, has_main)| **has_main)
+                .map(|(path, _)| path.as_path())
+                .collect(),
+        }
+    }
+
+    /// The index's symbols and paths, with symbols from older versions converted to the
+    /// current `SymbolContext`
     pub fn into_symbols(self) -> (Vec<Symbol>, Vec<Symbol>, Vec<PathBuf>) {
         match self {
-            SymbolData::V1(data) => (data.functions, data.classes, data.paths),
+            SymbolData::V1(data) => (
+                data.functions.into_iter().map(Symbol::from).collect(),
+                data.classes.into_iter().map(Symbol::from).collect(),
+                data.paths,
+            ),
+            SymbolData::V2(data) => (
+                data.functions.into_iter().map(Symbol::from).collect(),
+                data.classes.into_iter().map(Symbol::from).collect(),
+                data.paths,
+            ),
+            SymbolData::V3(data) => (data.functions, data.classes, data.paths),
+            SymbolData::V4(data) => (data.functions, data.classes, data.paths),
         }
     }
 }
 
+/// Collect a loaded index's symbols into sets, building the function and class sets in
+/// parallel. Duplicates collapse exactly as with `into_iter().collect()`.
+pub fn symbol_sets(functions: Vec<Symbol>, classes: Vec<Symbol>) -> (HashSet<Symbol>, HashSet<Symbol>) {
+    let start = std::time::Instant::now();
+    let sets = rayon::join(
+        || functions.into_iter().collect(),
+        || classes.into_iter().collect(),
+    );
+    info!("Built symbol sets in {}ms", start.elapsed().as_millis());
+    sets
+}
+
+/// Move indexed paths from `old_root` to `new_root`, e.g. after copying a project and its
+/// index to another machine. Paths outside `old_root` are kept as they are.
+pub fn rebase_paths(paths: Vec<PathBuf>, old_root: &Path, new_root: &Path) -> Vec<PathBuf> {
+    paths.into_iter()
+        .map(|path| match path.strip_prefix(old_root) {
+            Ok(relative) => new_root.join(relative),
+            Err(_) => path,
+        })
+        .collect()
+}
+
 pub fn get_module_name(path: &Path) -> String {
     path.file_stem()
         .and_then(|s| s.to_str())
@@ -192,10 +754,151 @@ pub fn get_fully_qualified_module(path: &Path, base_dir: &Path) -> String {
         .unwrap_or_else(|| "unknown".to_string())
 }
 
+/// Like `get_fully_qualified_module`, but files under one of `source_roots` (e.g. `src` in a
+/// src layout) are named relative to that root instead, as Python would import them.
+/// Relative roots are taken to be under `base_dir`; the deepest matching root wins.
+pub fn get_fully_qualified_module_with_roots(path: &Path, base_dir: &Path, source_roots: &[PathBuf]) -> String {
+    get_fully_qualified_module(path, &module_root(path, base_dir, source_roots))
+}
+
+/// The directory `path`'s module is named relative to: its deepest source root, else `base_dir`
+pub fn module_root(path: &Path, base_dir: &Path, source_roots: &[PathBuf]) -> PathBuf {
+    source_roots.iter()
+        .map(|root| base_dir.join(root))
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .unwrap_or_else(|| base_dir.to_path_buf())
+}
+
+/// Like `get_fully_qualified_module`, but only directories with an `__init__.py` count as
+/// packages: the module path is the run of regular packages directly above the file, so a
+/// plain directory (or a PEP 420 namespace package) above them doesn't become part of it.
+/// A file whose own directory has no `__init__.py` is a top-level module (`""`).
+pub fn get_package_module(path: &Path, base_dir: &Path) -> String {
+    let Some(relative_dir) = path.strip_prefix(base_dir).ok().and_then(|p| p.parent()) else {
+        return "unknown".to_string();
+    };
+    let mut packages = Vec::new();
+    let mut dir = base_dir.join(relative_dir);
+    for name in relative_dir.iter().rev() {
+        if !dir.join("__init__.py").is_file() {
+            break;
+        }
+        packages.push(name.to_string_lossy());
+        dir.pop();
+    }
+    packages.reverse();
+    packages.join(".")
+}
+
+/// Distinct dotted module names with a representative file for each, sorted by module name
+pub fn list_modules(
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+) -> Vec<(String, PathBuf)> {
+    let mut modules: HashMap<String, &PathBuf> = HashMap::new();
+    for symbol in functions.iter().chain(classes.iter()) {
+        let path = path_registry.get_path(symbol.context.file_path_index);
+        modules.entry(symbol.context.module_path())
+            .and_modify(|existing| {
+                // Keep the choice deterministic when several files share a module name
+                if path < *existing {
+                    *existing = path;
+                }
+            })
+            .or_insert(path);
+    }
+
+    let mut modules: Vec<(String, PathBuf)> = modules.into_iter()
+        .map(|(module, path)| (module, path.clone()))
+        .collect();
+    modules.sort();
+    modules
+}
+
+/// Names defined more than `more_than` times, with every definition ordered by file and line.
+/// Names with the most definitions come first, ties broken by name.
+pub fn find_duplicate_names<'a>(
+    functions: &'a HashSet<Symbol>,
+    classes: &'a HashSet<Symbol>,
+    path_registry: &PathRegistry,
+    more_than: usize,
+) -> Vec<(String, Vec<&'a Symbol>)> {
+    let mut by_name: HashMap<&str, Vec<&Symbol>> = HashMap::new();
+    for symbol in functions.iter().chain(classes.iter()) {
+        by_name.entry(symbol.name.as_str()).or_default().push(symbol);
+    }
+
+    let mut duplicates: Vec<(String, Vec<&Symbol>)> = by_name.into_iter()
+        .filter(|(_, definitions)| definitions.len() > more_than)
+        .map(|(name, mut definitions)| {
+            definitions.sort_by(|a, b| {
+                path_registry.get_path(a.context.file_path_index)
+                    .cmp(path_registry.get_path(b.context.file_path_index))
+                    .then(a.context.line_number.cmp(&b.context.line_number))
+            });
+            (name.to_string(), definitions)
+        })
+        .collect();
+    duplicates.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+    duplicates
+}
+
+/// Headline counts for an index, e.g. for sanity-checking that a load picked up what was expected
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexSummary {
+    pub functions: usize,
+    pub classes: usize,
+    pub files: usize,
+    pub modules: usize,
+}
+
+pub fn summarize_index(
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+) -> IndexSummary {
+    IndexSummary {
+        functions: functions.len(),
+        classes: classes.len(),
+        files: path_registry.paths.len(),
+        modules: list_modules(functions, classes, path_registry).len(),
+    }
+}
+
 pub fn get_node_text(node: Node, source: &str) -> String {
     source[node.byte_range()].to_string()
 }
 
+/// The `SymbolContext::signature` of a function or class definition node. `None` for a class
+/// without base classes.
+pub fn definition_signature(node: Node, source: &str) -> Option<String> {
+    match node.kind() {
+        "function_definition" => {
+            let mut text = one_line_list(node.child_by_field_name("parameters")?, source);
+            if let Some(return_type) = node.child_by_field_name("return_type") {
+                text.push_str(" -> ");
+                text.push_str(&get_node_text(return_type, source));
+            }
+            Some(text)
+        }
+        "class_definition" => Some(one_line_list(node.child_by_field_name("superclasses")?, source)),
+        _ => None,
+    }
+}
+
+// A parenthesized list written across lines, on one line without its trailing comma
+fn one_line_list(node: Node, source: &str) -> String {
+    let text = get_node_text(node, source);
+    let collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    let collapsed = collapsed.replace("( ", "(").replace(" )", ")");
+    match collapsed.strip_suffix(",)") {
+        Some(items) => format!("{})", items),
+        None => collapsed,
+    }
+}
+
 pub fn collect_symbols(
     node: Node, 
     source: &str, 
@@ -221,7 +924,7 @@ pub fn collect_symbols(
                     let name = get_node_text(name_node, source);
                     let symbol_type = if current_parents.is_empty() {
                         SymbolType::Function
-                    } else if current_parents.last().map_or(false, |p| matches!(p.symbol_type, SymbolType::Class)) {
+                    } else if current_parents.last().is_some_and(|p| matches!(p.symbol_type, SymbolType::Class)) {
                         SymbolType::Method
                     } else {
                         SymbolType::NestedFunction
@@ -230,10 +933,15 @@ pub fn collect_symbols(
                     let context = SymbolContext {
                         file_path_index,
                         line_number: node.start_position().row + 1,
+                        end_line_number: node.end_position().row + 1,
                         module: module.clone(),
                         fully_qualified_module: fully_qualified_module.clone(),
                         symbol_type: symbol_type.clone(),
                         parent_context: current_parents.to_vec(),
+                        name_start_byte: Some(name_node.start_byte()),
+                        name_end_byte: Some(name_node.end_byte()),
+                        signature: definition_signature(node, source),
+                        ..Default::default()
                     };
                     function_symbols.push(Symbol { name: name.clone(), context });
 
@@ -271,10 +979,15 @@ pub fn collect_symbols(
                     let context = SymbolContext {
                         file_path_index,
                         line_number: node.start_position().row + 1,
+                        end_line_number: node.end_position().row + 1,
                         module: module.clone(),
                         fully_qualified_module: fully_qualified_module.clone(),
                         symbol_type: symbol_type.clone(),
                         parent_context: current_parents.to_vec(),
+                        name_start_byte: Some(name_node.start_byte()),
+                        name_end_byte: Some(name_node.end_byte()),
+                        signature: definition_signature(node, source),
+                        ..Default::default()
                     };
                     class_symbols.push(Symbol { name: name.clone(), context });
 
@@ -335,7 +1048,121 @@ pub fn parse_python_file(
     Ok((functions, classes))
 }
 
+/// On-disk encoding of a saved symbol index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexCompression {
+    /// Gzip-compressed bincode (the default, smallest on disk)
+    Gzip,
+    /// Raw bincode, faster to load from fast local storage
+    None,
+}
+
+impl IndexCompression {
+    /// Pick the encoding from the file extension: `.bin` is uncompressed, anything else gzip
+    pub fn from_path(path: &Path) -> Self {
+        if path.extension().is_some_and(|ext| ext == "bin") {
+            IndexCompression::None
+        } else {
+            IndexCompression::Gzip
+        }
+    }
+}
+
+// First two bytes of every gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+// Variant index of `SymbolData::V4`, the newest format
+const SYMBOL_DATA_LATEST_VARIANT: u32 = 3;
+
+const NOT_AN_INDEX: &str = "not a valid pylight index file (expected gzip-compressed bincode)";
+
+/// Most a deflate stream can expand: a 258-byte match costs at least two bits (RFC 1951)
+const MAX_DEFLATE_RATIO: u64 = 1032;
+
+/// Open a saved index for reading its bincode, decompressing it if needed. Also returns an
+/// upper bound on how many bytes of bincode the index can hold, to bound decoding with.
+fn open_symbol_data(path: &Path) -> Result<(Box<dyn Read>, u64)> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open symbols file {}", path.display()))?;
+    let file_len = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let header = reader.fill_buf()?;
+    let is_gzip = header.starts_with(&GZIP_MAGIC);
+    // Uncompressed indexes start with the `SymbolData` variant as a little-endian u32
+    let is_raw_bincode = header.len() >= 4 && u32::from_le_bytes([header[0], header[1], header[2], header[3]]) <= SYMBOL_DATA_LATEST_VARIANT;
+    if !is_gzip && !is_raw_bincode {
+        anyhow::bail!("{} is {}", path.display(), NOT_AN_INDEX);
+    }
+
+    // Not the gzip trailer's size, which wraps at 4 GiB
+    Ok(if is_gzip {
+        (Box::new(GzDecoder::new(reader)), file_len.saturating_mul(MAX_DEFLATE_RATIO))
+    } else {
+        (Box::new(reader), file_len)
+    })
+}
+
+/// Decode bincode as `bincode::deserialize_from` does, but never reading past `limit` bytes, so
+/// a garbage length in a corrupt or outdated index fails instead of attempting a huge allocation
+fn decode_symbol_data<T: serde::de::DeserializeOwned>(reader: impl Read, limit: u64) -> bincode::Result<T> {
+    bincode::options()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(limit)
+        .deserialize_from(reader)
+}
+
+/// Explain why a saved index failed to decode. Indexes of an older version than the latest
+/// were written with a symbol layout no legacy struct describes, and need rebuilding.
+fn decode_failure(path: &Path) -> String {
+    let variant = open_symbol_data(path)
+        .ok()
+        .and_then(|(reader, limit)| decode_symbol_data::<u32>(reader, limit).ok());
+    match variant {
+        Some(variant) if variant < SYMBOL_DATA_LATEST_VARIANT => format!(
+            "{} is a V{} pylight index in an outdated format that can no longer be read; rebuild it",
+            path.display(),
+            variant + 1,
+        ),
+        // Truncated downloads and other corruption otherwise surface as bare "unexpected end of file"
+        _ => format!("{} is {}, or it is truncated or corrupt", path.display(), NOT_AN_INDEX),
+    }
+}
+
+/// Read a saved symbol index, detecting gzip vs raw bincode from the file contents
+pub fn load_symbol_data(path: &Path) -> Result<SymbolData> {
+    let (reader, limit) = open_symbol_data(path)?;
+    decode_symbol_data(reader, limit).with_context(|| decode_failure(path))
+}
+
+/// Read just the metadata of a saved index. For V4 indexes only the header is decoded;
+/// older ones are loaded in full to count their symbols.
+pub fn read_index_metadata(path: &Path) -> Result<IndexMetadata> {
+    let (mut reader, limit) = open_symbol_data(path)?;
+    let corrupt = || format!("{} is {}, or it is truncated or corrupt", path.display(), NOT_AN_INDEX);
+    // bincode writes the `SymbolData` variant first, then the variant's fields in order
+    let variant: u32 = decode_symbol_data(&mut reader, limit).with_context(corrupt)?;
+    if variant == SYMBOL_DATA_LATEST_VARIANT {
+        return decode_symbol_data(&mut reader, limit).with_context(corrupt);
+    }
+    Ok(load_symbol_data(path)?.metadata())
+}
+
 pub fn save_symbols(path: &Path, stats: &SymbolStats) -> Result<()> {
+    save_symbols_with_compression(path, stats, IndexCompression::Gzip)
+}
+
+pub fn save_symbols_with_compression(path: &Path, stats: &SymbolStats, compression: IndexCompression) -> Result<()> {
+    save_symbols_with_base_dir(path, stats, compression, None)
+}
+
+/// Save symbols along with the directory they were scanned from, so the index can be rebased
+pub fn save_symbols_with_base_dir(
+    path: &Path,
+    stats: &SymbolStats,
+    compression: IndexCompression,
+    base_dir: Option<&Path>,
+) -> Result<()> {
     let path_registry = stats.path_registry.lock().unwrap();
     
     // Convert HashSets to Vecs
@@ -345,25 +1172,128 @@ pub fn save_symbols(path: &Path, stats: &SymbolStats) -> Result<()> {
     let functions_vec: Vec<Symbol> = functions.iter().cloned().collect();
     let classes_vec: Vec<Symbol> = classes.iter().cloned().collect();
     
-    let symbol_data = SymbolData::new(
+    let entry_points: HashSet<PathBuf> = stats.entry_points.lock().unwrap().iter().cloned().collect();
+    let has_main = path_registry.paths.iter().map(|path| entry_points.contains(path)).collect();
+    
+    let symbol_data = SymbolData::with_entry_points(
         functions_vec,
         classes_vec,
         path_registry.paths.clone(),
+        base_dir.map(Path::to_path_buf),
+        has_main,
     );
-    
+    write_symbol_data(path, &symbol_data, compression)
+}
+
+/// Write an index file in the format `load_symbol_data` reads
+pub fn write_symbol_data(path: &Path, symbol_data: &SymbolData, compression: IndexCompression) -> Result<()> {
     let file = File::create(path)?;
-    let writer = BufWriter::new(file);
-    let encoder = GzEncoder::new(writer, Compression::default());
-    bincode::serialize_into(encoder, &symbol_data)?;
+    let mut writer = BufWriter::new(file);
+    match compression {
+        IndexCompression::Gzip => {
+            let mut encoder = GzEncoder::new(writer, Compression::default());
+            bincode::serialize_into(&mut encoder, symbol_data)?;
+            encoder.finish()?.flush()?;
+        }
+        IndexCompression::None => {
+            bincode::serialize_into(&mut writer, symbol_data)?;
+            writer.flush()?;
+        }
+    }
     
     Ok(())
 }
 
+/// Write symbols as an Exuberant-ctags-compatible tags file, one
+/// `name<TAB>file<TAB>line;"<TAB>kind` line per symbol, sorted as ctags expects
+pub fn write_ctags<W: Write>(
+    writer: &mut W,
+    functions: &HashSet<Symbol>,
+    classes: &HashSet<Symbol>,
+    path_registry: &PathRegistry,
+) -> Result<()> {
+    let mut lines: Vec<String> = functions.iter().chain(classes.iter())
+        .map(|symbol| format!("{}\t{}\t{};\"\t{}",
+            symbol.name,
+            path_registry.get_path(symbol.context.file_path_index).display(),
+            symbol.context.line_number,
+            symbol.context.symbol_type.ctags_kind()))
+        .collect();
+    // Editors binary search the file, so lines must be in byte order
+    lines.sort();
+
+    writeln!(writer, "!_TAG_FILE_FORMAT\t2\t/extended format/")?;
+    writeln!(writer, "!_TAG_FILE_SORTED\t1\t/0=unsorted, 1=sorted, 2=foldcase/")?;
+    for line in lines {
+        writeln!(writer, "{}", line)?;
+    }
+    Ok(())
+}
+
+// One symbol as written by `write_symbol_json_line`
+#[derive(Serialize)]
+struct SymbolRecord<'a> {
+    name: &'a str,
+    kind: &'static str,
+    file: String,
+    line: usize,
+    module: String,
+}
+
+/// Write one symbol as a single-line JSON object (name, kind, file, line, module) followed by
+/// a newline, so huge dumps can be streamed through line-oriented tools
+pub fn write_symbol_json_line<W: Write>(writer: &mut W, symbol: &Symbol, path_registry: &PathRegistry) -> std::io::Result<()> {
+    let record = SymbolRecord {
+        name: &symbol.name,
+        kind: symbol.context.symbol_type.kind_name(),
+        file: path_registry.get_path(symbol.context.file_path_index).display().to_string(),
+        line: symbol.context.line_number,
+        module: symbol.context.module_path(),
+    };
+    serde_json::to_writer(&mut *writer, &record)?;
+    writeln!(writer)
+}
+
+pub fn save_ctags(path: &Path, stats: &SymbolStats) -> Result<()> {
+    let path_registry = stats.path_registry.lock().unwrap();
+    let functions = stats.functions.lock().unwrap();
+    let classes = stats.classes.lock().unwrap();
+
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(file);
+    write_ctags(&mut writer, &functions, &classes, &path_registry)?;
+    writer.flush()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
 
+    // Helper function to create a sample symbol
+    fn create_test_symbol(
+        name: &str,
+        kind: SymbolType,
+        line: usize,
+        file_index: usize,
+        module: &str,
+        fully_qualified_module: &str,
+    ) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            context: SymbolContext {
+                symbol_type: kind,
+                line_number: line,
+                end_line_number: line,
+                file_path_index: file_index,
+                module: module.to_string(),
+                fully_qualified_module: fully_qualified_module.to_string(),
+                ..Default::default()
+            },
+        }
+    }
+
     #[test]
     fn test_path_registry_register_and_get() {
         let mut registry = PathRegistry::new();
@@ -385,6 +1315,28 @@ mod tests {
         assert_eq!(index1, index3);
     }
 
+    #[test]
+    fn test_path_normalization_forms() {
+        let composed = PathBuf::from("/proj/caf\u{e9}/r\u{e9}sum\u{e9}.py");
+        let decomposed = PathBuf::from("/proj/cafe\u{301}/re\u{301}sume\u{301}.py");
+        assert_ne!(composed, decomposed);
+        assert_eq!(nfc_path(decomposed.clone()), composed);
+        assert_eq!(nfc_path(composed.clone()), composed);
+
+        let mut registry = PathRegistry::new();
+        let index = registry.register_path(decomposed.clone());
+        if cfg!(target_os = "macos") {
+            // Both forms open the same file, so they share an index
+            assert_eq!(registry.register_path(composed.clone()), index);
+            assert_eq!(registry.get_path(index), &composed);
+            assert_eq!(registry.index_of(&decomposed), Some(index));
+        } else {
+            // Both forms can exist side by side as different files
+            assert_ne!(registry.register_path(composed.clone()), index);
+            assert_eq!(registry.get_path(index), &decomposed);
+        }
+    }
+
     #[test]
     fn test_symbol_context_creation() {
         let mut path_registry = PathRegistry::new();
@@ -394,10 +1346,11 @@ mod tests {
         let context = SymbolContext {
             file_path_index,
             line_number: 42,
+            end_line_number: 42,
             module: "file".to_string(),
             fully_qualified_module: "module.file".to_string(),
             symbol_type: SymbolType::Function,
-            parent_context: vec![],
+            ..Default::default()
         };
         
         assert_eq!(context.line_number, 42);
@@ -416,10 +1369,11 @@ mod tests {
         let context = SymbolContext {
             file_path_index,
             line_number: 42,
+            end_line_number: 42,
             module: "file".to_string(),
             fully_qualified_module: "module.file".to_string(),
             symbol_type: SymbolType::Function,
-            parent_context: vec![],
+            ..Default::default()
         };
         
         let symbol = Symbol {
@@ -432,6 +1386,453 @@ mod tests {
         assert_eq!(*path_registry.get_path(symbol.context.file_path_index), PathBuf::from("/test/module/file.py"));
     }
 
+    #[test]
+    fn test_write_symbol_json_line() -> Result<()> {
+        let mut path_registry = PathRegistry::new();
+        let index = path_registry.register_path(PathBuf::from("pkg/shapes.py"));
+        let symbol = Symbol {
+            name: "area".to_string(),
+            context: SymbolContext {
+                file_path_index: index,
+                line_number: 12,
+                end_line_number: 14,
+                module: "shapes".to_string(),
+                fully_qualified_module: "pkg".to_string(),
+                symbol_type: SymbolType::Method,
+                snippet: Some("def area(self):\n    ...".to_string()),
+                ..Default::default()
+            },
+        };
+
+        let mut output = Vec::new();
+        write_symbol_json_line(&mut output, &symbol, &path_registry)?;
+        write_symbol_json_line(&mut output, &symbol, &path_registry)?;
+        let output = String::from_utf8(output)?;
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "Each symbol is exactly one line, even with multi-line fields elsewhere");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(lines[0])?,
+            serde_json::json!({ "name": "area", "kind": "method", "file": "pkg/shapes.py", "line": 12, "module": "pkg.shapes" })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_loading_a_non_index_file_explains_why() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let text = temp_dir.path().join("notes.txt");
+        std::fs::write(&text, "just some notes\n")?;
+        let error = load_symbol_data(&text).err().unwrap().to_string();
+        assert!(error.contains("not a valid pylight index file (expected gzip-compressed bincode)"), "{}", error);
+
+        let stats = SymbolStats::new();
+        stats.path_registry.lock().unwrap().register_path(PathBuf::from("pkg/mod.py"));
+        let index = temp_dir.path().join("symbols.bin");
+        save_symbols(&index, &stats)?;
+        let bytes = std::fs::read(&index)?;
+        std::fs::write(&index, &bytes[..bytes.len() / 2])?;
+        let error = load_symbol_data(&index).err().unwrap().to_string();
+        assert!(error.contains("not a valid pylight index file") && error.contains("truncated"), "{}", error);
+        Ok(())
+    }
+
+    // Saved by `collect_symbols_parallel --save` before indexes were versioned beyond V1, from
+    // a `/tmp/pysrc/pkg/app.py` holding `def main()` and `class Greeter` with a `greet` method
+    const BASELINE_V1_INDEX: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x7d, 0x8e,
+        0x51, 0x0a, 0x80, 0x20, 0x10, 0x44, 0x47, 0x2b, 0x3a, 0x41, 0xd7, 0xc8,
+        0x63, 0x74, 0x0e, 0x09, 0x91, 0x08, 0x63, 0x31, 0x7f, 0xbc, 0x7d, 0x4a,
+        0xbb, 0x20, 0x51, 0x0d, 0xc8, 0x5b, 0x9c, 0x19, 0x76, 0x01, 0x40, 0x95,
+        0xa7, 0x71, 0xab, 0x67, 0x06, 0xbb, 0x1d, 0x3c, 0x62, 0x60, 0x76, 0x4c,
+        0x4b, 0x24, 0x23, 0xed, 0x1e, 0x8d, 0x24, 0xe9, 0xa3, 0x73, 0x49, 0x3e,
+        0xf5, 0x6f, 0x5d, 0xf3, 0x01, 0x55, 0x23, 0x73, 0xa9, 0x75, 0x17, 0x55,
+        0x63, 0x7d, 0x44, 0xf0, 0xb0, 0xdf, 0x77, 0x88, 0xdb, 0x26, 0x27, 0xa6,
+        0x49, 0x81, 0x0c, 0xe5, 0x33, 0xae, 0xa6, 0x24, 0x4d, 0x29, 0xce, 0x94,
+        0x2f, 0x99, 0xd7, 0x58, 0x47, 0x16, 0x01, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_load_baseline_v1_index() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let index_path = temp_dir.path().join("symbols.idx");
+        std::fs::write(&index_path, BASELINE_V1_INDEX)?;
+
+        let data = load_symbol_data(&index_path)?;
+        assert_eq!(data.base_dir(), None);
+        assert_eq!(data.metadata().version, 1);
+        assert_eq!(data.metadata().symbols(), 3);
+        let (functions, classes, paths) = data.into_symbols();
+        assert_eq!(paths, vec![PathBuf::from("/tmp/pysrc/pkg/app.py")]);
+        assert_eq!(classes.len(), 1);
+        assert_eq!(classes[0].name, "Greeter");
+        assert_eq!(classes[0].context.module_path(), "pkg.app");
+
+        let mut functions: Vec<(String, SymbolType)> = functions.into_iter()
+            .map(|symbol| {
+                assert_eq!(symbol.context.end_line_number, symbol.context.line_number);
+                assert!(symbol.context.decorators.is_empty());
+                assert_eq!(symbol.context.name_start_byte, None);
+                assert_eq!(symbol.context.signature, None);
+                (symbol.name, symbol.context.symbol_type)
+            })
+            .collect();
+        functions.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(functions, vec![("greet".to_string(), SymbolType::Method), ("main".to_string(), SymbolType::Function)]);
+        Ok(())
+    }
+
+    // The same file indexed by the first build to save V2 indexes
+    const V2_INDEX: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x7d, 0x4f,
+        0xcb, 0x0a, 0x80, 0x30, 0x0c, 0x6b, 0x7d, 0x82, 0xe0, 0x51, 0xbc, 0xf8,
+        0x0d, 0xee, 0xe6, 0xc1, 0x1f, 0xf0, 0x3b, 0x44, 0x86, 0x88, 0x28, 0x65,
+        0x7a, 0xf1, 0xef, 0x75, 0xd2, 0xaa, 0x4c, 0x31, 0x30, 0x92, 0x91, 0x2d,
+        0x4d, 0x11, 0x00, 0x3c, 0x3e, 0x16, 0x21, 0x73, 0x6f, 0xb4, 0x5e, 0x59,
+        0x5f, 0xa6, 0xef, 0x70, 0x4b, 0x24, 0x92, 0xc6, 0xde, 0xbe, 0x42, 0xbe,
+        0xc6, 0xcc, 0x8d, 0x8d, 0xd1, 0x06, 0x1f, 0xd6, 0x0d, 0xcc, 0x45, 0x14,
+        0x2c, 0x02, 0xe6, 0xa9, 0x1d, 0x66, 0x70, 0x1a, 0x45, 0xbf, 0xc3, 0xe1,
+        0x13, 0x58, 0x89, 0xa8, 0x45, 0x7c, 0x17, 0x04, 0xc7, 0xfe, 0xdf, 0xf4,
+        0xbd, 0xca, 0xf9, 0x57, 0x1a, 0x62, 0xea, 0xa4, 0x65, 0xcc, 0x6a, 0x9d,
+        0x48, 0xd1, 0xb6, 0x98, 0x4e, 0x1d, 0x29, 0xea, 0x08, 0x2d, 0x69, 0xc3,
+        0xe4, 0xe5, 0xee, 0x00, 0x8a, 0x67, 0x1d, 0x95, 0x01, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_load_v2_index_as_untruncated() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let index_path = temp_dir.path().join("symbols.idx");
+        std::fs::write(&index_path, V2_INDEX)?;
+
+        let data = load_symbol_data(&index_path)?;
+        assert_eq!(data.base_dir(), Some(Path::new("/tmp/pysrc")));
+        assert_eq!(data.metadata().version, 2);
+        let (functions, classes, _) = data.into_symbols();
+        assert_eq!(classes[0].name, "Greeter");
+        assert_eq!(classes[0].context.end_line_number, 3);
+        let greet = functions.iter().find(|symbol| symbol.name == "greet").unwrap();
+        assert_eq!(greet.context.parent_context.len(), 1);
+        assert!(!greet.context.parents_truncated);
+        assert!(functions.iter().chain(&classes).all(|symbol| symbol.context.name_start_byte.is_some()));
+        assert!(functions.iter().chain(&classes).all(|symbol| symbol.context.signature.is_none()));
+        Ok(())
+    }
+
+    // The same file indexed by the first build to save V3 indexes
+    const V3_INDEX: &[u8] = &[
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0x8d, 0x8f,
+        0x4d, 0x0a, 0xc2, 0x30, 0x10, 0x85, 0x67, 0x52, 0x7f, 0x40, 0x70, 0x29,
+        0x6e, 0x3c, 0x40, 0xbb, 0x31, 0x3b, 0x17, 0x5e, 0xc0, 0x73, 0x14, 0x89,
+        0x45, 0xb4, 0x32, 0xa4, 0xdd, 0xf4, 0xf6, 0xa6, 0xf8, 0x26, 0xf6, 0x17,
+        0x1a, 0x08, 0xdf, 0xcb, 0xcc, 0xf0, 0xde, 0xc4, 0x10, 0x51, 0x12, 0xae,
+        0xa1, 0xdf, 0x59, 0x81, 0x65, 0xfe, 0xfc, 0x40, 0xd2, 0x1a, 0xdc, 0x80,
+        0x09, 0x98, 0x8b, 0xa8, 0x94, 0x57, 0x41, 0x93, 0x87, 0x2f, 0x2a, 0xae,
+        0xb1, 0xa4, 0x59, 0x69, 0xa6, 0xce, 0x85, 0x77, 0xae, 0xd6, 0xbe, 0x19,
+        0xc4, 0x4c, 0xc7, 0xb5, 0x53, 0x8c, 0xe7, 0x16, 0xbc, 0xb5, 0x36, 0xce,
+        0x73, 0xa7, 0xd5, 0xd9, 0xe4, 0xa8, 0xe2, 0x14, 0x4b, 0xfa, 0xa3, 0xb4,
+        0x72, 0xef, 0x47, 0x36, 0x63, 0x17, 0xa7, 0x17, 0xed, 0x35, 0x0e, 0xee,
+        0x25, 0xf1, 0xfe, 0x5f, 0x03, 0x0f, 0xa0, 0xad, 0x4b, 0xb1, 0xd2, 0x54,
+        0xfe, 0x6e, 0x83, 0x8f, 0x0d, 0xb6, 0x67, 0x69, 0x78, 0x37, 0xea, 0xc6,
+        0x84, 0x2f, 0x4b, 0xec, 0xbe, 0x66, 0xbc, 0x01, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn test_read_metadata_of_older_indexes() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let expected_counts = vec![(SymbolType::Class, 1), (SymbolType::Function, 1), (SymbolType::Method, 1)];
+        for (version, bytes, base_dir) in [
+            (1, BASELINE_V1_INDEX, None),
+            (2, V2_INDEX, Some(PathBuf::from("/tmp/pysrc"))),
+            (3, V3_INDEX, Some(PathBuf::from("/tmp/pysrc"))),
+        ] {
+            let index_path = temp_dir.path().join(format!("v{}.idx", version));
+            std::fs::write(&index_path, bytes)?;
+            let metadata = read_index_metadata(&index_path)?;
+            assert_eq!(metadata.version, version);
+            assert_eq!(metadata.created_at, None);
+            assert_eq!(metadata.base_dir, base_dir);
+            assert_eq!(metadata.files, 1);
+            assert_eq!(metadata.kind_counts, expected_counts);
+        }
+
+        let index_path = temp_dir.path().join("v3.idx");
+        let (functions, _, _) = load_symbol_data(&index_path)?.into_symbols();
+        let greet = functions.iter().find(|symbol| symbol.name == "greet").unwrap();
+        assert_eq!(greet.context.signature.as_deref(), Some("(self)"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_outdated_index_asks_for_a_rebuild() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let index_path = temp_dir.path().join("symbols.idx");
+        let file = temp_dir.path().join("models.py");
+        std::fs::write(&file, "class User:\n    def save(self, force: bool = False) -> None:\n        pass\n")?;
+        let stats = SymbolStats::new();
+        crate::python::parse_python_files_parallel(&[file], temp_dir.path(), &stats)?;
+        save_symbols(&index_path, &stats)?;
+
+        // A V2 index written with the current symbol layout, as builds between V2 and V3 did
+        let (functions, classes, paths) = load_symbol_data(&index_path)?.into_symbols();
+        let has_main = vec![false; paths.len()];
+        let outdated = SymbolData::V3(SymbolDataV3 { version: 2, functions, classes, paths, base_dir: None, has_main });
+        let mut bytes = bincode::serialize(&outdated)?;
+        bytes[0] = 1;
+        std::fs::write(&index_path, &bytes)?;
+
+        for error in [load_symbol_data(&index_path).err().unwrap(), read_index_metadata(&index_path).err().unwrap()] {
+            let error = error.to_string();
+            assert!(error.contains("V2 pylight index in an outdated format") && error.contains("rebuild it"), "{}", error);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_with_wrapped_gzip_size_loads() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let index_path = temp_dir.path().join("symbols.idx");
+        let file = temp_dir.path().join("models.py");
+        std::fs::write(&file, "class User:\n    def save(self):\n        pass\n")?;
+        let stats = SymbolStats::new();
+        crate::python::parse_python_files_parallel(&[file], temp_dir.path(), &stats)?;
+        save_symbols(&index_path, &stats)?;
+
+        // The gzip trailer holds the size modulo 4 GiB, so an index of exactly 4 GiB records 0
+        let mut bytes = std::fs::read(&index_path)?;
+        let size_at = bytes.len() - 4;
+        bytes[size_at..].copy_from_slice(&0u32.to_le_bytes());
+        std::fs::write(&index_path, &bytes)?;
+
+        let (functions, classes, _) = load_symbol_data(&index_path)?.into_symbols();
+        assert_eq!(functions.len(), 1);
+        assert_eq!(classes.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_index_metadata_round_trip() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let base_dir = temp_dir.path().join("project");
+        std::fs::create_dir_all(&base_dir)?;
+        let file = base_dir.join("models.py");
+        std::fs::write(&file, "class User:\n    def save(self):\n        pass\n\n    def delete(self):\n        pass\n\ndef helper():\n    pass\n")?;
+        let stats = SymbolStats::new();
+        crate::python::parse_python_files_parallel(&[file], &base_dir, &stats)?;
+
+        let index_path = temp_dir.path().join("index.bin");
+        save_symbols_with_base_dir(&index_path, &stats, IndexCompression::None, Some(&base_dir))?;
+        let metadata = read_index_metadata(&index_path)?;
+        assert_eq!(metadata.version, 4);
+        assert!(metadata.created_at.is_some());
+        assert_eq!(metadata.base_dir.as_deref(), Some(base_dir.as_path()));
+        assert_eq!(metadata.files, 1);
+        assert_eq!(metadata.kind_counts, vec![(SymbolType::Method, 2), (SymbolType::Class, 1), (SymbolType::Function, 1)]);
+        assert_eq!(metadata.symbols(), 4);
+        assert_eq!(load_symbol_data(&index_path)?.metadata(), metadata);
+
+        // Only the header is read, so a file cut off in the symbols still reports it
+        let bytes = std::fs::read(&index_path)?;
+        std::fs::write(&index_path, &bytes[..bytes.len() - 20])?;
+        assert!(load_symbol_data(&index_path).is_err());
+        assert_eq!(read_index_metadata(&index_path)?, metadata);
+
+        // Compressed indexes, and older versions counted from their symbols
+        let gzipped = temp_dir.path().join("index.idx");
+        save_symbols_with_base_dir(&gzipped, &stats, IndexCompression::Gzip, Some(&base_dir))?;
+        assert_eq!(read_index_metadata(&gzipped)?.kind_counts, metadata.kind_counts);
+        let (functions, classes, paths) = load_symbol_data(&gzipped)?.into_symbols();
+        let has_main = vec![false; paths.len()];
+        let old = SymbolData::V3(SymbolDataV3 { version: 3, functions, classes, paths, base_dir: None, has_main });
+        write_symbol_data(&gzipped, &old, IndexCompression::Gzip)?;
+        let old_metadata = read_index_metadata(&gzipped)?;
+        assert_eq!(old_metadata.version, 3);
+        assert_eq!(old_metadata.created_at, None);
+        assert_eq!(old_metadata.kind_counts, metadata.kind_counts);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rebase_saved_index() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let old_root = temp_dir.path().join("checkout");
+        std::fs::create_dir_all(old_root.join("pkg"))?;
+        let file = old_root.join("pkg").join("mod.py");
+        std::fs::write(&file, "def relocated():\n    pass\n")?;
+
+        let stats = SymbolStats::new();
+        crate::python::parse_python_files_parallel(&[file], &old_root, &stats)?;
+        let index_path = temp_dir.path().join("index.bin");
+        save_symbols_with_base_dir(&index_path, &stats, IndexCompression::None, Some(&old_root))?;
+
+        // Move the project; the stored paths no longer exist
+        let new_root = temp_dir.path().join("moved");
+        std::fs::rename(&old_root, &new_root)?;
+
+        let data = load_symbol_data(&index_path)?;
+        assert_eq!(data.base_dir(), Some(old_root.as_path()));
+        let stored_root = data.base_dir().unwrap().to_path_buf();
+        let (functions, _, paths) = data.into_symbols();
+        assert!(!paths[0].exists());
+
+        let mut path_registry = PathRegistry::new();
+        for path in rebase_paths(paths, &stored_root, &new_root) {
+            path_registry.register_path(path);
+        }
+        let symbol = &functions[0];
+        assert_eq!(symbol.context.module_path(), "pkg.mod", "Module names do not depend on the root");
+        let uri = crate::lsp::symbol_uri(symbol, &path_registry).expect("rebased path should form a URI");
+        let resolved = url::Url::parse(uri.as_str())?.to_file_path().unwrap();
+        assert_eq!(resolved, new_root.join("pkg").join("mod.py"));
+        assert!(resolved.exists());
+
+        // Paths outside the old root are left alone
+        let outside = rebase_paths(vec![PathBuf::from("/elsewhere/x.py")], &stored_root, &new_root);
+        assert_eq!(outside, vec![PathBuf::from("/elsewhere/x.py")]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_save_and_load_both_compressions() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let stats = SymbolStats::new();
+        let file_path_index = stats.path_registry.lock().unwrap().register_path(PathBuf::from("/test/module/file.py"));
+        stats.functions.lock().unwrap().insert(Symbol {
+            name: "saved_function".to_string(),
+            context: SymbolContext {
+                file_path_index,
+                line_number: 3,
+                end_line_number: 3,
+                module: "file".to_string(),
+                fully_qualified_module: "module".to_string(),
+                symbol_type: SymbolType::Function,
+                ..Default::default()
+            },
+        });
+
+        for compression in [IndexCompression::Gzip, IndexCompression::None] {
+            let path = temp_dir.path().join(format!("symbols_{:?}", compression));
+            save_symbols_with_compression(&path, &stats, compression)?;
+
+            let is_gzip = std::fs::read(&path)?.starts_with(&GZIP_MAGIC);
+            assert_eq!(is_gzip, compression == IndexCompression::Gzip);
+
+            let (functions, classes, paths) = load_symbol_data(&path)?.into_symbols();
+            assert_eq!(functions.len(), 1);
+            assert_eq!(functions[0].name, "saved_function");
+            assert!(classes.is_empty());
+            assert_eq!(paths, vec![PathBuf::from("/test/module/file.py")]);
+        }
+
+        assert_eq!(IndexCompression::from_path(Path::new("index.bin")), IndexCompression::None);
+        assert_eq!(IndexCompression::from_path(Path::new("index.gz")), IndexCompression::Gzip);
+        assert_eq!(IndexCompression::from_path(Path::new("index")), IndexCompression::Gzip);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_ctags() -> Result<()> {
+        let mut path_registry = PathRegistry::new();
+        let a = path_registry.register_path(PathBuf::from("pkg/a.py"));
+        let b = path_registry.register_path(PathBuf::from("pkg/b.py"));
+        let functions: HashSet<Symbol> = [
+            create_test_symbol("save", SymbolType::Method, 10, a, "a", "pkg"),
+            create_test_symbol("load", SymbolType::Function, 3, b, "a", "pkg"),
+        ].into_iter().collect();
+        let classes: HashSet<Symbol> = [
+            create_test_symbol("Store", SymbolType::Class, 5, a, "a", "pkg"),
+        ].into_iter().collect();
+
+        let mut output = Vec::new();
+        write_ctags(&mut output, &functions, &classes, &path_registry)?;
+        let output = String::from_utf8(output)?;
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("!_TAG_FILE_FORMAT\t2"));
+        assert!(lines[1].starts_with("!_TAG_FILE_SORTED\t1"));
+        assert_eq!(&lines[2..], [
+            "Store\tpkg/a.py\t5;\"\tc",
+            "load\tpkg/b.py\t3;\"\tf",
+            "save\tpkg/a.py\t10;\"\tm",
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_symbol_sets_match_sequential_collect() {
+        let functions: Vec<Symbol> = (0..1000)
+            .map(|i| create_test_symbol(&format!("f{}", i % 700), SymbolType::Function, i % 700, 0, "a", "pkg"))
+            .collect();
+        let classes = vec![
+            create_test_symbol("A", SymbolType::Class, 1, 0, "a", "pkg"),
+            create_test_symbol("A", SymbolType::Class, 1, 0, "a", "pkg"),
+            create_test_symbol("B", SymbolType::Class, 2, 0, "a", "pkg"),
+        ];
+
+        let (function_set, class_set) = symbol_sets(functions.clone(), classes.clone());
+        assert_eq!(function_set, functions.into_iter().collect::<HashSet<_>>());
+        assert_eq!(function_set.len(), 700);
+        assert_eq!(class_set, classes.into_iter().collect::<HashSet<_>>());
+        assert_eq!(class_set.len(), 2);
+    }
+
+    #[test]
+    fn test_list_modules() {
+        let mut path_registry = PathRegistry::new();
+        let a = path_registry.register_path(PathBuf::from("/base/pkg/a.py"));
+        let b = path_registry.register_path(PathBuf::from("/base/pkg/sub/b.py"));
+        let root = path_registry.register_path(PathBuf::from("/base/root.py"));
+
+        let functions: HashSet<Symbol> = [
+            create_test_symbol("f1", SymbolType::Function, 1, a, "a", "pkg"),
+            create_test_symbol("f2", SymbolType::Function, 1, a, "a", "pkg"),
+            create_test_symbol("g", SymbolType::Function, 1, root, "root", ""),
+        ].into_iter().collect();
+        let classes: HashSet<Symbol> = [
+            create_test_symbol("B", SymbolType::Class, 1, b, "b", "pkg.sub"),
+        ].into_iter().collect();
+
+        let modules = list_modules(&functions, &classes, &path_registry);
+        assert_eq!(modules, vec![
+            ("pkg.a".to_string(), PathBuf::from("/base/pkg/a.py")),
+            ("pkg.sub.b".to_string(), PathBuf::from("/base/pkg/sub/b.py")),
+            ("root".to_string(), PathBuf::from("/base/root.py")),
+        ]);
+
+        assert_eq!(summarize_index(&functions, &classes, &path_registry), IndexSummary {
+            functions: 3,
+            classes: 1,
+            files: 3,
+            modules: 3,
+        });
+    }
+
+    #[test]
+    fn test_find_duplicate_names() {
+        let mut path_registry = PathRegistry::new();
+        let a = path_registry.register_path(PathBuf::from("/base/a.py"));
+        let b = path_registry.register_path(PathBuf::from("/base/b.py"));
+        let c = path_registry.register_path(PathBuf::from("/base/c.py"));
+
+        let functions: HashSet<Symbol> = [
+            create_test_symbol("setup", SymbolType::Function, 3, c, "m", ""),
+            create_test_symbol("setup", SymbolType::Function, 7, a, "m", ""),
+            create_test_symbol("setup", SymbolType::Function, 1, b, "m", ""),
+            create_test_symbol("helper", SymbolType::Function, 1, a, "m", ""),
+            create_test_symbol("helper", SymbolType::Function, 9, b, "m", ""),
+            create_test_symbol("unique", SymbolType::Function, 1, c, "m", ""),
+        ].into_iter().collect();
+        let classes: HashSet<Symbol> = [create_test_symbol("Helper", SymbolType::Class, 5, c, "m", "")].into_iter().collect();
+
+        let duplicates = find_duplicate_names(&functions, &classes, &path_registry, 1);
+        let summary: Vec<(&str, Vec<usize>)> = duplicates.iter()
+            .map(|(name, defs)| (name.as_str(), defs.iter().map(|s| s.context.file_path_index).collect()))
+            .collect();
+        assert_eq!(summary, vec![("setup", vec![a, b, c]), ("helper", vec![a, b])]);
+
+        let duplicates = find_duplicate_names(&functions, &classes, &path_registry, 2);
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates[0].0, "setup");
+    }
+
     #[test]
     fn test_get_module_name() {
         assert_eq!(get_module_name(Path::new("/path/to/module.py")), "module");
@@ -459,4 +1860,64 @@ mod tests {
             "unknown"
         );
     }
+
+    #[test]
+    fn test_package_module_requires_init() -> Result<()> {
+        let temp_dir = tempfile::tempdir()?;
+        let base_dir = temp_dir.path();
+        // scripts/ is a plain directory holding a regular package
+        let package = base_dir.join("scripts").join("tool");
+        std::fs::create_dir_all(package.join("plain"))?;
+        File::create(package.join("__init__.py"))?;
+
+        let module = package.join("cli.py");
+        assert_eq!(get_fully_qualified_module(&module, base_dir), "scripts.tool");
+        assert_eq!(get_package_module(&module, base_dir), "tool");
+
+        // Without an __init__.py the file's own directory is not a package
+        let stray = package.join("plain").join("helper.py");
+        assert_eq!(get_fully_qualified_module(&stray, base_dir), "scripts.tool.plain");
+        assert_eq!(get_package_module(&stray, base_dir), "");
+
+        assert_eq!(get_package_module(Path::new("/elsewhere/x.py"), base_dir), "unknown");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fully_qualified_module_with_source_roots() {
+        let base_dir = Path::new("/repo");
+        let roots = vec![PathBuf::from("src"), PathBuf::from("/repo/tools/lib")];
+
+        // A src layout names modules as they are imported
+        assert_eq!(
+            get_fully_qualified_module_with_roots(Path::new("/repo/src/package/sub/module.py"), base_dir, &roots),
+            "package.sub"
+        );
+        assert_eq!(
+            get_fully_qualified_module_with_roots(Path::new("/repo/src/top.py"), base_dir, &roots),
+            ""
+        );
+        assert_eq!(
+            get_fully_qualified_module_with_roots(Path::new("/repo/tools/lib/helpers/io.py"), base_dir, &roots),
+            "helpers"
+        );
+
+        // Files outside every root are still named from the base directory
+        assert_eq!(
+            get_fully_qualified_module_with_roots(Path::new("/repo/tests/unit/test_x.py"), base_dir, &roots),
+            "tests.unit"
+        );
+        // A directory that merely starts with a root's name is not under it
+        assert_eq!(
+            get_fully_qualified_module_with_roots(Path::new("/repo/srcgen/module.py"), base_dir, &roots),
+            "srcgen"
+        );
+
+        // The deepest matching root wins
+        let nested = vec![PathBuf::from("src"), PathBuf::from("src/vendor")];
+        assert_eq!(
+            get_fully_qualified_module_with_roots(Path::new("/repo/src/vendor/six/moves.py"), base_dir, &nested),
+            "six"
+        );
+    }
 } 
\ No newline at end of file